@@ -0,0 +1,77 @@
+//! Chip 8 benchmark ROMs.
+//! (C) Ryan Jeffrey <ryan@ryanmj.xyz>, 2022
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or (at
+// your option) any later version.
+
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use chip8::{InterpreterData, Instruction, convert_bin_format, convert_program};
+use chip8::machine::Machine;
+
+/// An ALU loop: straight line arithmetic followed by a jump back.
+pub const ALU_LOOP: [u8; 24] = [
+    0x60, 0x01, // 0x200: LD V0, 0x01
+    0x61, 0x03, // 0x202: LD V1, 0x03
+    0x80, 0x14, // 0x204: ADD V0, V1
+    0x81, 0x03, // 0x206: XOR V1, V0
+    0x82, 0x0E, // 0x208: SHL V2, V0
+    0x83, 0x25, // 0x20A: SUB V3, V2
+    0x84, 0x31, // 0x20C: OR V4, V3
+    0x85, 0x42, // 0x20E: AND V5, V4
+    0x70, 0x07, // 0x210: ADD V0, 0x07
+    0x86, 0x56, // 0x212: SHR V6, V5
+    0xF0, 0x1E, // 0x214: ADD I, V0
+    0x12, 0x04, // 0x216: JP 0x204
+];
+
+/// Draws the font across the screen, mostly `Drw`.
+pub const DRAW_LOOP: [u8; 22] = [
+    0x60, 0x00, // 0x200: LD V0, 0x00
+    0x61, 0x00, // 0x202: LD V1, 0x00
+    0x62, 0x00, // 0x204: LD V2, 0x00
+    0xF2, 0x29, // 0x206: LD F, V2
+    0xD0, 0x15, // 0x208: DRW V0, V1, 5
+    0x70, 0x05, // 0x20A: ADD V0, 0x05
+    0x71, 0x01, // 0x20C: ADD V1, 0x01
+    0x72, 0x01, // 0x20E: ADD V2, 0x01
+    0x63, 0x0F, // 0x210: LD V3, 0x0F
+    0x82, 0x32, // 0x212: AND V2, V3
+    0x12, 0x06, // 0x214: JP 0x206
+];
+
+/// Copies memory ten bytes at a time with `LdIRM` and `LdIR`.
+pub const COPY_LOOP: [u8; 20] = [
+    0x6A, 0x00, // 0x200: LD VA, 0x00
+    0x6B, 0x0A, // 0x202: LD VB, 0x0A
+    0xA3, 0x00, // 0x204: LD I, 0x300
+    0xFA, 0x1E, // 0x206: ADD I, VA
+    0xF9, 0x65, // 0x208: LD V9, [I]
+    0xA5, 0x00, // 0x20A: LD I, 0x500
+    0xFA, 0x1E, // 0x20C: ADD I, VA
+    0xF9, 0x55, // 0x20E: LD [I], V9
+    0x8A, 0xB4, // 0x210: ADD VA, VB
+    0x12, 0x04, // 0x212: JP 0x204
+];
+
+/// Convert ROM bytes into a program and an emulator state with the font and
+/// ROM loaded.
+/// # Arguments
+/// * `rom` The ROM bytes.
+pub fn load(rom: &[u8]) -> (Vec<Instruction>, InterpreterData) {
+    let machine = Machine::default();
+    let program = match convert_bin_format(rom).and_then(|d| convert_program(&d, &machine)) {
+        Ok(p) => p,
+        Err(e) => panic!("{}", e),
+    };
+    let mut emu_state = InterpreterData::with_machine(machine);
+    emu_state.load(rom);
+    (program, emu_state)
+}
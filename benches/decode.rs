@@ -0,0 +1,41 @@
+//! Chip 8 instruction decoding benchmarks.
+//! (C) Ryan Jeffrey <ryan@ryanmj.xyz>, 2022
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or (at
+// your option) any later version.
+
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+extern crate chip8;
+#[macro_use]
+extern crate criterion;
+
+use chip8::program_to_enum;
+use criterion::{Criterion, Throughput, black_box};
+
+fn bench_decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode");
+    group.throughput(Throughput::Elements(0x10000));
+
+    // Every possible opcode, valid or not.
+    group.bench_function("program_to_enum", |b| b.iter(|| {
+        let mut valid = 0;
+        for opcode in 0..=0xFFFFu16 {
+            if program_to_enum(black_box(opcode)).is_ok() {
+                valid += 1;
+            }
+        }
+        black_box(valid)
+    }));
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_decode);
+criterion_main!(benches);
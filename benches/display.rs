@@ -0,0 +1,48 @@
+//! Chip 8 display conversion benchmarks.
+//! (C) Ryan Jeffrey <ryan@ryanmj.xyz>, 2022
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or (at
+// your option) any later version.
+
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+extern crate chip8;
+#[macro_use]
+extern crate criterion;
+
+use chip8::{NUM_COLS, NUM_ROWS};
+use chip8::display::{FRAME_BYTES, screen_to_rgb};
+use chip8::framebuffer::Framebuffer;
+use chip8::palette::Palette;
+use criterion::{Criterion, Throughput, black_box};
+
+fn bench_display(c: &mut Criterion) {
+    let mut group = c.benchmark_group("display");
+    group.throughput(Throughput::Elements((NUM_COLS * NUM_ROWS) as u64));
+
+    // A checkerboard, so neither colour dominates.
+    let mut screen = Framebuffer::new(NUM_COLS, NUM_ROWS, 1);
+    for y in 0..NUM_ROWS {
+        for x in 0..NUM_COLS {
+            screen.set(0, x, y, (x + y) % 2 == 0);
+        }
+    }
+    let mut pixels = vec![0u8; FRAME_BYTES];
+    let palette = Palette::default();
+
+    group.bench_function("screen_to_rgb", |b| b.iter(|| {
+        screen_to_rgb(black_box(&screen), &palette, &mut pixels);
+        black_box(pixels[0])
+    }));
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_display);
+criterion_main!(benches);
@@ -0,0 +1,48 @@
+//! Chip 8 interpreter benchmarks, instructions per second of `emulate`.
+//! (C) Ryan Jeffrey <ryan@ryanmj.xyz>, 2022
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or (at
+// your option) any later version.
+
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+extern crate chip8;
+#[macro_use]
+extern crate criterion;
+
+mod common;
+
+use chip8::emulate;
+use criterion::{Criterion, Throughput, black_box};
+
+/// Instructions run per iteration.
+const INSTRUCTIONS: usize = 100_000;
+
+fn bench_emulate(c: &mut Criterion) {
+    let keys = [false; 0x10];
+    let mut group = c.benchmark_group("emulate");
+    group.throughput(Throughput::Elements(INSTRUCTIONS as u64));
+
+    for (name, rom) in [("alu_loop", &common::ALU_LOOP[..]),
+                        ("draw_loop", &common::DRAW_LOOP[..]),
+                        ("copy_loop", &common::COPY_LOOP[..])].iter() {
+        group.bench_function(*name, |b| b.iter(|| {
+            let (program, mut emu_state) = common::load(rom);
+            for _ in 0..INSTRUCTIONS {
+                emulate(&program, &mut emu_state, &keys);
+            }
+            black_box(emu_state.v)
+        }));
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_emulate);
+criterion_main!(benches);
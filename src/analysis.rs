@@ -0,0 +1,337 @@
+//! Chip 8 static control flow analysis.
+//! (C) Ryan Jeffrey <ryan@ryanmj.xyz>, 2022
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or (at
+// your option) any later version.
+
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Range;
+
+use super::{Instruction, program_to_enum, get_last_3_nibbles};
+use disasm::mnemonic;
+
+/// How control leaves a basic block.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum Exit {
+    /// Execution continues into the next block, which is a branch target.
+    FallThrough,
+    /// Unconditional jump.
+    Jump,
+    /// Subroutine call, execution resumes after it on return.
+    Call,
+    /// One of the skip instructions, the next instruction may be skipped.
+    Skip,
+    /// Return from the current subroutine.
+    Return,
+    /// Jump to an address plus V0, the target is not known statically.
+    Indirect,
+    /// Execution reaches a word that is not a valid instruction.
+    Invalid,
+    /// Execution runs off the end of the ROM.
+    End,
+}
+
+/// The kind of a control flow edge.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum EdgeKind {
+    /// Execution continues with the next instruction.
+    FallThrough,
+    /// Target of a jump.
+    Jump,
+    /// Target of a subroutine call.
+    Call,
+    /// The instruction after a taken skip.
+    Skip,
+}
+
+/// A control flow edge to the block starting at `target`.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub struct Edge {
+    /// Address of the target.
+    pub target: u16,
+    /// How control gets there.
+    pub kind: EdgeKind,
+}
+
+/// A straight line run of instructions with a single entry and exit.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct BasicBlock {
+    /// Address of the first instruction.
+    pub start: u16,
+    /// The address and raw opcode of every instruction in the block.
+    pub instructions: Vec<(u16, u16)>,
+    /// How control leaves the block.
+    pub exit: Exit,
+    /// Blocks control may go to after this one.
+    pub successors: Vec<Edge>,
+}
+
+impl BasicBlock {
+    /// Return the address one past the last instruction of the block.
+    pub fn end(&self) -> u16 {
+        self.start + 2 * self.instructions.len() as u16
+    }
+}
+
+/// Control flow graph of a ROM, separating reachable code from data.
+pub struct ControlFlowGraph {
+    /// Address the ROM is loaded at.
+    origin: u16,
+    /// Basic blocks keyed by their start address.
+    blocks: BTreeMap<u16, BasicBlock>,
+    /// Every reachable instruction, keyed by address.
+    instructions: BTreeMap<u16, u16>,
+    /// Per ROM byte, whether it belongs to a reachable instruction.
+    code: Vec<bool>,
+}
+
+/// Read the opcode at `address`, return None if it is outside of the ROM.
+/// # Arguments
+/// * `rom` The ROM bytes.
+/// * `origin` Address the ROM is loaded at.
+/// * `address` Address of the opcode.
+fn fetch(rom: &[u8], origin: u16, address: u16) -> Option<u16> {
+    if address < origin {
+        return None;
+    }
+    let offset = (address - origin) as usize;
+    if offset + 1 < rom.len() {
+        Some((rom[offset] as u16) << 8 | rom[offset + 1] as u16)
+    } else {
+        None
+    }
+}
+
+/// Return how the instruction at `address` changes control flow, or None
+/// if execution simply continues with the next instruction.
+/// # Arguments
+/// * `address` Address of the instruction.
+/// * `instruction` The decoded instruction.
+/// * `opcode` Raw opcode of the instruction.
+fn control_flow(address: u16, instruction: Instruction,
+                opcode: u16) -> Option<(Exit, Vec<Edge>)> {
+    type I = Instruction;
+    let next = address.wrapping_add(2);
+    let edge = |target, kind| Edge { target, kind };
+
+    match instruction {
+        I::Jp(..) => {
+            Some((Exit::Jump, vec![edge(get_last_3_nibbles(opcode), EdgeKind::Jump)]))
+        },
+        I::Call(..) => {
+            Some((Exit::Call, vec![edge(get_last_3_nibbles(opcode), EdgeKind::Call),
+                                   edge(next, EdgeKind::FallThrough)]))
+        },
+        I::Se(..) | I::Sne(..) | I::SeR(..) | I::SneR(..) | I::Skp(..) | I::SkpN(..) => {
+            Some((Exit::Skip, vec![edge(next, EdgeKind::FallThrough),
+                                   edge(next.wrapping_add(2), EdgeKind::Skip)]))
+        },
+        I::Ret => Some((Exit::Return, Vec::new())),
+        I::JpI(..) => Some((Exit::Indirect, Vec::new())),
+        _ => None,
+    }
+}
+
+/// Recursively follow control flow from `origin` and build the control
+/// flow graph of `rom`. Everything that cannot be reached is data.
+/// # Arguments
+/// * `rom` The ROM bytes.
+/// * `origin` Address the ROM is loaded at, analysis starts here.
+pub fn analyse(rom: &[u8], origin: u16) -> ControlFlowGraph {
+    let mut instructions: BTreeMap<u16, u16> = BTreeMap::new();
+    let mut leaders: BTreeSet<u16> = BTreeSet::new();
+    let mut worklist = vec![origin];
+    leaders.insert(origin);
+
+    // Discover every reachable instruction and every block leader.
+    while let Some(start) = worklist.pop() {
+        let mut address = start;
+        loop {
+            if instructions.contains_key(&address) {
+                // Ran into code that was already decoded, this is a join.
+                leaders.insert(address);
+                break;
+            }
+            let opcode = match fetch(rom, origin, address) {
+                Some(o) => o,
+                None => break,
+            };
+            let instruction = match program_to_enum(opcode) {
+                Ok(i) => i,
+                Err(_) => break,
+            };
+            instructions.insert(address, opcode);
+
+            if let Some((_, edges)) = control_flow(address, instruction, opcode) {
+                for e in edges {
+                    if leaders.insert(e.target) {
+                        worklist.push(e.target);
+                    }
+                }
+                break;
+            }
+            address = address.wrapping_add(2);
+        }
+    }
+
+    // Split the instructions into blocks at the leaders.
+    let mut blocks = BTreeMap::new();
+    for &leader in leaders.iter().filter(|l| instructions.contains_key(l)) {
+        let mut block = BasicBlock {
+            start: leader,
+            instructions: Vec::new(),
+            exit: Exit::End,
+            successors: Vec::new(),
+        };
+        let mut address = leader;
+        loop {
+            let opcode = instructions[&address];
+            block.instructions.push((address, opcode));
+            if let Ok(instruction) = program_to_enum(opcode) {
+                if let Some((exit, edges)) = control_flow(address, instruction, opcode) {
+                    block.exit = exit;
+                    block.successors = edges;
+                    break;
+                }
+            }
+
+            let next = address.wrapping_add(2);
+            if leaders.contains(&next) && instructions.contains_key(&next) {
+                block.exit = Exit::FallThrough;
+                block.successors.push(Edge { target: next, kind: EdgeKind::FallThrough });
+                break;
+            } else if !instructions.contains_key(&next) {
+                block.exit = match fetch(rom, origin, next) {
+                    Some(_) => Exit::Invalid,
+                    None => Exit::End,
+                };
+                break;
+            }
+            address = next;
+        }
+        blocks.insert(leader, block);
+    }
+
+    let mut code = vec![false; rom.len()];
+    for &address in instructions.keys() {
+        let offset = (address - origin) as usize;
+        code[offset] = true;
+        code[offset + 1] = true;
+    }
+
+    ControlFlowGraph {
+        origin,
+        blocks,
+        instructions,
+        code,
+    }
+}
+
+impl ControlFlowGraph {
+    /// Return the address the analysed ROM is loaded at.
+    pub fn origin(&self) -> u16 {
+        self.origin
+    }
+
+    /// Iterate over the basic blocks in address order.
+    pub fn blocks(&self) -> impl Iterator<Item = &BasicBlock> {
+        self.blocks.values()
+    }
+
+    /// Return the block starting at `address`.
+    /// # Arguments
+    /// * `address` Start address of the block.
+    pub fn block(&self, address: u16) -> Option<&BasicBlock> {
+        self.blocks.get(&address)
+    }
+
+    /// Return the block that contains the instruction at `address`.
+    /// # Arguments
+    /// * `address` Address of an instruction.
+    pub fn block_containing(&self, address: u16) -> Option<&BasicBlock> {
+        self.blocks.range(..=address).rev()
+            .find(|(_, b)| b.instructions.iter().any(|&(a, _)| a == address))
+            .map(|(_, b)| b)
+    }
+
+    /// Return the opcode of the reachable instruction starting at `address`.
+    /// # Arguments
+    /// * `address` Address of the instruction.
+    pub fn instruction_at(&self, address: u16) -> Option<u16> {
+        self.instructions.get(&address).copied()
+    }
+
+    /// Return true if the byte at `address` is part of reachable code.
+    /// # Arguments
+    /// * `address` Address of the byte.
+    pub fn is_code(&self, address: u16) -> bool {
+        address >= self.origin &&
+            self.code.get((address - self.origin) as usize).copied().unwrap_or(false)
+    }
+
+    /// Return the address ranges of the ROM that were never reached.
+    pub fn data_ranges(&self) -> Vec<Range<u16>> {
+        let mut result: Vec<Range<u16>> = Vec::new();
+        for (offset, _) in self.code.iter().enumerate().filter(|(_, c)| !**c) {
+            let address = self.origin + offset as u16;
+            match result.last_mut() {
+                Some(r) if r.end == address => r.end += 1,
+                _ => result.push(address..address + 1),
+            }
+        }
+        result
+    }
+
+    /// Return the addresses of every computed jump.
+    pub fn indirect_jumps(&self) -> Vec<u16> {
+        self.blocks.values()
+            .filter(|b| b.exit == Exit::Indirect)
+            .filter_map(|b| b.instructions.last().map(|&(a, _)| a))
+            .collect()
+    }
+
+    /// Export the graph in Graphviz DOT format.
+    pub fn to_dot(&self) -> String {
+        let mut result = String::from("digraph chip8 {\n");
+        result.push_str("    node [shape=box, fontname=\"monospace\"];\n");
+
+        for block in self.blocks.values() {
+            let mut label = String::new();
+            for &(address, opcode) in &block.instructions {
+                label.push_str(&format!("0x{:03X}: {}\\l", address, mnemonic(opcode)));
+            }
+            let style = match block.exit {
+                Exit::Indirect => ", color=red, xlabel=\"indirect\"",
+                Exit::Invalid | Exit::End => ", color=orange",
+                _ => "",
+            };
+            result.push_str(&format!("    \"0x{:03X}\" [label=\"{}\"{}];\n",
+                                     block.start, label, style));
+        }
+
+        for block in self.blocks.values() {
+            for e in &block.successors {
+                let style = match e.kind {
+                    EdgeKind::FallThrough => "",
+                    EdgeKind::Jump => " [label=\"jump\"]",
+                    EdgeKind::Call => " [label=\"call\", style=bold]",
+                    EdgeKind::Skip => " [label=\"skip\", style=dashed]",
+                };
+                result.push_str(&format!("    \"0x{:03X}\" -> \"0x{:03X}\"{};\n",
+                                         block.start, e.target, style));
+            }
+        }
+
+        result.push_str("}\n");
+        result
+    }
+}
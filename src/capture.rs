@@ -0,0 +1,63 @@
+//! Chip 8 screenshots and recordings.
+//! (C) Ryan Jeffrey <ryan@ryanmj.xyz>, 2022
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or (at
+// your option) any later version.
+
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use std::fs;
+use std::path::Path;
+
+use chip8::framebuffer::Framebuffer;
+use chip8::image::screenshot;
+use chip8::options::Options;
+use chip8::palette::Palette;
+use chip8::recording::Recorder;
+
+/// Return the path of a file saved at `frame`, named after the ROM, in the
+/// current directory, e.g. `pong-120.png`.
+/// # Arguments
+/// * `rom` Path of the ROM file.
+/// * `frame` Frame number.
+/// * `extension` File extension.
+pub fn capture_path(rom: &str, frame: u64, extension: &str) -> String {
+    let stem = Path::new(rom).file_stem().map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| String::from("chip8"));
+    format!("{}-{}.{}", stem, frame, extension)
+}
+
+/// Save a screenshot of the screen as PNG, with the scale and filters from
+/// the options. Return string on error.
+/// # Arguments
+/// * `screen` The emulator screen.
+/// * `palette` Colours of the screen.
+/// * `options` Command line options.
+/// * `frame` Frame number, used in the file name.
+pub fn save_screenshot(screen: &Framebuffer, palette: &Palette,
+                       options: &Options, frame: u64) -> Result<(), String> {
+    let path = capture_path(options.rom.as_deref().unwrap_or("chip8"), frame, "png");
+    let png = screenshot(screen, palette, &options.upscaler, options.image_scale)?;
+    fs::write(&path, png).map_err(|e| format!("{}: {}", path, e))?;
+    println!("Saved {}.", path);
+    Ok(())
+}
+
+/// Save a recording as an animated GIF. Return string on error.
+/// # Arguments
+/// * `recorder` The recording.
+/// * `options` Command line options.
+/// * `frame` Frame the recording started at, used in the file name.
+pub fn save_recording(recorder: &Recorder, options: &Options, frame: u64) -> Result<(), String> {
+    let path = capture_path(options.rom.as_deref().unwrap_or("chip8"), frame, "gif");
+    fs::write(&path, recorder.encode_gif()?).map_err(|e| format!("{}: {}", path, e))?;
+    println!("Saved {}, {} frames.", path, recorder.frames());
+    Ok(())
+}
@@ -0,0 +1,279 @@
+//! Chip 8 Octo cartridge loading.
+//! (C) Ryan Jeffrey <ryan@ryanmj.xyz>, 2022
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or (at
+// your option) any later version.
+
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use std::iter::Peekable;
+use std::str::Chars;
+
+use gif::{ColorOutput, DecodeOptions};
+use octo::assemble;
+use palette::{Palette, parse_color};
+
+/// Magic bytes GIF images, and so cartridges, start with.
+const GIF_MAGIC: &[u8] = b"GIF8";
+
+/// A JSON value, as far as cartridges need them.
+#[derive(PartialEq, Debug)]
+enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// Return the value of a key of an object, None for other values.
+    /// # Arguments
+    /// * `key` The key.
+    fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+}
+
+/// Read a JSON value, skipping the whitespace around it. Return string on
+/// error.
+/// # Arguments
+/// * `chars` The text, read up to the end of the value.
+fn parse_json(chars: &mut Peekable<Chars>) -> Result<Json, String> {
+    skip_whitespace(chars);
+    let result = match chars.peek().copied() {
+        Some('{') => {
+            chars.next();
+            let mut members = Vec::new();
+            skip_whitespace(chars);
+            if chars.peek() == Some(&'}') {
+                chars.next();
+                return Ok(Json::Object(members));
+            }
+            loop {
+                skip_whitespace(chars);
+                let key = match parse_json(chars)? {
+                    Json::String(key) => key,
+                    v => return Err(format!("Expected a key, got {:?}.", v)),
+                };
+                expect_char(chars, ':')?;
+                members.push((key, parse_json(chars)?));
+                match chars.next() {
+                    Some(',') => continue,
+                    Some('}') => break,
+                    c => return Err(format!("Expected , or }}, got {:?}.", c)),
+                }
+            }
+            Json::Object(members)
+        },
+        Some('[') => {
+            chars.next();
+            let mut values = Vec::new();
+            skip_whitespace(chars);
+            if chars.peek() == Some(&']') {
+                chars.next();
+                return Ok(Json::Array(values));
+            }
+            loop {
+                values.push(parse_json(chars)?);
+                match chars.next() {
+                    Some(',') => continue,
+                    Some(']') => break,
+                    c => return Err(format!("Expected , or ], got {:?}.", c)),
+                }
+            }
+            Json::Array(values)
+        },
+        Some('"') => {
+            chars.next();
+            let mut s = String::new();
+            loop {
+                match chars.next().ok_or("Unterminated string.")? {
+                    '"' => break,
+                    '\\' => s.push(match chars.next().ok_or("Unterminated string.")? {
+                        'n' => '\n',
+                        't' => '\t',
+                        'r' => '\r',
+                        'b' => '\u{8}',
+                        'f' => '\u{c}',
+                        'u' => {
+                            let hex: String = chars.by_ref().take(4).collect();
+                            let code = u32::from_str_radix(&hex, 16)
+                                .map_err(|_| format!("Invalid escape \\u{}.", hex))?;
+                            std::char::from_u32(code).unwrap_or('\u{fffd}')
+                        },
+                        c => c,
+                    }),
+                    c => s.push(c),
+                }
+            }
+            Json::String(s)
+        },
+        Some(c) if c == '-' || c.is_ascii_digit() => {
+            let mut number = String::new();
+            while let Some(&c) = chars.peek() {
+                if !(c.is_ascii_digit() || "+-.eE".contains(c)) {
+                    break;
+                }
+                number.push(c);
+                chars.next();
+            }
+            Json::Number(number.parse().map_err(|_| format!("Invalid number {}.", number))?)
+        },
+        Some(_) => {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek().filter(|c| c.is_ascii_alphabetic()) {
+                word.push(c);
+                chars.next();
+            }
+            match word.as_str() {
+                "true" => Json::Bool(true),
+                "false" => Json::Bool(false),
+                "null" => Json::Null,
+                _ => return Err(format!("Unexpected {}.", word)),
+            }
+        },
+        None => return Err(String::from("Unexpected end of JSON.")),
+    };
+    skip_whitespace(chars);
+    Ok(result)
+}
+
+/// Skip whitespace in JSON.
+/// # Arguments
+/// * `chars` The text.
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.peek().is_some_and(|c| c.is_whitespace()) {
+        chars.next();
+    }
+}
+
+/// Read a character of JSON. Return string if it is another.
+/// # Arguments
+/// * `chars` The text.
+/// * `expected` The character.
+fn expect_char(chars: &mut Peekable<Chars>, expected: char) -> Result<(), String> {
+    match chars.next() {
+        Some(c) if c == expected => Ok(()),
+        c => Err(format!("Expected {}, got {:?}.", expected, c)),
+    }
+}
+
+/// An Octo cartridge: a GIF image carrying the source of a program and the
+/// settings it runs with. The low nibble of each pixel's colour index is
+/// half a byte, the high half first, going through the frames in order.
+/// The bytes are a 32 bit big endian length and that much JSON, with the
+/// source in `program` and the settings in `options`.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Cartridge {
+    /// Octo source of the program.
+    pub source: String,
+    /// Instructions run per 60 Hz frame.
+    pub tickrate: Option<u64>,
+    /// `Drw` waits for the next frame, Octo's vBlank quirk.
+    pub display_wait: Option<bool>,
+    /// Shifts ignore Vy, Octo's shift quirk.
+    pub shift: Option<bool>,
+    /// `LdIR` and `LdIRM` move I, the opposite of Octo's load/store quirk.
+    pub increment_i: Option<bool>,
+    /// `JpI` jumps by Vx, Octo's jump quirk.
+    pub jump: Option<bool>,
+    /// Sprites stop at the edges, Octo's clip quirk.
+    pub clip: Option<bool>,
+    /// Colours of the display, classic ones for those not given.
+    pub palette: Option<Palette>,
+}
+
+impl Cartridge {
+    /// Return whether a file is a GIF image, which might be a cartridge.
+    /// # Arguments
+    /// * `bytes` Contents of the file.
+    pub fn is_cartridge(bytes: &[u8]) -> bool {
+        bytes.starts_with(GIF_MAGIC)
+    }
+
+    /// Decode a cartridge. Return string on error.
+    /// # Arguments
+    /// * `bytes` Contents of the GIF file.
+    pub fn decode(bytes: &[u8]) -> Result<Self, String> {
+        let mut options = DecodeOptions::new();
+        options.set_color_output(ColorOutput::Indexed);
+        let mut decoder = options.read_info(bytes).map_err(|e| e.to_string())?;
+        let mut pixels = Vec::new();
+        while let Some(frame) = decoder.read_next_frame().map_err(|e| e.to_string())? {
+            pixels.extend_from_slice(&frame.buffer);
+        }
+
+        let mut payload = pixels.chunks_exact(2).map(|p| (p[0] & 0xF) << 4 | p[1] & 0xF);
+        let length = (0..4).try_fold(0usize, |n, _| payload.next().map(|b| n << 8 | b as usize))
+            .ok_or("The cartridge is empty.")?;
+        let data: Vec<u8> = payload.take(length).collect();
+        if data.len() < length {
+            return Err(String::from("The cartridge is cut short."));
+        }
+        // Older cartridges are not UTF-8, their bytes are characters.
+        let text = String::from_utf8(data)
+            .unwrap_or_else(|e| e.into_bytes().iter().map(|&b| b as char).collect());
+        let json = parse_json(&mut text.chars().peekable())
+            .map_err(|e| format!("Invalid cartridge: {}", e))?;
+
+        let source = match json.get("program") {
+            Some(Json::String(source)) => source.clone(),
+            _ => return Err(String::from("The cartridge has no program.")),
+        };
+        let options = json.get("options");
+        let option = |key: &str| options.and_then(|o| o.get(key));
+        let quirk = |key: &str| match option(key) {
+            Some(&Json::Bool(b)) => Some(b),
+            _ => None,
+        };
+        let color = |key: &str| match option(key) {
+            Some(Json::String(s)) => parse_color(s).ok(),
+            _ => None,
+        };
+        let colors = [color("backgroundColor"), color("fillColor"), color("fillColor2"),
+                      color("blendColor")];
+        let palette = if colors.iter().any(Option::is_some) {
+            let classic = Palette::default();
+            Some(Palette {
+                name: String::from("cartridge"),
+                background: colors[0].unwrap_or(classic.background),
+                fill: colors[1].unwrap_or(classic.fill),
+                fill2: colors[2].unwrap_or(classic.fill2),
+                blend: colors[3].unwrap_or(classic.blend),
+            })
+        } else {
+            None
+        };
+
+        Ok(Self {
+            source,
+            tickrate: match option("tickrate") {
+                Some(&Json::Number(n)) if n >= 1.0 => Some(n as u64),
+                _ => None,
+            },
+            display_wait: quirk("vBlankQuirks"),
+            shift: quirk("shiftQuirks"),
+            increment_i: quirk("loadStoreQuirks").map(|b| !b),
+            jump: quirk("jumpQuirks"),
+            clip: quirk("clipQuirks"),
+            palette,
+        })
+    }
+
+    /// Assemble the program. Return string on error.
+    pub fn rom(&self) -> Result<Vec<u8>, String> {
+        assemble(&self.source)
+    }
+}
@@ -0,0 +1,193 @@
+//! Chip 8 debugger, breakpoints, monitors and tracing.
+//! (C) Ryan Jeffrey <ryan@ryanmj.xyz>, 2022
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or (at
+// your option) any later version.
+
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::BTreeMap;
+
+use super::InterpreterData;
+use disasm::labelled_mnemonic;
+use symbols::{Monitor, SymbolTable};
+
+/// Debugger state.
+pub struct Debugger {
+    /// Labels for addresses.
+    symbols: SymbolTable,
+    /// Armed breakpoints, names keyed by address.
+    breakpoints: BTreeMap<u16, String>,
+    /// Memory contents of every monitor the last time they were checked.
+    monitor_values: Vec<Vec<u8>>,
+    /// Emulation is stopped.
+    pub paused: bool,
+    /// Do not break on the next instruction, used to continue from a
+    /// breakpoint.
+    resuming: bool,
+    /// Print every instruction before it is run.
+    pub trace: bool,
+}
+
+/// Return the opcode at the current program counter.
+/// # Arguments
+/// * `emu_state` The emulator state.
+fn current_opcode(emu_state: &InterpreterData) -> u16 {
+    let address = emu_state.machine.address(emu_state.pc) as usize % emu_state.mem.len();
+    let next = (address + 1) % emu_state.mem.len();
+    (emu_state.mem[address] as u16) << 8 | emu_state.mem[next] as u16
+}
+
+/// Return the bytes of memory under a monitor.
+/// # Arguments
+/// * `mem` The emulator memory.
+/// * `monitor` The monitor.
+fn monitor_bytes(mem: &[u8], monitor: &Monitor) -> Vec<u8> {
+    (0..monitor.length)
+        .map(|i| mem[(monitor.address as usize + i as usize) % mem.len()])
+        .collect()
+}
+
+impl Debugger {
+    /// Create a debugger with every breakpoint from `symbols` armed.
+    /// # Arguments
+    /// * `symbols` Labels, breakpoints and monitors.
+    /// * `trace` Print every instruction before it is run.
+    pub fn new(symbols: SymbolTable, trace: bool) -> Self {
+        let breakpoints = symbols.breakpoints.iter()
+            .map(|(name, address)| (*address, name.clone()))
+            .collect();
+        let monitor_values = symbols.monitors.iter().map(|_| Vec::new()).collect();
+        Self {
+            symbols,
+            breakpoints,
+            monitor_values,
+            paused: false,
+            resuming: false,
+            trace,
+        }
+    }
+
+    /// Check whether the instruction about to run has a breakpoint and pause
+    /// if so. Return the name of the breakpoint that was hit.
+    /// # Arguments
+    /// * `emu_state` The emulator state.
+    pub fn check_breakpoint(&mut self, emu_state: &InterpreterData) -> Option<String> {
+        if self.resuming {
+            self.resuming = false;
+            return None;
+        }
+        let address = emu_state.machine.address(emu_state.pc);
+        let name = self.breakpoints.get(&address).cloned();
+        if name.is_some() {
+            self.paused = true;
+        }
+        name
+    }
+
+    /// Pause if the last instruction stopped emulation, such as with a stack
+    /// overflow, clearing the error so stepping retries the instruction.
+    /// Return the error.
+    /// # Arguments
+    /// * `emu_state` The emulator state.
+    pub fn check_error(&mut self, emu_state: &mut InterpreterData) -> Option<String> {
+        let error = emu_state.error.take();
+        if error.is_some() {
+            self.paused = true;
+        }
+        error
+    }
+
+    /// Continue running after a pause.
+    pub fn resume(&mut self) {
+        if self.paused {
+            self.paused = false;
+            self.resuming = true;
+        }
+    }
+
+    /// Return the instruction about to run, e.g. `0x204: CALL draw-player`.
+    /// # Arguments
+    /// * `emu_state` The emulator state.
+    pub fn trace_line(&self, emu_state: &InterpreterData) -> String {
+        format!("0x{:03X}: {}", emu_state.machine.address(emu_state.pc),
+                labelled_mnemonic(current_opcode(emu_state), &self.symbols))
+    }
+
+    /// Return a description of the registers and monitors.
+    /// # Arguments
+    /// * `emu_state` The emulator state.
+    pub fn state(&self, emu_state: &InterpreterData) -> String {
+        let pc = emu_state.machine.address(emu_state.pc);
+        let mut result = format!("PC 0x{:03X} ({})  I 0x{:03X} ({})  SP {}  DT {}  ST {}\n",
+                                 pc, self.symbols.describe(pc),
+                                 emu_state.i, self.symbols.describe(emu_state.i),
+                                 emu_state.sp, emu_state.delay_timer,
+                                 emu_state.sound_timer);
+        let registers = emu_state.v.iter().enumerate()
+            .map(|(i, v)| format!("V{:X} {:02X}", i, v))
+            .collect::<Vec<String>>();
+        result.push_str(&registers.join("  "));
+        result.push('\n');
+        for monitor in &self.symbols.monitors {
+            result.push_str(&Self::monitor_line(monitor, &monitor_bytes(&emu_state.mem, monitor)));
+        }
+        result.push_str(&self.trace_line(emu_state));
+        result
+    }
+
+    /// Return the registers and the instruction about to run as short lines,
+    /// for a narrow side pane.
+    /// # Arguments
+    /// * `emu_state` The emulator state.
+    pub fn state_lines(&self, emu_state: &InterpreterData) -> Vec<String> {
+        // Addresses with the closest label, if there is one.
+        let describe = |address: u16| match self.symbols.describe(address) {
+            d if d.starts_with("0x") => format!("0x{:03X}", address),
+            d => format!("0x{:03X} {}", address, d),
+        };
+        let mut result = vec![
+            format!("PC {}", describe(emu_state.machine.address(emu_state.pc))),
+            format!("I  {}", describe(emu_state.i)),
+            format!("SP {:<2} DT {:<3} ST {}", emu_state.sp, emu_state.delay_timer,
+                    emu_state.sound_timer),
+        ];
+        for (n, v) in emu_state.v.chunks(2).enumerate() {
+            result.push(format!("V{:X} {:02X}  V{:X} {:02X}", n * 2, v[0], n * 2 + 1, v[1]));
+        }
+        result.push(self.trace_line(emu_state));
+        result
+    }
+
+    /// Return a line for every monitor whose memory changed since the last
+    /// call.
+    /// # Arguments
+    /// * `mem` The emulator memory.
+    pub fn changed_monitors(&mut self, mem: &[u8]) -> Vec<String> {
+        let mut result = Vec::new();
+        for (i, monitor) in self.symbols.monitors.iter().enumerate() {
+            let bytes = monitor_bytes(mem, monitor);
+            if bytes != self.monitor_values[i] {
+                result.push(Self::monitor_line(monitor, &bytes));
+                self.monitor_values[i] = bytes;
+            }
+        }
+        result
+    }
+
+    /// Format a monitor and the bytes under it.
+    /// # Arguments
+    /// * `monitor` The monitor.
+    /// * `bytes` Memory contents of the monitor.
+    fn monitor_line(monitor: &Monitor, bytes: &[u8]) -> String {
+        let bytes = bytes.iter().map(|b| format!("{:02X}", b)).collect::<Vec<String>>();
+        format!("{} 0x{:03X}: {}\n", monitor.name, monitor.address, bytes.join(" "))
+    }
+}
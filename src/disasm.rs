@@ -0,0 +1,125 @@
+//! Chip 8 disassembler.
+//! (C) Ryan Jeffrey <ryan@ryanmj.xyz>, 2022
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or (at
+// your option) any later version.
+
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use super::{Instruction, program_to_enum, get_last_3_nibbles};
+use analysis::ControlFlowGraph;
+use symbols::SymbolTable;
+
+/// The maximum number of data bytes shown on one line.
+const DATA_BYTES_PER_LINE: usize = 8;
+
+/// Return the assembly mnemonic of a raw opcode, using the syntax from
+/// http://devernay.free.fr/hacks/chip8/C8TECH10.HTM
+/// # Arguments
+/// * `opcode` Chip 8 opcode.
+pub fn mnemonic(opcode: u16) -> String {
+    labelled_mnemonic(opcode, &SymbolTable::new())
+}
+
+/// Return the assembly mnemonic of a raw opcode, with addresses replaced by
+/// their labels.
+/// # Arguments
+/// * `opcode` Chip 8 opcode.
+/// * `symbols` Labels for addresses.
+pub fn labelled_mnemonic(opcode: u16, symbols: &SymbolTable) -> String {
+    type I = Instruction;
+    let nnn = symbols.name(get_last_3_nibbles(opcode));
+
+    match program_to_enum(opcode) {
+        Ok(I::Sys(_)) => format!("SYS {}", nnn),
+        Ok(I::Cls) => String::from("CLS"),
+        Ok(I::Ret) => String::from("RET"),
+        Ok(I::Jp(_)) => format!("JP {}", nnn),
+        Ok(I::Call(_)) => format!("CALL {}", nnn),
+        Ok(I::Se(x, kk)) => format!("SE V{:X}, 0x{:02X}", x, kk),
+        Ok(I::Sne(x, kk)) => format!("SNE V{:X}, 0x{:02X}", x, kk),
+        Ok(I::SeR(x, y)) => format!("SE V{:X}, V{:X}", x, y),
+        Ok(I::Ld(x, kk)) => format!("LD V{:X}, 0x{:02X}", x, kk),
+        Ok(I::Add(x, kk)) => format!("ADD V{:X}, 0x{:02X}", x, kk),
+        Ok(I::LdR(x, y)) => format!("LD V{:X}, V{:X}", x, y),
+        Ok(I::Or(x, y)) => format!("OR V{:X}, V{:X}", x, y),
+        Ok(I::And(x, y)) => format!("AND V{:X}, V{:X}", x, y),
+        Ok(I::Xor(x, y)) => format!("XOR V{:X}, V{:X}", x, y),
+        Ok(I::AddR(x, y)) => format!("ADD V{:X}, V{:X}", x, y),
+        Ok(I::Sub(x, y)) => format!("SUB V{:X}, V{:X}", x, y),
+        Ok(I::Shr(x, y)) => format!("SHR V{:X}, V{:X}", x, y),
+        Ok(I::SubN(x, y)) => format!("SUBN V{:X}, V{:X}", x, y),
+        Ok(I::Shl(x, y)) => format!("SHL V{:X}, V{:X}", x, y),
+        Ok(I::SneR(x, y)) => format!("SNE V{:X}, V{:X}", x, y),
+        Ok(I::LdI(_)) => format!("LD I, {}", nnn),
+        Ok(I::JpI(_)) => format!("JP V0, {}", nnn),
+        Ok(I::Rnd(x, kk)) => format!("RND V{:X}, 0x{:02X}", x, kk),
+        Ok(I::Drw(x, y, n)) => format!("DRW V{:X}, V{:X}, {}", x, y, n),
+        Ok(I::Skp(x)) => format!("SKP V{:X}", x),
+        Ok(I::SkpN(x)) => format!("SKNP V{:X}", x),
+        Ok(I::LdD(x)) => format!("LD V{:X}, DT", x),
+        Ok(I::LdW(x)) => format!("LD V{:X}, K", x),
+        Ok(I::LdSD(x)) => format!("LD DT, V{:X}", x),
+        Ok(I::LdS(x)) => format!("LD ST, V{:X}", x),
+        Ok(I::AddI(x)) => format!("ADD I, V{:X}", x),
+        Ok(I::LdSp(x)) => format!("LD F, V{:X}", x),
+        Ok(I::LdHf(x)) => format!("LD HF, V{:X}", x),
+        Ok(I::LdBCD(x)) => format!("LD B, V{:X}", x),
+        Ok(I::LdIR(x)) => format!("LD [I], V{:X}", x),
+        Ok(I::LdIRM(x)) => format!("LD V{:X}, [I]", x),
+        Err(_) => format!("DW 0x{:04X}", opcode),
+    }
+}
+
+/// Disassemble a ROM. Reachable code is printed as instructions, with a
+/// blank line before every basic block, everything else as data bytes.
+/// # Arguments
+/// * `rom` The ROM bytes.
+/// * `cfg` The control flow graph of `rom`.
+/// * `symbols` Labels to print in place of addresses.
+pub fn disassemble(rom: &[u8], cfg: &ControlFlowGraph, symbols: &SymbolTable) -> String {
+    let mut result = String::new();
+    let origin = cfg.origin();
+    let mut offset = 0;
+
+    while offset < rom.len() {
+        let address = origin + offset as u16;
+        if let Some(opcode) = cfg.instruction_at(address) {
+            if cfg.block(address).is_some() && offset != 0 {
+                result.push('\n');
+            }
+            if let Some(label) = symbols.label(address) {
+                result.push_str(&format!(": {}\n", label));
+            }
+            result.push_str(&format!("0x{:03X}: {:04X}  {}\n", address, opcode,
+                                     labelled_mnemonic(opcode, symbols)));
+            offset += 2;
+        } else {
+            if let Some(label) = symbols.label(address) {
+                result.push_str(&format!(": {}\n", label));
+            }
+            // Gather data bytes until the next instruction or label.
+            let start = offset;
+            offset += 1;
+            while offset < rom.len() && offset - start < DATA_BYTES_PER_LINE &&
+                cfg.instruction_at(origin + offset as u16).is_none() &&
+                symbols.label(origin + offset as u16).is_none() {
+                offset += 1;
+            }
+            let bytes = rom[start..offset].iter()
+                .map(|b| format!("0x{:02X}", b))
+                .collect::<Vec<String>>();
+            result.push_str(&format!("0x{:03X}:       DB {}\n",
+                                     address, bytes.join(", ")));
+        }
+    }
+
+    result
+}
@@ -0,0 +1,73 @@
+//! Chip 8 display conversion.
+//! (C) Ryan Jeffrey <ryan@ryanmj.xyz>, 2022
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or (at
+// your option) any later version.
+
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use super::{NUM_COLS, NUM_ROWS};
+use framebuffer::Framebuffer;
+use palette::Palette;
+
+/// Bytes per pixel of RGB pixel data.
+pub const BYTES_PER_PIXEL: usize = 3;
+
+/// Size in bytes of the RGB pixel data of one frame.
+pub const FRAME_BYTES: usize = NUM_COLS * NUM_ROWS * BYTES_PER_PIXEL;
+
+/// Convert the screen into row-major RGB pixel data, one pixel per cell, in
+/// the palette's colour of the planes each cell is set in.
+/// # Arguments
+/// * `screen` The emulator screen.
+/// * `palette` Colours of the cells.
+/// * `pixels` Destination, at least `BYTES_PER_PIXEL` bytes per cell long.
+pub fn screen_to_rgb(screen: &Framebuffer, palette: &Palette, pixels: &mut [u8]) {
+    for ((_, _, planes), pixel) in screen.iter().zip(pixels.chunks_exact_mut(BYTES_PER_PIXEL)) {
+        pixel.copy_from_slice(&palette.color(planes));
+    }
+}
+
+/// Convert the screen into lines of text, each character showing two cells
+/// above one another with Unicode half blocks, which keeps cells square in a
+/// terminal.
+/// # Arguments
+/// * `screen` The emulator screen.
+pub fn screen_to_half_blocks(screen: &Framebuffer) -> Vec<String> {
+    (0..screen.height() / 2)
+        .map(|line| (0..screen.width())
+             .map(|col| match (screen.get(col, line * 2), screen.get(col, line * 2 + 1)) {
+                 (false, false) => ' ',
+                 (true, false) => '\u{2580}',
+                 (false, true) => '\u{2584}',
+                 (true, true) => '\u{2588}',
+             })
+             .collect())
+        .collect()
+}
+
+/// Return the largest area with the screen's aspect ratio that fits in a
+/// `width` x `height` area, centered, as x, y, width and height.
+/// # Arguments
+/// * `width` Width of the area.
+/// * `height` Height of the area.
+/// * `integer_scale` Only scale cells by whole numbers, leaving wider borders.
+pub fn fit_screen(width: u32, height: u32, integer_scale: bool) -> (i32, i32, u32, u32) {
+    let (cols, rows) = (NUM_COLS as u32, NUM_ROWS as u32);
+    let (w, h) = if integer_scale {
+        let scale = (width / cols).min(height / rows).max(1);
+        (cols * scale, rows * scale)
+    } else if width * rows > height * cols {
+        (height * cols / rows, height)
+    } else {
+        (width, width * rows / cols)
+    };
+    ((width as i32 - w as i32) / 2, (height as i32 - h as i32) / 2, w, h)
+}
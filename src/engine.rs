@@ -0,0 +1,378 @@
+//! Chip 8 block caching execution engine.
+//! (C) Ryan Jeffrey <ryan@ryanmj.xyz>, 2022
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or (at
+// your option) any later version.
+
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use std::cmp;
+use std::ops::Range;
+use rand::Rng;
+
+use super::{Instruction, InterpreterData, emulate};
+
+/// The maximum number of instructions translated into one block.
+const MAX_BLOCK_LEN: usize = 64;
+
+/// A straight line instruction with its operands decoded, the variants
+/// mirror `Instruction`. Control flow instructions have no micro-op, they
+/// are run by the reference interpreter.
+#[derive(Copy, Clone)]
+enum MicroOp {
+    /// Does nothing, `Sys` and `LdW`.
+    Nop,
+    Cls,
+    Ld(u8, u8),
+    Add(u8, u8),
+    LdR(u8, u8),
+    Or(u8, u8),
+    And(u8, u8),
+    Xor(u8, u8),
+    AddR(u8, u8),
+    Sub(u8, u8),
+    Shr(u8, u8),
+    SubN(u8, u8),
+    Shl(u8, u8),
+    LdI(u16),
+    Rnd(u8, u8),
+    Drw(u8, u8, u8),
+    LdD(u8),
+    LdSD(u8),
+    LdS(u8),
+    AddI(u8),
+    LdSp(u8),
+    LdHf(u8),
+    LdBCD(u8),
+    LdIR(u8),
+    LdIRM(u8),
+}
+
+/// A translated straight line run of the program.
+struct Block {
+    /// The straight line instructions.
+    ops: Vec<MicroOp>,
+    /// The run ends with a control flow instruction.
+    terminated: bool,
+}
+
+impl Block {
+    /// Return the number of program instructions the block covers.
+    fn len(&self) -> usize {
+        self.ops.len() + self.terminated as usize
+    }
+}
+
+/// Execution engine that translates straight line runs of the program into
+/// cached blocks of micro-ops. It produces the same results as calling
+/// `emulate` once per instruction, code the program writes over included:
+/// the blocks covering it are translated anew.
+#[derive(Default)]
+pub struct BlockEngine {
+    /// The program the blocks were translated from.
+    program: Vec<Instruction>,
+    /// Translated blocks keyed by the position of their first instruction.
+    blocks: Vec<Option<Block>>,
+}
+
+/// Return the micro-op of a straight line instruction, or None if it
+/// changes control flow.
+/// # Arguments
+/// * `instruction` The instruction to translate.
+fn micro_op(instruction: Instruction) -> Option<MicroOp> {
+    type I = Instruction;
+    type M = MicroOp;
+    Some(match instruction {
+        I::Sys(..) | I::LdW(..) => M::Nop,
+        I::Cls => M::Cls,
+        I::Ld(x, kk) => M::Ld(x, kk),
+        I::Add(x, kk) => M::Add(x, kk),
+        I::LdR(x, y) => M::LdR(x, y),
+        I::Or(x, y) => M::Or(x, y),
+        I::And(x, y) => M::And(x, y),
+        I::Xor(x, y) => M::Xor(x, y),
+        I::AddR(x, y) => M::AddR(x, y),
+        I::Sub(x, y) => M::Sub(x, y),
+        I::Shr(x, y) => M::Shr(x, y),
+        I::SubN(x, y) => M::SubN(x, y),
+        I::Shl(x, y) => M::Shl(x, y),
+        I::LdI(nnn) => M::LdI(nnn),
+        I::Rnd(x, kk) => M::Rnd(x, kk),
+        I::Drw(x, y, n) => M::Drw(x, y, n),
+        I::LdD(x) => M::LdD(x),
+        I::LdSD(x) => M::LdSD(x),
+        I::LdS(x) => M::LdS(x),
+        I::AddI(x) => M::AddI(x),
+        I::LdSp(x) => M::LdSp(x),
+        I::LdHf(x) => M::LdHf(x),
+        I::LdBCD(x) => M::LdBCD(x),
+        I::LdIR(x) => M::LdIR(x),
+        I::LdIRM(x) => M::LdIRM(x),
+        I::Ret | I::Jp(..) | I::Call(..) | I::Se(..) | I::Sne(..) | I::SeR(..) |
+        I::SneR(..) | I::JpI(..) | I::Skp(..) | I::SkpN(..) => return None,
+    })
+}
+
+/// Translate the straight line run starting at `start`, as the program is
+/// in memory.
+/// # Arguments
+/// * `program` The chip8 program as Instructions.
+/// * `emu_state` The emulator state, holding the code written over.
+/// * `start` Position of the first instruction.
+fn translate(program: &[Instruction], emu_state: &InterpreterData, start: usize) -> Block {
+    let mut ops = Vec::new();
+    for pos in start..cmp::min(start + MAX_BLOCK_LEN, program.len()) {
+        match micro_op(emu_state.instruction(program, pos)) {
+            Some(op) => ops.push(op),
+            None => return Block { ops, terminated: true },
+        }
+    }
+    Block { ops, terminated: false }
+}
+
+/// Run a micro-op, the same way `emulate` runs its instruction. Return the
+/// memory it wrote to, if any.
+/// # Arguments
+/// * `op` The micro-op to run.
+/// * `emu_state` The emulator state to change.
+fn execute(op: MicroOp, emu_state: &mut InterpreterData) -> Option<Range<usize>> {
+    type M = MicroOp;
+    match op {
+        M::Nop => {},
+        M::Cls => {
+            emu_state.screen.clear();
+        },
+        M::Ld(x, kk) => {
+            emu_state.set_register(x, kk);
+        },
+        M::Add(x, kk) => {
+            emu_state.set_register(x, emu_state.get_register(x).wrapping_add(kk));
+        },
+        M::LdR(x, y) => {
+            emu_state.set_register(x, emu_state.get_register(y));
+        },
+        M::Or(x, y) => {
+            emu_state.set_register(x, emu_state.get_register(x) | emu_state.get_register(y));
+        },
+        M::And(x, y) => {
+            emu_state.set_register(x, emu_state.get_register(x) & emu_state.get_register(y));
+        },
+        M::Xor(x, y) => {
+            emu_state.set_register(x, emu_state.get_register(x) ^ emu_state.get_register(y));
+        },
+        M::AddR(x, y) => {
+            emu_state.set_register(x, emu_state.get_register(x)
+                                   .wrapping_add(emu_state.get_register(y)));
+            if emu_state.get_register(x) < emu_state.get_register(y) {
+                emu_state.set_register(0xf, 1);
+            }
+        },
+        M::Sub(x, y) => {
+            if emu_state.get_register(x) > emu_state.get_register(y) {
+                emu_state.set_register(0xf, 1);
+            }
+            emu_state.set_register(x, emu_state.get_register(x)
+                                   .wrapping_sub(emu_state.get_register(y)));
+        },
+        M::Shr(x, y) => {
+            let y = if emu_state.quirks.shift { x } else { y };
+            if emu_state.get_register(y) & 1 == 1 {
+                emu_state.set_register(0xf, 1);
+            }
+            emu_state.set_register(x, emu_state.get_register(y) >> 1);
+        },
+        M::SubN(x, y) => {
+            if emu_state.get_register(x) < emu_state.get_register(y) {
+                emu_state.set_register(0xf, 1);
+            }
+            emu_state.set_register(x, emu_state.get_register(y)
+                                   .wrapping_sub(emu_state.get_register(x)));
+        },
+        M::Shl(x, y) => {
+            let y = if emu_state.quirks.shift { x } else { y };
+            if emu_state.get_register(y) & 0x80 != 0 {
+                emu_state.set_register(0xf, 1);
+            }
+            emu_state.set_register(x, emu_state.get_register(y) << 1);
+        },
+        M::LdI(nnn) => {
+            emu_state.i = nnn;
+        },
+        M::Rnd(x, kk) => {
+            let rn = emu_state.rng.gen::<u8>();
+            emu_state.set_register(x, rn & kk);
+        },
+        M::Drw(x, y, n) => {
+            emu_state.draw_sprite(x, y, n);
+        },
+        M::LdD(x) => {
+            emu_state.set_register(x, emu_state.delay_timer);
+        },
+        M::LdSD(x) => {
+            emu_state.delay_timer = emu_state.get_register(x);
+        },
+        M::LdS(x) => {
+            emu_state.sound_timer = emu_state.get_register(x);
+        },
+        M::AddI(x) => {
+            emu_state.i = emu_state.i.wrapping_add(emu_state.get_register(x) as u16);
+        },
+        M::LdSp(x) => {
+            emu_state.i = emu_state.machine.font_address
+                .wrapping_add(5 * emu_state.get_register(x) as u16);
+        },
+        M::LdHf(x) => {
+            emu_state.i = emu_state.machine.big_font_address()
+                .wrapping_add(10 * emu_state.get_register(x) as u16);
+        },
+        M::LdBCD(x) => {
+            let n = emu_state.get_register(x);
+            let i = emu_state.i as usize;
+            emu_state.mem[i] = (n / 100) % 10;
+            emu_state.mem[i + 1] = (n / 10) % 10;
+            emu_state.mem[i + 2] = n % 10;
+            return Some(i..i + 3);
+        },
+        M::LdIR(x) => {
+            for i in 0..=(x as u16) {
+                emu_state.mem[emu_state.i.wrapping_add(i) as usize] =
+                    emu_state.get_register(i as u8);
+            }
+            let i = emu_state.i as usize;
+            if emu_state.quirks.increment_i {
+                emu_state.i = emu_state.i.wrapping_add(x as u16 + 1);
+            }
+            return Some(i..i + x as usize + 1);
+        },
+        M::LdIRM(x) => {
+            for i in 0..=(x as u16) {
+                emu_state.set_register(i as u8,
+                                       emu_state.mem[emu_state.i.wrapping_add(i) as usize]);
+            }
+            if emu_state.quirks.increment_i {
+                emu_state.i = emu_state.i.wrapping_add(x as u16 + 1);
+            }
+        },
+    }
+    None
+}
+
+impl BlockEngine {
+    /// Create an engine with an empty cache.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Drop every cached block. Running another program does so by itself,
+    /// call it when the same program is loaded again or run on another
+    /// state, whose code may have been written over differently.
+    pub fn reset(&mut self) {
+        self.program.clear();
+        self.blocks.clear();
+    }
+
+    /// Return true if a block starting at instruction position `pos` is
+    /// cached.
+    /// # Arguments
+    /// * `pos` Instruction position.
+    pub fn is_cached(&self, pos: u16) -> bool {
+        matches!(self.blocks.get(pos as usize), Some(Some(_)))
+    }
+
+    /// Drop every cached block that covers the instructions from `first` to
+    /// `last`. Return true if the block starting at `current`, which is not
+    /// in the cache while it runs, covers them as well.
+    /// # Arguments
+    /// * `first` Position of the first instruction written over.
+    /// * `last` Position of the last instruction written over.
+    /// * `current` Position of the running block.
+    /// * `current_len` Length of the running block.
+    fn invalidate(&mut self, first: usize, last: usize, current: usize,
+                  current_len: usize) -> bool {
+        for pos in first.saturating_sub(MAX_BLOCK_LEN)..=last {
+            let covers = match &self.blocks[pos] {
+                Some(block) => pos + block.len() > first,
+                None => false,
+            };
+            if covers {
+                self.blocks[pos] = None;
+            }
+        }
+        current <= last && current + current_len > first
+    }
+
+    /// Run `count` instructions starting at emu_state's program counter, the
+    /// same as calling `emulate` `count` times. Stops early once
+    /// `emu_state.error` is set.
+    /// # Arguments
+    /// * `program` The chip8 program as Instructions.
+    /// * `emu_state` The emulator state to change.
+    /// * `cur_pressed_keys` Keypad state.
+    /// * `count` The number of instructions to run.
+    pub fn run(&mut self, program: &[Instruction], emu_state: &mut InterpreterData,
+               cur_pressed_keys: &[bool; 0x10], count: usize) {
+        if self.program[..] != *program {
+            self.reset();
+            self.program.extend_from_slice(program);
+            self.blocks.resize_with(program.len(), || None);
+        }
+
+        let mut remaining = count;
+        while remaining > 0 && emu_state.error.is_none() {
+            let pc = emu_state.pc as usize;
+            if pc >= program.len() {
+                // Running off the end sets the error, as `emulate` does.
+                emulate(program, emu_state, cur_pressed_keys);
+                remaining -= 1;
+                continue;
+            }
+
+            let block = match self.blocks[pc].take() {
+                Some(b) => b,
+                None => translate(program, emu_state, pc),
+            };
+
+            // Run the straight line part, stop early after a write to code.
+            let n = cmp::min(block.ops.len(), remaining);
+            let mut ran = 0;
+            let mut keep = true;
+            while ran < n {
+                // Drawing may wait for the next frame, leave it to `emulate`.
+                if let MicroOp::Drw(..) = block.ops[ran] {
+                    if emu_state.quirks.display_wait {
+                        break;
+                    }
+                }
+                let written = execute(block.ops[ran], emu_state);
+                emu_state.vblank = false;
+                ran += 1;
+                if let Some((first, last)) = written.and_then(|w| emu_state.rewrite(program.len(), w)) {
+                    if self.invalidate(first, last, pc, block.len()) {
+                        keep = false;
+                        break;
+                    }
+                }
+            }
+            emu_state.pc += ran as u16;
+            remaining -= ran;
+
+            // The waiting draw or the control flow instruction at the end.
+            let ended = ran == block.ops.len() && block.terminated && remaining > 0;
+            if keep && (ran < n || ended) {
+                emulate(program, emu_state, cur_pressed_keys);
+                remaining -= 1;
+            }
+
+            if keep {
+                self.blocks[pc] = Some(block);
+            }
+        }
+    }
+}
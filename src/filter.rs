@@ -0,0 +1,277 @@
+//! Chip 8 display filters, applied to frames after emulation.
+//! (C) Ryan Jeffrey <ryan@ryanmj.xyz>, 2022
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or (at
+// your option) any later version.
+
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use super::{NUM_COLS, NUM_ROWS};
+use framebuffer::Framebuffer;
+use display::{BYTES_PER_PIXEL, FRAME_BYTES};
+use palette::Palette;
+
+/// Intensity of a cell that is set.
+const FULL: u8 = 0xff;
+
+/// Phosphor persistence. Cells light up at once when set but fade out over
+/// a number of frames when cleared, like the CRT of the original machines,
+/// which hides the flicker of sprites being erased and redrawn.
+pub struct Persistence {
+    /// Intensity lost per frame.
+    decay: u8,
+    /// Intensity of every cell, row-major.
+    intensity: Vec<u8>,
+    /// Planes every cell was last set in, row-major, which pick the colour
+    /// it fades from.
+    planes: Vec<u8>,
+}
+
+/// Blend two colour components.
+/// # Arguments
+/// * `background` Component at intensity 0.
+/// * `foreground` Component at full intensity.
+/// * `intensity` Intensity.
+fn blend(background: u8, foreground: u8, intensity: u8) -> u8 {
+    let (b, f, i) = (background as i32, foreground as i32, intensity as i32);
+    (b + (f - b) * i / FULL as i32) as u8
+}
+
+impl Persistence {
+    /// Create a filter with every cell dark.
+    /// # Arguments
+    /// * `frames` Number of frames a cleared cell takes to fade out, at
+    ///   least 1.
+    pub fn new(frames: u32) -> Self {
+        let frames = frames.clamp(1, FULL as u32);
+        Self {
+            decay: (FULL as u32).div_ceil(frames) as u8,
+            intensity: vec![0; NUM_COLS * NUM_ROWS],
+            planes: vec![0; NUM_COLS * NUM_ROWS],
+        }
+    }
+
+    /// Advance one frame, lighting set cells and fading the others. Return
+    /// true if any intensity or colour changed.
+    /// # Arguments
+    /// * `screen` The emulator screen.
+    pub fn update(&mut self, screen: &Framebuffer) -> bool {
+        let mut changed = false;
+        for (((_, _, planes), intensity), last) in screen.iter()
+            .zip(self.intensity.iter_mut()).zip(self.planes.iter_mut()) {
+            let next = if planes != 0 { FULL } else { intensity.saturating_sub(self.decay) };
+            changed |= next != *intensity || (planes != 0 && planes != *last);
+            *intensity = next;
+            if planes != 0 {
+                *last = planes;
+            }
+        }
+        changed
+    }
+
+    /// Return the intensity of a cell, 0 to 255.
+    /// # Arguments
+    /// * `col` Column of the cell.
+    /// * `row` Row of the cell.
+    pub fn intensity(&self, col: usize, row: usize) -> u8 {
+        self.intensity[row * NUM_COLS + col]
+    }
+
+    /// Convert the intensities into row-major RGB pixel data, one pixel per
+    /// cell, blending between the background and the palette's colour of the
+    /// planes the cell was last set in.
+    /// # Arguments
+    /// * `palette` Colours of the cells.
+    /// * `pixels` Destination, at least `FRAME_BYTES` long.
+    pub fn to_rgb(&self, palette: &Palette, pixels: &mut [u8]) {
+        for ((pixel, &i), &planes) in pixels[..FRAME_BYTES].chunks_exact_mut(BYTES_PER_PIXEL)
+            .zip(self.intensity.iter()).zip(self.planes.iter()) {
+            let (background, foreground) = (palette.background, palette.color(planes));
+            for c in 0..BYTES_PER_PIXEL {
+                pixel[c] = blend(background[c], foreground[c], i);
+            }
+        }
+    }
+}
+
+/// Pixel art scaling algorithm.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum Scaler {
+    /// Every cell becomes a square of pixels.
+    Nearest,
+    /// EPX, smooths diagonals at twice the size.
+    Scale2x,
+    /// AdvMAME3x, smooths diagonals at three times the size.
+    Scale3x,
+}
+
+/// Pattern darkening part of every scaled cell.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum Mask {
+    None,
+    /// Darken the bottom row of pixels of every cell, like a CRT.
+    Scanlines,
+    /// Darken the bottom row and right column of every cell, like an LCD.
+    DotMatrix,
+}
+
+impl Scaler {
+    /// Parse a scaler name, `nearest`, `scale2x` or `scale3x`. Return string
+    /// on error.
+    /// # Arguments
+    /// * `name` Scaler name.
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "nearest" => Ok(Scaler::Nearest),
+            "scale2x" => Ok(Scaler::Scale2x),
+            "scale3x" => Ok(Scaler::Scale3x),
+            _ => Err(format!("Unknown filter {}, expected nearest, scale2x or scale3x.", name)),
+        }
+    }
+}
+
+impl Mask {
+    /// Parse a mask name, `none`, `scanlines` or `dot-matrix`. Return string
+    /// on error.
+    /// # Arguments
+    /// * `name` Mask name.
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "none" => Ok(Mask::None),
+            "scanlines" => Ok(Mask::Scanlines),
+            "dot-matrix" => Ok(Mask::DotMatrix),
+            _ => Err(format!("Unknown mask {}, expected none, scanlines or dot-matrix.", name)),
+        }
+    }
+}
+
+/// Scales RGB frames up with a pixel art scaler, then applies a mask. Runs on
+/// the CPU, so the output is the same in the window and in exported images.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub struct Upscaler {
+    pub scaler: Scaler,
+    pub mask: Mask,
+}
+
+impl Default for Upscaler {
+    /// Nearest neighbour without a mask, which leaves frames as they are.
+    fn default() -> Self {
+        Self {
+            scaler: Scaler::Nearest,
+            mask: Mask::None,
+        }
+    }
+}
+
+/// An RGB frame being scaled.
+struct Frame<'a> {
+    pixels: &'a [u8],
+    width: usize,
+    height: usize,
+}
+
+impl<'a> Frame<'a> {
+    /// Return the colour at `x` + `dx`, `y` + `dy`, clamped to the edges.
+    fn get(&self, x: usize, y: usize, dx: isize, dy: isize) -> [u8; 3] {
+        let x = (x as isize + dx).clamp(0, self.width as isize - 1) as usize;
+        let y = (y as isize + dy).clamp(0, self.height as isize - 1) as usize;
+        let i = (y * self.width + x) * BYTES_PER_PIXEL;
+        [self.pixels[i], self.pixels[i + 1], self.pixels[i + 2]]
+    }
+}
+
+/// Return the pixels a cell is scaled to, row-major.
+/// # Arguments
+/// * `scaler` Scaling algorithm.
+/// * `factor` Scale of nearest neighbour.
+/// * `frame` The frame.
+/// * `x` Column of the cell.
+/// * `y` Row of the cell.
+fn scale_cell(scaler: Scaler, factor: usize, frame: &Frame, x: usize, y: usize) -> Vec<[u8; 3]> {
+    let p = |dx, dy| frame.get(x, y, dx, dy);
+    let e = p(0, 0);
+    match scaler {
+        Scaler::Nearest => vec![e; factor * factor],
+        Scaler::Scale2x => {
+            let (a, b, c, d) = (p(0, -1), p(1, 0), p(-1, 0), p(0, 1));
+            vec![
+                if c == a && c != d && a != b { a } else { e },
+                if a == b && a != c && b != d { b } else { e },
+                if d == c && d != b && c != a { c } else { e },
+                if b == d && b != a && d != c { d } else { e },
+            ]
+        },
+        Scaler::Scale3x => {
+            let (a, b, c) = (p(-1, -1), p(0, -1), p(1, -1));
+            let (d, f) = (p(-1, 0), p(1, 0));
+            let (g, h, i) = (p(-1, 1), p(0, 1), p(1, 1));
+            if b == h || d == f {
+                return vec![e; 9];
+            }
+            vec![
+                if d == b { d } else { e },
+                if (d == b && e != c) || (b == f && e != a) { b } else { e },
+                if b == f { f } else { e },
+                if (d == b && e != g) || (d == h && e != a) { d } else { e },
+                e,
+                if (b == f && e != i) || (h == f && e != c) { f } else { e },
+                if d == h { d } else { e },
+                if (d == h && e != i) || (h == f && e != g) { h } else { e },
+                if h == f { f } else { e },
+            ]
+        },
+    }
+}
+
+impl Upscaler {
+    /// Return how many times larger than their input frames are scaled. A
+    /// mask on its own needs room, so it scales nearest neighbour three
+    /// times.
+    pub fn factor(&self) -> usize {
+        match (self.scaler, self.mask) {
+            (Scaler::Nearest, Mask::None) => 1,
+            (Scaler::Nearest, _) | (Scaler::Scale3x, _) => 3,
+            (Scaler::Scale2x, _) => 2,
+        }
+    }
+
+    /// Scale a row-major RGB frame. Return the pixels of the scaled frame,
+    /// `factor` times as wide and high.
+    /// # Arguments
+    /// * `pixels` The frame, `width` * `height` pixels.
+    /// * `width` Width of the frame.
+    /// * `height` Height of the frame.
+    pub fn apply(&self, pixels: &[u8], width: usize, height: usize) -> Vec<u8> {
+        let frame = Frame { pixels, width, height };
+        let factor = self.factor();
+        let out_width = width * factor;
+        let mut result = vec![0; out_width * height * factor * BYTES_PER_PIXEL];
+
+        for y in 0..height {
+            for x in 0..width {
+                let cell = scale_cell(self.scaler, factor, &frame, x, y);
+                for (n, color) in cell.iter().enumerate() {
+                    let (cx, cy) = (n % factor, n / factor);
+                    let masked = match self.mask {
+                        Mask::None => false,
+                        Mask::Scanlines => cy == factor - 1,
+                        Mask::DotMatrix => cy == factor - 1 || cx == factor - 1,
+                    };
+                    let i = ((y * factor + cy) * out_width + x * factor + cx) * BYTES_PER_PIXEL;
+                    for c in 0..BYTES_PER_PIXEL {
+                        result[i + c] = if masked { color[c] / 2 } else { color[c] };
+                    }
+                }
+            }
+        }
+
+        result
+    }
+}
@@ -0,0 +1,211 @@
+//! Chip 8 font sets.
+//! (C) Ryan Jeffrey <ryan@ryanmj.xyz>, 2022
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or (at
+// your option) any later version.
+
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use std::fs;
+use std::path::Path;
+
+use super::FONTSET;
+
+/// Bytes of a small glyph, 5 rows.
+pub const SMALL_GLYPH: usize = 5;
+/// Bytes of a big glyph, 10 rows.
+pub const BIG_GLYPH: usize = 10;
+/// Number of glyphs in a font, one per hex digit.
+const GLYPHS: usize = 0x10;
+
+/// The COSMAC VIP interpreter's glyphs, from its ROM.
+const VIP: [u8; GLYPHS * SMALL_GLYPH] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x60, 0x20, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0x70, 0x10, 0xF0, // 3
+    0xA0, 0xA0, 0xF0, 0x20, 0x20, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x10, 0x10, 0x10, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xF0, 0x50, 0x70, 0x50, 0xF0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xF0, 0x50, 0x50, 0x50, 0xF0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+
+/// The DREAM 6800's glyphs, three pixels wide.
+const DREAM_6800: [u8; GLYPHS * SMALL_GLYPH] = [
+    0xE0, 0xA0, 0xA0, 0xA0, 0xE0, // 0
+    0x40, 0x40, 0x40, 0x40, 0x40, // 1
+    0xE0, 0x20, 0xE0, 0x80, 0xE0, // 2
+    0xE0, 0x20, 0xE0, 0x20, 0xE0, // 3
+    0x80, 0xA0, 0xA0, 0xE0, 0x20, // 4
+    0xE0, 0x80, 0xE0, 0x20, 0xE0, // 5
+    0xE0, 0x80, 0xE0, 0xA0, 0xE0, // 6
+    0xE0, 0x20, 0x20, 0x20, 0x20, // 7
+    0xE0, 0xA0, 0xE0, 0xA0, 0xE0, // 8
+    0xE0, 0xA0, 0xE0, 0x20, 0xE0, // 9
+    0xE0, 0xA0, 0xE0, 0xA0, 0xA0, // A
+    0xC0, 0xA0, 0xE0, 0xA0, 0xC0, // B
+    0xE0, 0x80, 0x80, 0x80, 0xE0, // C
+    0xC0, 0xA0, 0xA0, 0xA0, 0xC0, // D
+    0xE0, 0x80, 0xE0, 0x80, 0xE0, // E
+    0xE0, 0x80, 0xC0, 0x80, 0x80, // F
+];
+
+/// The ETI-660's glyphs, three pixels wide with lower case B and D.
+const ETI_660: [u8; GLYPHS * SMALL_GLYPH] = [
+    0xE0, 0xA0, 0xA0, 0xA0, 0xE0, // 0
+    0x20, 0x20, 0x20, 0x20, 0x20, // 1
+    0xE0, 0x20, 0xE0, 0x80, 0xE0, // 2
+    0xE0, 0x20, 0xE0, 0x20, 0xE0, // 3
+    0xA0, 0xA0, 0xE0, 0x20, 0x20, // 4
+    0xE0, 0x80, 0xE0, 0x20, 0xE0, // 5
+    0xE0, 0x80, 0xE0, 0xA0, 0xE0, // 6
+    0xE0, 0x20, 0x20, 0x20, 0x20, // 7
+    0xE0, 0xA0, 0xE0, 0xA0, 0xE0, // 8
+    0xE0, 0xA0, 0xE0, 0x20, 0xE0, // 9
+    0xE0, 0xA0, 0xE0, 0xA0, 0xA0, // A
+    0x80, 0x80, 0xE0, 0xA0, 0xE0, // B
+    0xE0, 0x80, 0x80, 0x80, 0xE0, // C
+    0x20, 0x20, 0xE0, 0xA0, 0xE0, // D
+    0xE0, 0x80, 0xE0, 0x80, 0xE0, // E
+    0xE0, 0x80, 0xC0, 0x80, 0x80, // F
+];
+
+/// SUPER-CHIP 1.1's 8x10 glyphs, which only cover the decimal digits.
+const SCHIP_BIG: [u8; 10 * BIG_GLYPH] = [
+    0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C, // 0
+    0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C, // 1
+    0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF, // 2
+    0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C, // 3
+    0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C, // 5
+    0x3E, 0x7C, 0xC0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C, // 6
+    0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60, // 7
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C, // 8
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C, // 9
+];
+
+/// Built-in fonts: name, small glyphs and big glyphs.
+const BUILTINS: [(&str, &[u8; GLYPHS * SMALL_GLYPH], &[u8]); 5] = [
+    ("modern", &FONTSET, &[]),
+    ("vip", &VIP, &[]),
+    ("dream-6800", &DREAM_6800, &[]),
+    ("eti-660", &ETI_660, &[]),
+    ("schip", &FONTSET, &SCHIP_BIG),
+];
+
+/// The hex digit glyphs an interpreter loads into memory: 4x5 ones `LdSp`
+/// points I at and, for SUPER-CHIP, 8x10 ones `LdHf` points I at, stored
+/// right after them.
+///
+/// Font files hold the 80 bytes of the small glyphs, 5 per digit, then
+/// optionally up to 16 big glyphs of 10 bytes.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Font {
+    /// Name of the font, the file name for font files.
+    pub name: String,
+    /// The 4x5 glyphs, one byte per row.
+    pub small: [u8; GLYPHS * SMALL_GLYPH],
+    /// The 8x10 glyphs, one byte per row, empty if there are none.
+    pub big: Vec<u8>,
+}
+
+impl Default for Font {
+    /// The font of most modern interpreters, from CHIP-48.
+    fn default() -> Self {
+        Self::builtins().remove(0)
+    }
+}
+
+impl Font {
+    /// Return every built-in font, the modern one first.
+    pub fn builtins() -> Vec<Self> {
+        BUILTINS.iter()
+            .map(|&(name, small, big)| Self {
+                name: name.to_string(),
+                small: *small,
+                big: big.to_vec(),
+            })
+            .collect()
+    }
+
+    /// Return the built-in font called `name`.
+    /// # Arguments
+    /// * `name` Font name.
+    pub fn builtin(name: &str) -> Option<Self> {
+        Self::builtins().into_iter().find(|f| f.name == name)
+    }
+
+    /// Read the bytes of a font file. Return string on error.
+    /// # Arguments
+    /// * `name` Name of the font.
+    /// * `bytes` Contents of the font file.
+    pub fn parse(name: &str, bytes: &[u8]) -> Result<Self, String> {
+        let small_len = GLYPHS * SMALL_GLYPH;
+        let big_len = bytes.len().saturating_sub(small_len);
+        if bytes.len() < small_len || !big_len.is_multiple_of(BIG_GLYPH) ||
+            big_len > GLYPHS * BIG_GLYPH {
+            return Err(format!("A font is {} bytes, then up to {} big glyphs of {}, not {}.",
+                               small_len, GLYPHS, BIG_GLYPH, bytes.len()));
+        }
+        let mut small = [0; GLYPHS * SMALL_GLYPH];
+        small.copy_from_slice(&bytes[..small_len]);
+        Ok(Self {
+            name: name.to_string(),
+            small,
+            big: bytes[small_len..].to_vec(),
+        })
+    }
+
+    /// Load a font file. Return string on error.
+    /// # Arguments
+    /// * `path` Path of the font file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let bytes = fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let name = path.file_stem().map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Self::parse(&name, &bytes).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Return the built-in font called `arg`, or load it as a font file.
+    /// Return string on error.
+    /// # Arguments
+    /// * `arg` Font name or path.
+    pub fn from_arg(arg: &str) -> Result<Self, String> {
+        match Self::builtin(arg) {
+            Some(f) => Ok(f),
+            None if Path::new(arg).is_file() => Self::load(arg),
+            None => Err(format!("{} is neither a font file nor one of {}.", arg,
+                                Self::builtins().iter().map(|f| f.name.as_str())
+                                .collect::<Vec<&str>>().join(", "))),
+        }
+    }
+
+    /// Return the size of the font in memory, both sets of glyphs.
+    pub fn size(&self) -> usize {
+        self.small.len() + self.big.len()
+    }
+
+    /// Return the font as it is stored in memory, the small glyphs then the
+    /// big ones.
+    pub fn bytes(&self) -> Vec<u8> {
+        let mut result = self.small.to_vec();
+        result.extend_from_slice(&self.big);
+        result
+    }
+}
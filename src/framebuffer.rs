@@ -0,0 +1,274 @@
+//! Chip 8 bit-packed framebuffer.
+//! (C) Ryan Jeffrey <ryan@ryanmj.xyz>, 2022
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or (at
+// your option) any later version.
+
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+/// Bits in a word of a row.
+const WORD_BITS: usize = 64;
+
+/// FNV-1a offset basis and prime, for `hash`.
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/// The screen, one bit per cell and plane. Each row of each plane is packed
+/// into 64 bit words, the most significant bit of the first word being the
+/// leftmost cell, so drawing, copying and comparing work on whole words.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Framebuffer {
+    /// Width in cells.
+    width: usize,
+    /// Height in cells.
+    height: usize,
+    /// Number of planes.
+    planes: usize,
+    /// Words per row.
+    stride: usize,
+    /// Rows of the first plane from the top, then those of the next plane.
+    /// Bits past the width are always clear.
+    words: Vec<u64>,
+}
+
+impl Framebuffer {
+    /// Create a clear framebuffer.
+    /// # Arguments
+    /// * `width` Width in cells.
+    /// * `height` Height in cells.
+    /// * `planes` Number of planes, 1 to 8.
+    pub fn new(width: usize, height: usize, planes: usize) -> Self {
+        let planes = planes.clamp(1, 8);
+        let stride = width.div_ceil(WORD_BITS);
+        Self {
+            width,
+            height,
+            planes,
+            stride,
+            words: vec![0; stride * height * planes],
+        }
+    }
+
+    /// Return the width in cells.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Return the height in cells.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Return the number of planes.
+    pub fn planes(&self) -> usize {
+        self.planes
+    }
+
+    /// Return the index of the first word of a row.
+    /// # Arguments
+    /// * `plane` The plane.
+    /// * `y` The row.
+    fn row_start(&self, plane: usize, y: usize) -> usize {
+        (plane * self.height + y) * self.stride
+    }
+
+    /// Return the words of a row, bits past the width are clear.
+    /// # Arguments
+    /// * `plane` The plane.
+    /// * `y` The row.
+    pub fn row(&self, plane: usize, y: usize) -> &[u64] {
+        let start = self.row_start(plane, y);
+        &self.words[start..start + self.stride]
+    }
+
+    /// Return true if a cell is set in any plane.
+    /// # Arguments
+    /// * `x` Column of the cell.
+    /// * `y` Row of the cell.
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        self.planes_at(x, y) != 0
+    }
+
+    /// Return the planes a cell is set in, bit 0 for the first plane, as
+    /// `Palette::color` takes them.
+    /// # Arguments
+    /// * `x` Column of the cell.
+    /// * `y` Row of the cell.
+    pub fn planes_at(&self, x: usize, y: usize) -> u8 {
+        let (word, bit) = (x / WORD_BITS, WORD_BITS - 1 - x % WORD_BITS);
+        (0..self.planes).fold(0, |mask, plane| {
+            let set = (self.words[self.row_start(plane, y) + word] >> bit) & 1;
+            mask | (set as u8) << plane
+        })
+    }
+
+    /// Set or clear a cell of a plane.
+    /// # Arguments
+    /// * `plane` The plane.
+    /// * `x` Column of the cell.
+    /// * `y` Row of the cell.
+    /// * `on` Set the cell if true, clear it otherwise.
+    pub fn set(&mut self, plane: usize, x: usize, y: usize, on: bool) {
+        let index = self.row_start(plane, y) + x / WORD_BITS;
+        let bit = 1 << (WORD_BITS - 1 - x % WORD_BITS);
+        if on {
+            self.words[index] |= bit;
+        } else {
+            self.words[index] &= !bit;
+        }
+    }
+
+    /// Clear every plane.
+    pub fn clear(&mut self) {
+        self.words.iter_mut().for_each(|w| *w = 0);
+    }
+
+    /// XOR `len` bits into a row from column `x`, which must fit in the row.
+    /// Return true if a set cell was cleared.
+    /// # Arguments
+    /// * `start` Index of the first word of the row.
+    /// * `x` Column of the leftmost bit.
+    /// * `bits` The bits, right aligned, the leftmost cell first.
+    /// * `len` Number of bits, 1 to 64.
+    fn xor_bits(&mut self, start: usize, x: usize, bits: u64, len: usize) -> bool {
+        let (word, offset) = (start + x / WORD_BITS, x % WORD_BITS);
+        // Left align the bits in 128 bits, then move them to the column, so
+        // they can straddle two words.
+        let shifted = ((bits as u128) << (128 - len)) >> offset;
+        let mut collision = false;
+        for (i, part) in [(shifted >> 64) as u64, shifted as u64].iter().enumerate() {
+            if *part != 0 {
+                collision |= self.words[word + i] & part != 0;
+                self.words[word + i] ^= part;
+            }
+        }
+        collision
+    }
+
+    /// XOR a sprite row into a plane, wrapping around the right edge. Return
+    /// true if a set cell was cleared.
+    /// # Arguments
+    /// * `plane` The plane.
+    /// * `x` Column of the leftmost bit, wrapped around the width.
+    /// * `y` Row, wrapped around the height.
+    /// * `bits` The sprite row, right aligned, the leftmost cell first.
+    /// * `len` Width of the sprite row, 1 to 64 bits.
+    pub fn xor_sprite_row(&mut self, plane: usize, x: usize, y: usize, bits: u64,
+                          len: usize) -> bool {
+        let (x, len) = (x % self.width, len.min(self.width));
+        let start = self.row_start(plane, y % self.height);
+        let bits = bits & (u64::MAX >> (WORD_BITS - len));
+        let fits = (self.width - x).min(len);
+        let mut collision = self.xor_bits(start, x, bits >> (len - fits), fits);
+        if fits < len {
+            let wrapped = len - fits;
+            collision |= self.xor_bits(start, 0, bits & (u64::MAX >> (WORD_BITS - wrapped)),
+                                       wrapped);
+        }
+        collision
+    }
+
+    /// Shift every plane, clearing the cells scrolled in.
+    /// # Arguments
+    /// * `dx` Cells to the right, negative to the left.
+    /// * `dy` Cells down, negative up.
+    pub fn scroll(&mut self, dx: isize, dy: isize) {
+        let mut result = Self::new(self.width, self.height, self.planes);
+        for plane in 0..self.planes {
+            for y in 0..self.height {
+                let from = y as isize - dy;
+                if from < 0 || from >= self.height as isize {
+                    continue;
+                }
+                let source = self.row(plane, from as usize);
+                let start = result.row_start(plane, y);
+                shift_row(source, &mut result.words[start..start + self.stride], dx);
+            }
+        }
+        // Clear what was shifted past the right edge.
+        let tail = self.width % WORD_BITS;
+        if tail != 0 {
+            let mask = !(u64::MAX >> tail);
+            for row in result.words.chunks_exact_mut(self.stride) {
+                row[self.stride - 1] &= mask;
+            }
+        }
+        self.words = result.words;
+    }
+
+    /// Return a 64 bit FNV-1a hash of the size and cells, the same on every
+    /// run and platform.
+    pub fn hash(&self) -> u64 {
+        [self.width as u64, self.height as u64, self.planes as u64].iter()
+            .chain(self.words.iter())
+            .flat_map(|w| w.to_le_bytes())
+            .fold(FNV_OFFSET, |hash, byte| (hash ^ byte as u64).wrapping_mul(FNV_PRIME))
+    }
+
+    /// Return the cells whose planes differ from another framebuffer's, row
+    /// by row, as column and row. Every cell differs from a framebuffer of
+    /// another size.
+    /// # Arguments
+    /// * `other` The framebuffer to compare with.
+    pub fn diff(&self, other: &Framebuffer) -> Vec<(usize, usize)> {
+        if (self.width, self.height, self.planes) != (other.width, other.height, other.planes) {
+            return self.iter().map(|(x, y, _)| (x, y)).collect();
+        }
+        let mut result = Vec::new();
+        for y in 0..self.height {
+            for word in 0..self.stride {
+                let mut changed = (0..self.planes).fold(0, |changed, plane| {
+                    let i = self.row_start(plane, y) + word;
+                    changed | (self.words[i] ^ other.words[i])
+                });
+                while changed != 0 {
+                    let bit = changed.leading_zeros() as usize;
+                    result.push((word * WORD_BITS + bit, y));
+                    changed &= !(1 << (WORD_BITS - 1 - bit));
+                }
+            }
+        }
+        result
+    }
+
+    /// Return an iterator over every cell, row by row, as column, row and
+    /// the planes it is set in.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, usize, u8)> + '_ {
+        (0..self.height).flat_map(move |y| (0..self.width).map(move |x| (x, y, self.planes_at(x, y))))
+    }
+
+    /// Return an iterator over the cells set in any plane, row by row, as
+    /// column and row.
+    pub fn lit(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.iter().filter(|(_, _, planes)| *planes != 0).map(|(x, y, _)| (x, y))
+    }
+}
+
+/// Copy a row shifted by `dx` bits, to the right if positive.
+/// # Arguments
+/// * `source` Words of the row.
+/// * `dest` Words to write to, as many as `source`.
+/// * `dx` Bits to shift by.
+fn shift_row(source: &[u64], dest: &mut [u64], dx: isize) {
+    let (words, bits) = (dx.unsigned_abs() / WORD_BITS, dx.unsigned_abs() % WORD_BITS);
+    // The word `i` words from `word` in the shift's direction, 0 outside.
+    let at = |word: usize, i: usize| -> u64 {
+        let index = if dx >= 0 { word.checked_sub(i) } else { word.checked_add(i) };
+        index.and_then(|j| source.get(j)).copied().unwrap_or(0)
+    };
+    for (word, out) in dest.iter_mut().enumerate() {
+        let (near, far) = (at(word, words), at(word, words + 1));
+        *out = match (dx >= 0, bits) {
+            (_, 0) => near,
+            (true, _) => near >> bits | far << (WORD_BITS - bits),
+            (false, _) => near << bits | far >> (WORD_BITS - bits),
+        };
+    }
+}
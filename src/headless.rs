@@ -0,0 +1,66 @@
+//! Chip 8 emulation without a window.
+//! (C) Ryan Jeffrey <ryan@ryanmj.xyz>, 2022
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or (at
+// your option) any later version.
+
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use chip8::{Instruction, InterpreterData};
+use chip8::engine::BlockEngine;
+use chip8::options::Options;
+use chip8::palette::Palette;
+use chip8::recording::Recorder;
+
+use capture::{save_recording, save_screenshot};
+
+/// Frames per second, the rate of the timers.
+const FRAMES_PER_SECOND: u64 = 60;
+
+/// Run the program as fast as possible with the block engine, without input,
+/// for `--frames` or until the screenshot, saving the screenshot and
+/// recording if asked for. Return string on error.
+/// # Arguments
+/// * `program` The chip8 program as Instructions.
+/// * `emu_state` The emulator state, with the program loaded.
+/// * `palette` Colours of saved images.
+/// * `options` Command line options.
+pub fn run(program: &[Instruction], emu_state: &mut InterpreterData, palette: &Palette,
+           options: &Options) -> Result<(), String> {
+    let last_frame = match options.frames.or(options.screenshot_at_frame) {
+        Some(frame) => frame,
+        None => return Err(String::from("--headless requires --frames or --screenshot-at-frame.")),
+    };
+    let keys = [false; 0x10];
+    let mut recorder = Recorder::new(options.upscaler, options.image_scale);
+    let mut engine = BlockEngine::new();
+
+    for frame in 1..=last_frame {
+        // Spread the instructions evenly over the frames of each second.
+        let instructions = frame * options.speed / FRAMES_PER_SECOND -
+            (frame - 1) * options.speed / FRAMES_PER_SECOND;
+        engine.run(program, emu_state, &keys, instructions as usize);
+        if let Some(e) = emu_state.error.take() {
+            return Err(e);
+        }
+        emu_state.tick_frame();
+        if options.record {
+            recorder.capture(&emu_state.screen, palette);
+        }
+        if options.screenshot_at_frame == Some(frame) {
+            save_screenshot(&emu_state.screen, palette, options, frame)?;
+        }
+    }
+
+    if options.record {
+        save_recording(&recorder, options, 1)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,100 @@
+//! Chip 8 image export.
+//! (C) Ryan Jeffrey <ryan@ryanmj.xyz>, 2022
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or (at
+// your option) any later version.
+
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use super::{NUM_COLS, NUM_ROWS};
+use display::{BYTES_PER_PIXEL, FRAME_BYTES, screen_to_rgb};
+use filter::Upscaler;
+use framebuffer::Framebuffer;
+use palette::Palette;
+use png;
+
+/// An RGB image of the screen.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Image {
+    /// Row-major RGB pixel data.
+    pub pixels: Vec<u8>,
+    pub width: usize,
+    pub height: usize,
+}
+
+/// Scale an RGB image up, every pixel becoming a `scale` x `scale` square.
+/// # Arguments
+/// * `image` The image.
+/// * `scale` Scale factor, at least 1.
+fn scale_nearest(image: Image, scale: usize) -> Image {
+    if scale <= 1 {
+        return image;
+    }
+    let width = image.width * scale;
+    let mut pixels = Vec::with_capacity(width * image.height * scale * BYTES_PER_PIXEL);
+    for row in image.pixels.chunks_exact(image.width * BYTES_PER_PIXEL) {
+        let mut scaled_row = Vec::with_capacity(width * BYTES_PER_PIXEL);
+        for pixel in row.chunks_exact(BYTES_PER_PIXEL) {
+            for _ in 0..scale {
+                scaled_row.extend_from_slice(pixel);
+            }
+        }
+        for _ in 0..scale {
+            pixels.extend_from_slice(&scaled_row);
+        }
+    }
+    Image { pixels, width, height: image.height * scale }
+}
+
+/// Draw the screen the way the SDL frontend does, in the palette's colours,
+/// through an upscaler, then scaled up by a whole number.
+/// # Arguments
+/// * `screen` The emulator screen.
+/// * `palette` Colours of the screen.
+/// * `upscaler` Filter applied before scaling.
+/// * `scale` Size of every filtered pixel in the image.
+pub fn render(screen: &Framebuffer, palette: &Palette, upscaler: &Upscaler,
+              scale: usize) -> Image {
+    let mut pixels = vec![0; FRAME_BYTES];
+    screen_to_rgb(screen, palette, &mut pixels);
+    let factor = upscaler.factor();
+    let image = Image {
+        pixels: upscaler.apply(&pixels, NUM_COLS, NUM_ROWS),
+        width: NUM_COLS * factor,
+        height: NUM_ROWS * factor,
+    };
+    scale_nearest(image, scale)
+}
+
+/// Encode an image as PNG. Return string on error.
+/// # Arguments
+/// * `image` The image.
+pub fn encode_png(image: &Image) -> Result<Vec<u8>, String> {
+    let mut result = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut result, image.width as u32, image.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+        writer.write_image_data(&image.pixels).map_err(|e| e.to_string())?;
+    }
+    Ok(result)
+}
+
+/// Return a screenshot of the screen as PNG bytes. Return string on error.
+/// # Arguments
+/// * `screen` The emulator screen.
+/// * `palette` Colours of the screen.
+/// * `upscaler` Filter applied before scaling.
+/// * `scale` Size of every filtered pixel in the image.
+pub fn screenshot(screen: &Framebuffer, palette: &Palette,
+                  upscaler: &Upscaler, scale: usize) -> Result<Vec<u8>, String> {
+    encode_png(&render(screen, palette, upscaler, scale))
+}
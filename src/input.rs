@@ -0,0 +1,227 @@
+//! Chip 8 SDL input.
+//! (C) Ryan Jeffrey <ryan@ryanmj.xyz>, 2022
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or (at
+// your option) any later version.
+
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+
+use chip8::keymap::{Keymap, NUM_PADS};
+use sdl2::GameControllerSubsystem;
+use sdl2::controller::{Axis, Button, GameController};
+use sdl2::event::Event;
+use sdl2::keyboard::Scancode;
+
+/// A controller binding resolved to SDL types.
+#[derive(Copy, Clone)]
+enum Control {
+    Button(Button),
+    /// An axis and whether it is bound in the positive direction.
+    Axis(Axis, bool),
+}
+
+/// An open game controller.
+struct Pad {
+    controller: GameController,
+    /// Keys held down by the controller.
+    keys: [bool; 0x10],
+}
+
+/// Keyboard and game controller state, resolved from a keymap.
+pub struct Input {
+    /// Chip 8 keys keyed by scancode.
+    keys: HashMap<Scancode, u8>,
+    /// Controls of every pad and their chip 8 keys.
+    controls: Vec<Vec<(Control, u8)>>,
+    /// Axis positions closer to the centre than this are ignored.
+    deadzone: i16,
+    /// Used to open controllers when they are plugged in.
+    subsystem: GameControllerSubsystem,
+    /// Open controllers, in the order they were plugged in.
+    pads: Vec<Option<Pad>>,
+    /// Keys held down on the keyboard.
+    keyboard: [bool; 0x10],
+    /// Key held down with the mouse on the on-screen keypad.
+    clicked: Option<u8>,
+}
+
+/// Resolve a control name, e.g. `dpup` or `leftx-`. Return string on error.
+/// # Arguments
+/// * `name` Button or axis direction name.
+fn control(name: &str) -> Result<Control, String> {
+    let axis = |axis: &str, positive| Axis::from_string(axis).map(|a| Control::Axis(a, positive));
+    let result = if let Some(axis_name) = name.strip_suffix('+') {
+        axis(axis_name, true)
+    } else if let Some(axis_name) = name.strip_suffix('-') {
+        axis(axis_name, false)
+    } else {
+        Button::from_string(name).map(Control::Button)
+    };
+    result.ok_or_else(|| format!("Unknown controller button or axis {} in keymap.", name))
+}
+
+impl Input {
+    /// Resolve the scancode and control names of a keymap. Return string on
+    /// error.
+    /// # Arguments
+    /// * `keymap` The keymap.
+    /// * `subsystem` SDL game controller subsystem.
+    pub fn new(keymap: &Keymap, subsystem: GameControllerSubsystem) -> Result<Self, String> {
+        let mut result = Self {
+            keys: HashMap::new(),
+            controls: Vec::new(),
+            deadzone: 0,
+            subsystem,
+            pads: (0..NUM_PADS).map(|_| None).collect(),
+            keyboard: [false; 0x10],
+            clicked: None,
+        };
+        result.set_keymap(keymap)?;
+        Ok(result)
+    }
+
+    /// Replace the keymap, keeping the open controllers and releasing every
+    /// key. Return string on error.
+    /// # Arguments
+    /// * `keymap` The keymap.
+    pub fn set_keymap(&mut self, keymap: &Keymap) -> Result<(), String> {
+        let mut keys = HashMap::new();
+        for (name, key) in keymap.bindings() {
+            let scancode = Scancode::from_name(name)
+                .ok_or_else(|| format!("Unknown scancode {} in keymap.", name))?;
+            keys.insert(scancode, key);
+        }
+        let mut controls = Vec::new();
+        for pad in 0..NUM_PADS {
+            controls.push(keymap.pad_bindings(pad)
+                          .map(|(name, key)| Ok((control(name)?, key)))
+                          .collect::<Result<Vec<(Control, u8)>, String>>()?);
+        }
+        self.keys = keys;
+        self.controls = controls;
+        self.deadzone = keymap.deadzone() as i16;
+        self.keyboard = [false; 0x10];
+        self.clicked = None;
+        for n in 0..NUM_PADS {
+            self.update_pad(n);
+        }
+        Ok(())
+    }
+
+    /// Update the key state from an event. Controllers are opened when they
+    /// are plugged in, SDL also reports controllers present at startup this
+    /// way.
+    /// # Arguments
+    /// * `event` SDL event.
+    pub fn handle(&mut self, event: &Event) {
+        match *event {
+            Event::KeyDown { scancode: Some(sc), .. } => {
+                if let Some(&key) = self.keys.get(&sc) {
+                    self.keyboard[key as usize] = true;
+                }
+            },
+            Event::KeyUp { scancode: Some(sc), .. } => {
+                if let Some(&key) = self.keys.get(&sc) {
+                    self.keyboard[key as usize] = false;
+                }
+            },
+            Event::ControllerDeviceAdded { which, .. } => {
+                if let Err(e) = self.add_controller(which) {
+                    eprintln!("Could not open controller: {}", e);
+                }
+            },
+            Event::ControllerDeviceRemoved { which, .. } => {
+                if let Some(n) = self.pad_index(which) {
+                    println!("Controller disconnected from pad {}.", n + 1);
+                    self.pads[n] = None;
+                }
+            },
+            Event::ControllerButtonDown { which, .. } |
+            Event::ControllerButtonUp { which, .. } |
+            Event::ControllerAxisMotion { which, .. } => {
+                if let Some(n) = self.pad_index(which) {
+                    self.update_pad(n);
+                }
+            },
+            _ => {},
+        }
+    }
+
+    /// Hold down a key of the on-screen keypad, releasing the previous one.
+    /// # Arguments
+    /// * `key` The key, None to release.
+    pub fn click(&mut self, key: Option<u8>) {
+        self.clicked = key;
+    }
+
+    /// Return the keys held down on the keyboard, the on-screen keypad or
+    /// any controller.
+    pub fn pressed_keys(&self) -> [bool; 0x10] {
+        let mut result = self.keyboard;
+        if let Some(key) = self.clicked {
+            result[key as usize] = true;
+        }
+        for pad in self.pads.iter().flatten() {
+            for (k, held) in result.iter_mut().zip(pad.keys.iter()) {
+                *k |= *held;
+            }
+        }
+        result
+    }
+
+    /// Open a controller in the first free pad, if there is one.
+    /// # Arguments
+    /// * `joystick_index` SDL joystick index of the controller.
+    fn add_controller(&mut self, joystick_index: u32) -> Result<(), String> {
+        let controller = self.subsystem.open(joystick_index).map_err(|e| e.to_string())?;
+        if self.pad_index(controller.instance_id()).is_some() {
+            return Ok(());
+        }
+        match self.pads.iter().position(|p| p.is_none()) {
+            Some(n) => {
+                println!("Controller {} connected as pad {}.", controller.name(), n + 1);
+                self.pads[n] = Some(Pad { controller, keys: [false; 0x10] });
+                self.update_pad(n);
+            },
+            None => println!("Controller {} ignored, all pads are in use.", controller.name()),
+        }
+        Ok(())
+    }
+
+    /// Return the pad of a controller.
+    /// # Arguments
+    /// * `instance_id` SDL joystick instance id of the controller.
+    fn pad_index(&self, instance_id: u32) -> Option<usize> {
+        self.pads.iter().position(|p| match p {
+            Some(pad) => pad.controller.instance_id() == instance_id,
+            None => false,
+        })
+    }
+
+    /// Read the keys a pad holds down from its controller.
+    /// # Arguments
+    /// * `n` The pad.
+    fn update_pad(&mut self, n: usize) {
+        let deadzone = self.deadzone;
+        if let Some(pad) = &mut self.pads[n] {
+            pad.keys = [false; 0x10];
+            for &(control, key) in &self.controls[n] {
+                let held = match control {
+                    Control::Button(button) => pad.controller.button(button),
+                    Control::Axis(axis, true) => pad.controller.axis(axis) > deadzone,
+                    Control::Axis(axis, false) => pad.controller.axis(axis) < -deadzone,
+                };
+                pad.keys[key as usize] |= held;
+            }
+        }
+    }
+}
@@ -0,0 +1,246 @@
+//! Chip 8 keymaps.
+//! (C) Ryan Jeffrey <ryan@ryanmj.xyz>, 2022
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or (at
+// your option) any later version.
+
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// The conventional layout, the left of a QWERTY keyboard mirrors the
+/// COSMAC VIP keypad:
+/// ```text
+/// 1 2 3 4      1 2 3 C
+/// Q W E R  ->  4 5 6 D
+/// A S D F      7 8 9 E
+/// Z X C V      A 0 B F
+/// ```
+const DEFAULT_BINDINGS: [(&str, u8); 0x10] = [
+    ("1", 0x1), ("2", 0x2), ("3", 0x3), ("4", 0xC),
+    ("Q", 0x4), ("W", 0x5), ("E", 0x6), ("R", 0xD),
+    ("A", 0x7), ("S", 0x8), ("D", 0x9), ("F", 0xE),
+    ("Z", 0xA), ("X", 0x0), ("C", 0xB), ("V", 0xF),
+];
+
+/// The number of game controllers that can be used at once.
+pub const NUM_PADS: usize = 2;
+
+/// Axis positions closer to the centre than this are ignored.
+const DEFAULT_DEADZONE: u16 = 8000;
+
+/// Controller bindings of each pad. On the first the D-pad and left stick
+/// move with the keys most ROMs use for directions, 5 up, 8 down, 7 left,
+/// 9 right. On the second they press the right column of the keypad, C up
+/// and D down being the second player's keys in two player games like Pong.
+const DEFAULT_PAD_BINDINGS: [[(&str, u8); 10]; NUM_PADS] = [
+    [
+        ("dpup", 0x5), ("dpdown", 0x8), ("dpleft", 0x7), ("dpright", 0x9),
+        ("lefty-", 0x5), ("lefty+", 0x8), ("leftx-", 0x7), ("leftx+", 0x9),
+        ("a", 0x6), ("b", 0x4),
+    ],
+    [
+        ("dpup", 0xC), ("dpdown", 0xD), ("dpleft", 0xE), ("dpright", 0xF),
+        ("lefty-", 0xC), ("lefty+", 0xD), ("leftx-", 0xE), ("leftx+", 0xF),
+        ("a", 0xB), ("b", 0xA),
+    ],
+];
+
+/// Maps physical keys, by SDL scancode name, to the 16 chip 8 keys.
+/// Keymap files have one `scancode = key` binding per line, where key is a
+/// hex digit, `#` starts a comment. Scancode names are those of
+/// `SDL_GetScancodeName`, e.g. `Q`, `Up` or `Keypad 5`.
+///
+/// Game controllers are bound with `padN control = key`, N being 1 for the
+/// first controller plugged in and 2 for the second. Controls are SDL game
+/// controller button names, e.g. `a` or `dpup`, or axis names followed by
+/// the direction, e.g. `leftx-` or `righttrigger+`. `:deadzone n` sets how
+/// far, out of 32767, an axis must move to press its key.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Keymap {
+    /// Chip 8 keys keyed by lower case scancode name.
+    bindings: BTreeMap<String, u8>,
+    /// Chip 8 keys of every pad keyed by lower case control name.
+    pads: [BTreeMap<String, u8>; NUM_PADS],
+    /// Axis deadzone, if set.
+    deadzone: Option<u16>,
+}
+
+impl Default for Keymap {
+    /// The conventional 1234/QWER/ASDF/ZXCV layout.
+    fn default() -> Self {
+        let mut result = Self::new();
+        for (name, key) in DEFAULT_BINDINGS.iter() {
+            result.bind(name, *key);
+        }
+        for (pad, bindings) in DEFAULT_PAD_BINDINGS.iter().enumerate() {
+            for (control, key) in bindings.iter() {
+                result.bind_pad(pad, control, *key);
+            }
+        }
+        result
+    }
+}
+
+impl Keymap {
+    /// Create a keymap without any bindings.
+    pub fn new() -> Self {
+        Self {
+            bindings: BTreeMap::new(),
+            pads: Default::default(),
+            deadzone: None,
+        }
+    }
+
+    /// Parse the text of a keymap file. Return string on error.
+    /// # Arguments
+    /// * `text` Contents of the keymap file.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut result = Self::new();
+
+        for (n, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(deadzone) = line.strip_prefix(":deadzone") {
+                match deadzone.trim().parse::<u16>() {
+                    Ok(d) if d <= i16::MAX as u16 => result.deadzone = Some(d),
+                    _ => return Err(format!("Line {}: invalid deadzone {}.", n + 1, line)),
+                }
+                continue;
+            }
+            // Split at the last =, the = key is a scancode too.
+            let (name, key) = match line.rfind('=') {
+                Some(i) => (line[..i].trim(), line[i + 1..].trim()),
+                None => return Err(format!("Line {}: expected scancode = key.", n + 1)),
+            };
+            let key = match u8::from_str_radix(key, 16) {
+                Ok(k) if k <= 0xf && !name.is_empty() => k,
+                _ => return Err(format!("Line {}: invalid binding {}.", n + 1, line)),
+            };
+            match pad_control(name) {
+                Some((pad, control)) => result.bind_pad(pad, control, key),
+                None => result.bind(name, key),
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Load a keymap file. Return string on error.
+    /// # Arguments
+    /// * `path` Path of the keymap file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let text = fs::read_to_string(path.as_ref())
+            .map_err(|e| format!("{}: {}", path.as_ref().display(), e))?;
+        Self::parse(&text)
+    }
+
+    /// Load the keymap next to `rom`, the ROM's path with a .keymap
+    /// extension. Return None if there is none.
+    /// # Arguments
+    /// * `rom` Path of the ROM file.
+    pub fn load_for_rom(rom: &str) -> Result<Option<Self>, String> {
+        let path = Path::new(rom).with_extension("keymap");
+        if path.is_file() {
+            Self::load(path).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Bind a scancode to a chip 8 key, replacing its previous binding.
+    /// # Arguments
+    /// * `name` Scancode name.
+    /// * `key` Chip 8 key, 0-0xf.
+    pub fn bind(&mut self, name: &str, key: u8) {
+        self.bindings.insert(name.to_lowercase(), key);
+    }
+
+    /// Add every binding of `other`, replacing bindings of the same
+    /// scancodes. Used to apply per-ROM overrides.
+    /// # Arguments
+    /// * `other` The overriding keymap.
+    pub fn merge(&mut self, other: &Keymap) {
+        for (name, key) in &other.bindings {
+            self.bindings.insert(name.clone(), *key);
+        }
+        for (pad, bindings) in self.pads.iter_mut().enumerate() {
+            for (control, key) in &other.pads[pad] {
+                bindings.insert(control.clone(), *key);
+            }
+        }
+        if other.deadzone.is_some() {
+            self.deadzone = other.deadzone;
+        }
+    }
+
+    /// Bind a game controller button or axis direction to a chip 8 key,
+    /// replacing its previous binding.
+    /// # Arguments
+    /// * `pad` Controller number, from 0.
+    /// * `control` Button or axis direction name.
+    /// * `key` Chip 8 key, 0-0xf.
+    pub fn bind_pad(&mut self, pad: usize, control: &str, key: u8) {
+        self.pads[pad].insert(control.to_lowercase(), key);
+    }
+
+    /// Return the chip 8 key bound to a scancode.
+    /// # Arguments
+    /// * `name` Scancode name, case insensitive.
+    pub fn key(&self, name: &str) -> Option<u8> {
+        self.bindings.get(&name.to_lowercase()).copied()
+    }
+
+    /// Iterate over the scancode names, in lower case, and their keys.
+    pub fn bindings(&self) -> impl Iterator<Item = (&str, u8)> {
+        self.bindings.iter().map(|(n, k)| (n.as_str(), *k))
+    }
+
+    /// Return the chip 8 key bound to a game controller control.
+    /// # Arguments
+    /// * `pad` Controller number, from 0.
+    /// * `control` Button or axis direction name, case insensitive.
+    pub fn pad_key(&self, pad: usize, control: &str) -> Option<u8> {
+        self.pads.get(pad)?.get(&control.to_lowercase()).copied()
+    }
+
+    /// Iterate over the control names, in lower case, of a game controller
+    /// and their keys.
+    /// # Arguments
+    /// * `pad` Controller number, from 0.
+    pub fn pad_bindings(&self, pad: usize) -> impl Iterator<Item = (&str, u8)> {
+        self.pads[pad].iter().map(|(c, k)| (c.as_str(), *k))
+    }
+
+    /// Return how far an axis must move from the centre to press its key.
+    pub fn deadzone(&self) -> u16 {
+        self.deadzone.unwrap_or(DEFAULT_DEADZONE)
+    }
+}
+
+/// Split a `padN control` binding name into the pad, from 0, and control.
+/// Return None if it is a scancode name.
+/// # Arguments
+/// * `name` Binding name.
+fn pad_control(name: &str) -> Option<(usize, &str)> {
+    let lower = name.to_lowercase();
+    let number = lower.strip_prefix("pad")?.split_whitespace().next()?;
+    let pad = number.parse::<usize>().ok().filter(|p| *p >= 1 && *p <= NUM_PADS)?;
+    let control = name[3 + number.len()..].trim();
+    if control.is_empty() {
+        None
+    } else {
+        Some((pad - 1, control))
+    }
+}
@@ -0,0 +1,77 @@
+//! Chip 8 hex keypad layout and key polling.
+//! (C) Ryan Jeffrey <ryan@ryanmj.xyz>, 2022
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or (at
+// your option) any later version.
+
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use super::{Instruction, InterpreterData};
+
+/// Keys of the COSMAC VIP keypad, by row.
+pub const LAYOUT: [[u8; 4]; 4] = [
+    [0x1, 0x2, 0x3, 0xC],
+    [0x4, 0x5, 0x6, 0xD],
+    [0x7, 0x8, 0x9, 0xE],
+    [0xA, 0x0, 0xB, 0xF],
+];
+
+/// A key the program is waiting for.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum KeyPoll {
+    /// `Skp` or `SkpN` checks this key.
+    Key(u8),
+    /// `LdW` waits for any key.
+    Any,
+}
+
+/// Return the key the instruction about to run polls, if it polls one.
+/// # Arguments
+/// * `program` The chip8 program as Instructions.
+/// * `emu_state` The emulator state.
+pub fn polled_key(program: &[Instruction], emu_state: &InterpreterData) -> Option<KeyPoll> {
+    match program.get(emu_state.pc as usize)? {
+        Instruction::Skp(x) | Instruction::SkpN(x) => {
+            let key = emu_state.v[*x as usize];
+            if key <= 0xf {
+                Some(KeyPoll::Key(key))
+            } else {
+                None
+            }
+        },
+        Instruction::LdW(_) => Some(KeyPoll::Any),
+        _ => None,
+    }
+}
+
+/// Return the column and row of a key in the layout.
+/// # Arguments
+/// * `key` Chip 8 key, 0-0xf.
+pub fn key_position(key: u8) -> (usize, usize) {
+    for (row, keys) in LAYOUT.iter().enumerate() {
+        if let Some(column) = keys.iter().position(|k| *k == key) {
+            return (column, row);
+        }
+    }
+    panic!("Invalid key {}.", key);
+}
+
+/// Return the key under a point of a square keypad.
+/// # Arguments
+/// * `x` Horizontal position relative to the keypad's left edge.
+/// * `y` Vertical position relative to the keypad's top edge.
+/// * `size` Width and height of the keypad.
+pub fn key_at(x: i32, y: i32, size: u32) -> Option<u8> {
+    let size = size as i32;
+    if x < 0 || y < 0 || x >= size || y >= size {
+        return None;
+    }
+    Some(LAYOUT[(y * 4 / size) as usize][(x * 4 / size) as usize])
+}
@@ -0,0 +1,375 @@
+//! Chip 8 ROM launcher.
+//! (C) Ryan Jeffrey <ryan@ryanmj.xyz>, 2022
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or (at
+// your option) any later version.
+
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use analysis::{self, analyse};
+use machine::Machine;
+use source::{RomSource, is_zip, zip_entries};
+
+/// Extensions of the ROM files listed by the launcher, Octo cartridges and
+/// hex files last.
+pub const ROM_EXTENSIONS: [&str; 6] = ["ch8", "sc8", "xo8", "gif", "hex", "ihx"];
+
+/// The chip 8 variant a ROM is written for, from the least to the most
+/// capable.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Debug)]
+pub enum Platform {
+    /// The original chip 8.
+    Chip8,
+    /// SUPER-CHIP, with the 128x64 mode and scrolling.
+    SuperChip,
+    /// XO-CHIP, with colour planes, sound and 16 bit addresses.
+    XoChip,
+}
+
+impl Platform {
+    /// Return the name of the platform as shown in the launcher.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Platform::Chip8 => "CHIP-8",
+            Platform::SuperChip => "SCHIP",
+            Platform::XoChip => "XO-CHIP",
+        }
+    }
+
+    /// Return the platform ROMs with a file extension are written for.
+    /// # Arguments
+    /// * `extension` File extension, without the dot.
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "ch8" => Some(Platform::Chip8),
+            "sc8" => Some(Platform::SuperChip),
+            "xo8" => Some(Platform::XoChip),
+            _ => None,
+        }
+    }
+
+    /// Return the platform of an opcode only an extension has, if it has
+    /// one.
+    /// # Arguments
+    /// * `opcode` 16 bit opcode.
+    fn of_opcode(opcode: u16) -> Option<Self> {
+        match opcode {
+            // Long I, planes, audio, pitch, register ranges and scroll up.
+            0xF000 | 0xF002 => Some(Platform::XoChip),
+            _ if opcode & 0xF0FF == 0xF001 || opcode & 0xF0FF == 0xF03A => Some(Platform::XoChip),
+            _ if opcode & 0xF00F == 0x5002 || opcode & 0xF00F == 0x5003 => Some(Platform::XoChip),
+            _ if opcode & 0xFFF0 == 0x00D0 => Some(Platform::XoChip),
+            // Scrolling, resolution, exit, big font and flag registers.
+            0x00FB..=0x00FF => Some(Platform::SuperChip),
+            _ if opcode & 0xFFF0 == 0x00C0 && opcode != 0x00C0 => Some(Platform::SuperChip),
+            _ if opcode & 0xF0FF == 0xF030 || opcode & 0xF0FF == 0xF075 ||
+                opcode & 0xF0FF == 0xF085 => Some(Platform::SuperChip),
+            _ => None,
+        }
+    }
+
+    /// Detect the platform a ROM is written for from the extension opcodes
+    /// in its reachable code, chip 8 if there are none. Data is not looked
+    /// at, so sprites that look like opcodes do not count. The ROM is
+    /// taken to be loaded at 0x200.
+    /// # Arguments
+    /// * `rom` The ROM bytes.
+    pub fn detect(rom: &[u8]) -> Self {
+        let origin = Machine::default().load_address;
+        let word = |address: u16| {
+            let offset = (address - origin) as usize;
+            rom.get(offset..offset + 2).map(|w| (w[0] as u16) << 8 | w[1] as u16)
+        };
+        let mut result = Platform::Chip8;
+        for block in analyse(rom, origin).blocks() {
+            let opcodes = block.instructions.iter().map(|&(_, opcode)| opcode);
+            // Analysis stops at opcodes chip 8 does not have.
+            let stop = match block.exit {
+                analysis::Exit::Invalid => word(block.end()),
+                _ => None,
+            };
+            for opcode in opcodes.chain(stop) {
+                if let Some(platform) = Self::of_opcode(opcode) {
+                    result = result.max(platform);
+                }
+            }
+        }
+        result
+    }
+}
+
+/// A ROM listed by the launcher.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct RomEntry {
+    /// Path of the ROM file, or of the ZIP archive holding it.
+    pub path: PathBuf,
+    /// Name of the ROM in its archive, None for ROM files.
+    pub entry: Option<String>,
+    /// Title, from the file or entry name.
+    pub title: String,
+    /// Size in bytes, of the assembled program for cartridges.
+    pub size: u64,
+    /// Platform the ROM is written for, from its extension or its code,
+    /// whichever needs more.
+    pub platform: Platform,
+}
+
+impl RomEntry {
+    /// Read a ROM and describe it. Return string on error.
+    /// # Arguments
+    /// * `source` Where the ROM comes from.
+    pub fn load(source: &RomSource) -> Result<Self, String> {
+        let rom = source.read(&Machine::default())?;
+        let name = source.name();
+        let extension = name.extension().and_then(|e| e.to_str())
+            .and_then(Platform::from_extension)
+            .unwrap_or(Platform::Chip8);
+        Ok(Self {
+            path: source.path().to_path_buf(),
+            entry: source.entry().map(String::from),
+            title: title(name),
+            size: rom.len() as u64,
+            platform: extension.max(Platform::detect(&rom)),
+        })
+    }
+}
+
+/// Return the title of a ROM from its file name, without the extension and
+/// with underscores and dashes as spaces.
+/// # Arguments
+/// * `path` Path of the ROM file.
+pub fn title(path: &Path) -> String {
+    path.file_stem().map(|s| s.to_string_lossy().replace(['_', '-'], " "))
+        .unwrap_or_default()
+}
+
+/// Return a file size for people, e.g. `246 B` or `3.5 KB`.
+/// # Arguments
+/// * `size` Size in bytes.
+pub fn format_size(size: u64) -> String {
+    if size < 1024 {
+        format!("{} B", size)
+    } else {
+        format!("{:.1} KB", size as f64 / 1024.0)
+    }
+}
+
+/// Sort ROMs by title.
+/// # Arguments
+/// * `entries` The ROMs.
+fn sort(entries: &mut [RomEntry]) {
+    entries.sort_by(|a, b| a.title.to_lowercase().cmp(&b.title.to_lowercase())
+                    .then_with(|| a.path.cmp(&b.path))
+                    .then_with(|| a.entry.cmp(&b.entry)));
+}
+
+/// Return the ROMs in a ZIP archive, sorted by title, leaving out those
+/// that cannot be read. Return string if the archive cannot be.
+/// # Arguments
+/// * `path` Path of the archive.
+pub fn scan_archive(path: &Path) -> Result<Vec<RomEntry>, String> {
+    let mut result: Vec<RomEntry> = zip_entries(path)?.into_iter()
+        .filter_map(|entry| {
+            RomEntry::load(&RomSource::Zip { archive: path.to_path_buf(), entry }).ok()
+        })
+        .collect();
+    sort(&mut result);
+    Ok(result)
+}
+
+/// Return the ROM files in a directory and the ROMs in its ZIP archives,
+/// sorted by title, leaving out the files and archives that cannot be read
+/// or decoded, such as GIF images that are not Octo cartridges. Return
+/// string if the directory cannot be read.
+/// # Arguments
+/// * `dir` The directory.
+pub fn scan(dir: &Path) -> Result<Vec<RomEntry>, String> {
+    let mut result = Vec::new();
+    for entry in fs::read_dir(dir).map_err(|e| format!("{}: {}", dir.display(), e))? {
+        let path = match entry {
+            Ok(entry) => entry.path(),
+            Err(_) => continue,
+        };
+        let extension = path.extension().and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase())
+            .unwrap_or_default();
+        if !path.is_file() {
+            continue;
+        }
+        if extension == "zip" && is_zip(&path) {
+            result.extend(scan_archive(&path).unwrap_or_default());
+            continue;
+        }
+        if !ROM_EXTENSIONS.contains(&extension.as_str()) {
+            continue;
+        }
+        if let Ok(entry) = RomEntry::load(&RomSource::File(path)) {
+            result.push(entry);
+        }
+    }
+    sort(&mut result);
+    Ok(result)
+}
+
+/// How a game ended.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum Exit {
+    /// The window was closed.
+    Quit,
+    /// The player went back to the launcher.
+    Menu,
+    /// Run a ROM from the start, one dropped on the window or a new build,
+    /// with its name in its ZIP archive.
+    Load(String, Option<String>),
+}
+
+/// The launcher between games: the ROM to run next, the one run last and
+/// why the last one could not be run, if it could not.
+#[derive(Default)]
+pub struct Session {
+    /// ROM to run next and its name in its archive, None to show the menu.
+    next: Option<(String, Option<String>)>,
+    /// ROM run last, selected in the menu.
+    last: Option<(String, Option<String>)>,
+    /// Why the last ROM could not be run, shown in the menu.
+    error: Option<String>,
+}
+
+impl Session {
+    /// Create a session running a ROM first, or showing the menu.
+    /// # Arguments
+    /// * `rom` The ROM to run and its name in its archive.
+    pub fn new(rom: Option<(String, Option<String>)>) -> Self {
+        Self {
+            next: rom,
+            ..Self::default()
+        }
+    }
+
+    /// Return the ROM to run next, None to show the menu.
+    pub fn take_next(&mut self) -> Option<(String, Option<String>)> {
+        self.next.take()
+    }
+
+    /// Return the ROM run last and its name in its archive.
+    pub fn last(&self) -> Option<(&str, Option<&str>)> {
+        self.last.as_ref().map(|(path, entry)| (path.as_str(), entry.as_deref()))
+    }
+
+    /// Return why the last ROM could not be run.
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// Note that a ROM is run, forgetting the error of the one before.
+    /// # Arguments
+    /// * `path` Path of the ROM file or archive.
+    /// * `entry` Name of the ROM in the archive.
+    pub fn start(&mut self, path: &str, entry: Option<&str>) {
+        self.last = Some((String::from(path), entry.map(String::from)));
+        self.error = None;
+    }
+
+    /// Note how a game ended, or the error that stopped it from running,
+    /// which goes back to the menu. Return false if the emulator quits.
+    /// # Arguments
+    /// * `result` How the game ended.
+    pub fn end(&mut self, result: Result<Exit, String>) -> bool {
+        match result {
+            Ok(Exit::Quit) => return false,
+            Ok(Exit::Menu) => {},
+            Ok(Exit::Load(path, entry)) => self.next = Some((path, entry)),
+            Err(e) => {
+                self.next = None;
+                self.error = Some(e);
+            },
+        }
+        true
+    }
+}
+
+/// The launcher menu: the ROMs, the selected one and the scroll position.
+pub struct Menu {
+    entries: Vec<RomEntry>,
+    /// Index of the selected entry.
+    selected: usize,
+    /// Index of the first entry shown.
+    top: usize,
+}
+
+impl Menu {
+    /// Create a menu with the first entry selected.
+    /// # Arguments
+    /// * `entries` The ROMs to choose from.
+    pub fn new(entries: Vec<RomEntry>) -> Self {
+        Self {
+            entries,
+            selected: 0,
+            top: 0,
+        }
+    }
+
+    /// Return the ROMs to choose from.
+    pub fn entries(&self) -> &[RomEntry] {
+        &self.entries
+    }
+
+    /// Return the selected ROM, None if there are none.
+    pub fn selected(&self) -> Option<&RomEntry> {
+        self.entries.get(self.selected)
+    }
+
+    /// Select the entry of a ROM, if it is listed.
+    /// # Arguments
+    /// * `path` Path of the ROM file or archive.
+    /// * `entry` Name of the ROM in the archive, None for ROM files.
+    pub fn select(&mut self, path: &Path, entry: Option<&str>) {
+        if let Some(i) = self.entries.iter()
+            .position(|e| e.path == path && e.entry.as_deref() == entry) {
+            self.selected = i;
+        }
+    }
+
+    /// Move the selection, stopping at the first and last entries.
+    /// # Arguments
+    /// * `delta` Entries to move down by, negative to move up.
+    pub fn move_by(&mut self, delta: isize) {
+        let last = self.entries.len().saturating_sub(1) as isize;
+        self.selected = (self.selected as isize + delta).clamp(0, last) as usize;
+    }
+
+    /// Return the lines to show, scrolled so the selection is visible, and
+    /// the index of the selected line. Each line has the title, size and
+    /// platform of a ROM in columns.
+    /// # Arguments
+    /// * `columns` Width of a line in characters.
+    /// * `rows` Number of lines that fit.
+    pub fn lines(&mut self, columns: usize, rows: usize) -> (Vec<String>, Option<usize>) {
+        if self.entries.is_empty() || rows == 0 {
+            return (Vec::new(), None);
+        }
+        if self.selected < self.top {
+            self.top = self.selected;
+        } else if self.selected >= self.top + rows {
+            self.top = self.selected + 1 - rows;
+        }
+        // Title, then size and platform right aligned.
+        let title_width = columns.saturating_sub(18).max(1);
+        let lines = self.entries.iter().skip(self.top).take(rows).map(|e| {
+            let title: String = e.title.chars().take(title_width).collect();
+            format!("{:<tw$} {:>8} {:>8}", title, format_size(e.size), e.platform.name(),
+                    tw = title_width)
+        }).collect();
+        (lines, Some(self.selected - self.top))
+    }
+}
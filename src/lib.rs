@@ -0,0 +1,959 @@
+//! Chip 8 emulator library.
+//! (C) Ryan Jeffrey <ryan@ryanmj.xyz>, 2022
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or (at
+// your option) any later version.
+
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+#[cfg(test)]
+mod lib_test;
+pub mod analysis;
+pub mod cartridge;
+pub mod debugger;
+pub mod disasm;
+pub mod display;
+pub mod engine;
+pub mod filter;
+pub mod font;
+pub mod framebuffer;
+pub mod image;
+pub mod keymap;
+pub mod keypad;
+pub mod launcher;
+pub mod machine;
+pub mod octo;
+pub mod options;
+pub mod palette;
+pub mod patch;
+pub mod quirks;
+pub mod recording;
+pub mod source;
+pub mod symbols;
+pub mod text;
+pub mod watch;
+extern crate gif;
+extern crate png;
+extern crate rand;
+extern crate zip;
+
+use std::cmp;
+use std::collections::HashMap;
+use std::ops::Range;
+
+use framebuffer::Framebuffer;
+use machine::Machine;
+use quirks::Quirks;
+use source::RomSource;
+use rand::Rng;
+use rand::rngs::ThreadRng;
+
+/// The number of rows on the screen (chip 8 height).
+pub const NUM_ROWS: usize = 32;
+/// The number of columns on the screen (chip 8 width).
+pub const NUM_COLS: usize = 64;
+
+/// From http://devernay.free.fr/hacks/chip8/C8TECH10.HTM#dispcoords
+/// The chip 8 font sprites.
+pub const FONTSET: [u8; 0x10 * 5] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+	0x20, 0x60, 0x20, 0x20, 0x70, // 1
+	0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+	0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+	0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+	0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+	0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+	0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+	0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+	0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+	0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+	0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+	0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+	0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+	0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+	0xF0, 0x80, 0xF0, 0x80, 0x80  // F
+];
+
+/// Interpreter state.
+pub struct InterpreterData {
+    /// V registers. 16 of them, general purpose, 8 bits.
+    pub v: [u8; 16],
+    /// I register, 16 bits.
+    pub i: u16,
+    /// Program counter, 16 bits.
+    pub pc: u16,
+    /// Stack pointer, the number of return addresses on the stack.
+    pub sp: u8,
+    /// The call stack, the machine's depth of instruction positions to
+    /// return to. Empty when the machine keeps the stack in memory.
+    pub stack: Vec<u16>,
+    /// Delay timer @ 60Hz, 8 bits.
+    pub delay_timer: u8,
+    /// Sound timer @ 60Hz, 8 bits.
+    pub sound_timer: u8,
+    /// Memory, of the machine's size.
+    pub mem: Vec<u8>,
+    /// The screen.
+    pub screen: Framebuffer,
+    /// Redraw the screen flag.
+    pub draw: bool,
+    /// Behaviours of the interpreter being emulated.
+    pub quirks: Quirks,
+    /// Where the ROM and font are loaded and how much memory there is.
+    pub machine: Machine,
+    /// Why emulation stopped, such as a stack overflow. No instruction runs
+    /// while it is set.
+    pub error: Option<String>,
+    /// A frame started since the last instruction, ending the display wait.
+    vblank: bool,
+    /// Instructions the program wrote over its code, by position, run in
+    /// place of those it was loaded with.
+    rewritten: HashMap<usize, Instruction>,
+    /// Rng.
+    rng: ThreadRng,
+}
+
+impl InterpreterData {
+    /// Create a new Interpreter state struct, 0 initialize.
+    pub fn new() -> Self {
+        Self::with_machine(Machine::default())
+    }
+
+    /// Create a new Interpreter state struct for a machine, 0 initialize.
+    /// # Arguments
+    /// * `machine` Memory layout of the machine.
+    pub fn with_machine(machine: Machine) -> Self {
+        Self {
+            v: [0; 16],
+            i: 0,
+            pc: 0,
+            sp: 0,
+            stack: if machine.stack_address.is_some() { Vec::new() } else {
+                vec![0; machine.stack_depth]
+            },
+            delay_timer: 0,
+            sound_timer: 0,
+            mem: vec![0; machine.memory_size],
+            screen: Framebuffer::new(NUM_COLS, NUM_ROWS, 1),
+            draw: false,
+            quirks: Quirks::default(),
+            machine,
+            error: None,
+            vblank: false,
+            rewritten: HashMap::new(),
+            rng: rand::thread_rng(),
+        }
+    }
+
+    /// Load the font and a ROM at the machine's addresses. The ROM must fit,
+    /// see `Machine::check_rom`.
+    /// # Arguments
+    /// * `rom` The ROM bytes.
+    pub fn load(&mut self, rom: &[u8]) {
+        let font = self.machine.font.bytes();
+        let font_start = self.machine.font_address as usize;
+        self.mem[font_start..font_start + font.len()].copy_from_slice(&font);
+        let start = self.machine.load_address as usize;
+        self.mem[start..start + rom.len()].copy_from_slice(rom);
+        self.rewritten.clear();
+    }
+
+    /// Replace the loaded ROM with a new build, keeping the registers, the
+    /// screen and the memory outside the program area.
+    /// # Arguments
+    /// * `old_len` Length of the loaded ROM, whose bytes are cleared.
+    /// * `rom` The new ROM bytes.
+    pub fn reload(&mut self, old_len: usize, rom: &[u8]) {
+        let start = self.machine.load_address as usize;
+        self.mem[start..start + old_len].iter_mut().for_each(|b| *b = 0);
+        self.mem[start..start + rom.len()].copy_from_slice(rom);
+        self.rewritten.clear();
+    }
+
+    /// Return the instruction at a position of the program, or the one the
+    /// program wrote over it with.
+    /// # Arguments
+    /// * `program` The chip8 program as Instructions.
+    /// * `pos` Instruction position, inside the program.
+    fn instruction(&self, program: &[Instruction], pos: usize) -> Instruction {
+        if self.rewritten.is_empty() {
+            return program[pos];
+        }
+        self.rewritten.get(&pos).copied().unwrap_or(program[pos])
+    }
+
+    /// Decode the code the program wrote over again from memory. Return the
+    /// first and last instruction positions written over, inclusive, or None
+    /// if the bytes are outside the program.
+    /// # Arguments
+    /// * `len` Number of instructions in the program.
+    /// * `written` Memory addresses that were written to.
+    fn rewrite(&mut self, len: usize, written: Range<usize>) -> Option<(usize, usize)> {
+        let start = self.machine.load_address as usize;
+        let code_end = start + 2 * len;
+        if written.end <= start || written.start >= code_end || written.is_empty() {
+            return None;
+        }
+        let first = (cmp::max(written.start, start) - start) / 2;
+        let last = (cmp::min(written.end, code_end) - 1 - start) / 2;
+        for pos in first..=last {
+            let address = start + 2 * pos;
+            let opcode = (self.mem[address] as u16) << 8 | self.mem[address + 1] as u16;
+            // Data becomes SYS 0, as in `convert_program`.
+            let instruction = program_to_enum_at(opcode, &self.machine)
+                .unwrap_or(Instruction::Sys(0));
+            self.rewritten.insert(pos, instruction);
+        }
+        Some((first, last))
+    }
+
+    /// Start a new 60 Hz frame, decrementing the timers and ending the
+    /// display wait of a `Drw`.
+    pub fn tick_frame(&mut self) {
+        if self.delay_timer > 0 {
+            self.delay_timer -= 1;
+        }
+        if self.sound_timer > 0 {
+            self.sound_timer -= 1;
+        }
+        self.vblank = true;
+    }
+
+    /// Pop the stack and return the instruction position on top of it.
+    /// Return string if the stack is empty.
+    fn pop_stack(&mut self) -> Result<u16, String> {
+        if self.sp == 0 {
+            return Err(format!("Stack underflow at 0x{:X}, returning with an empty stack.",
+                               self.machine.address(self.pc)));
+        }
+        self.sp -= 1;
+        match self.machine.stack_address {
+            Some(address) => {
+                let slot = address as usize + 2 * self.sp as usize;
+                let address = u16::from_be_bytes([self.mem[slot], self.mem[slot + 1]]);
+                Ok(self.machine.instruction_pos(address))
+            },
+            None => Ok(self.stack[self.sp as usize]),
+        }
+    }
+
+    /// Push an instruction position to the stack, as its memory address if
+    /// the stack is in memory. Return string if the stack is full.
+    /// # Arguments
+    /// * `pos` The instruction position to return to.
+    fn push_stack(&mut self, pos: u16) -> Result<(), String> {
+        if self.sp as usize >= self.machine.stack_depth {
+            return Err(format!("Stack overflow at 0x{:X}, all {} levels are in use.",
+                               self.machine.address(self.pc), self.machine.stack_depth));
+        }
+        match self.machine.stack_address {
+            Some(address) => {
+                let slot = address as usize + 2 * self.sp as usize;
+                let bytes = self.machine.address(pos).to_be_bytes();
+                self.mem[slot..slot + 2].copy_from_slice(&bytes);
+            },
+            None => self.stack[self.sp as usize] = pos,
+        }
+        self.sp += 1;
+        Ok(())
+    }
+
+    /// Get the value of register reg.
+    /// # Arguments
+    /// * `reg` The register to return. Valid from 0-0xf.
+    fn get_register(&self, reg: u8) -> u8 {
+        self.v[reg as usize]
+    }
+
+    /// Set the value of register reg.
+    /// # Arguments
+    /// * `reg` The register to set. Valid from 0-0xf.
+    /// * `value` The value to place in the register.
+    fn set_register(&mut self, reg: u8, value: u8) {
+        self.v[reg as usize] = value;
+    }
+
+    /// Display n-byte sprite starting at memory location I at (V[x], V[y]),
+    /// set VF = collision.
+    /// # Arguments
+    /// * `x` The register holding the column.
+    /// * `y` The register holding the row.
+    /// * `n` The height of the sprite.
+    fn draw_sprite(&mut self, x: u8, y: u8, n: u8) {
+        let (col, row) = (self.get_register(x) as usize, self.get_register(y) as usize);
+        let (width, height) = (self.screen.width(), self.screen.height());
+        // Clipped sprites start wrapped around but stop at the edges.
+        let (col, row, len) = if self.quirks.clip {
+            (col % width, row % height, (width - col % width).min(8))
+        } else {
+            (col, row, 8)
+        };
+        let mut collision = false;
+        for i in 0..(n as usize) {
+            if self.quirks.clip && row + i >= height {
+                break;
+            }
+            let sb = self.mem[self.i as usize + i];
+            collision |= self.screen.xor_sprite_row(0, col, row + i, sb as u64 >> (8 - len), len);
+        }
+        self.set_register(0xf, collision as u8);
+        self.draw = true;
+    }
+
+    /// Add `amount` to the current program counter, return result.
+    /// # Arguments
+    /// * `amount` The amount to add to the program counter.
+    fn increment_pc(&self, amount: u16) -> u16 {
+        self.pc + amount
+    }
+}
+
+impl Default for InterpreterData {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Chip 8 instruction and their arguments.
+#[derive(PartialEq, Eq, Copy, Clone)]
+pub enum Instruction {
+    /// Sys, ignored in this emulator.
+    Sys(u16),
+    /// Clear the screen.
+    Cls,
+    /// Return from the current subroutine, pop the stack.
+    Ret,
+    /// Jump to memory address.
+    Jp(u16),
+    /// Call subroutine, push to the stack.
+    Call(u16),
+    /// Skip next instruction if register equals value.
+    Se(u8, u8),
+    /// Skip next instruction if register does not equal value.
+    Sne(u8, u8),
+    /// Skip next instruction if registers are equal.
+    SeR(u8, u8),
+    /// Take register and load bottom byte.
+    Ld(u8, u8),
+    /// Add register to bottom byte.
+    Add(u8, u8),
+    /// Load register into other register.
+    LdR(u8, u8),
+    /// Bitwise OR registers.
+    Or(u8, u8),
+    /// Bitwise And registers.
+    And(u8, u8),
+    /// Bitwise Xor registers.
+    Xor(u8, u8),
+    /// Add first register to second register, store in first register.
+    AddR(u8, u8),
+    /// Subtract register to bottom byte.
+    Sub(u8, u8),
+    /// Divide register value by 2.
+    Shr(u8, u8),
+    /// Subtract second register by first register.
+    SubN(u8, u8),
+    /// Multiply register by 2.
+    Shl(u8, u8),
+    /// Skip next instruction if two registers do not equal.
+    SneR(u8, u8),
+    /// Load value into I register.
+    LdI(u16),
+    /// Jump to value + I.
+    JpI(u16),
+    /// Place random number AND bottom byte into register.
+    Rnd(u8, u8),
+    /// Draw n byte sprite at position gained from first two registers.
+    Drw(u8, u8, u8),
+    /// Skip next instruction if key is pressed.
+    Skp(u8),
+    /// Skip next instruction if key is not pressed.
+    SkpN(u8),
+    /// Load the value of the delay timer into register.
+    LdD(u8),
+    /// Load keypress, halt until key is pressed.
+    LdW(u8),
+    /// Set delay time value.
+    LdSD(u8),
+    /// Set sound time value.
+    LdS(u8),
+    /// Add value of register with I register, store in first register.
+    AddI(u8),
+    /// Load sprite location from V[x].
+    LdSp(u8),
+    /// Load big sprite location from V[x], SUPER-CHIP.
+    LdHf(u8),
+    /// Store BCD repr of V[x] in I, I + 1, I + 2.
+    LdBCD(u8),
+    /// Store registers V[0] to V[x] in memory starting at I.
+    LdIR(u8),
+    /// Store memory at I into V[0] to V[x].
+    LdIRM(u8),
+}
+
+/// Instruction interpretation error.
+pub enum InstructionError {
+    /// Instruction interpretation error.
+    InvalidInstruction
+}
+
+/// Return the bottom three nibbles from opcode.
+/// # Arguments
+/// * `n` 16 bit opcode.
+fn get_last_3_nibbles(n: u16) -> u16 {
+    n & 0x0FFFu16
+}
+
+/// Return the bottom byte from opcode.
+/// # Arguments
+/// * `n` 16 bit opcode.
+fn get_last_2_nibbles(n: u16) -> u8 {
+    (n & 0x00FFu16) as u8
+}
+
+
+/// Return the top second nibble from opcode.
+/// # Arguments
+/// * `n` 16 bit opcode.
+fn get_third_nibble(n: u16) -> u8 {
+    ((n & 0x0F00u16) >> 8) as u8
+}
+
+/// Return the top third nibble from opcode.
+/// # Arguments
+/// * `n` 16 bit opcode.
+fn get_second_nibble(n: u16) -> u8 {
+    ((n & 0x00F0u16) >> 4) as u8
+}
+
+/// Return the bottom nibble from opcode.
+/// # Arguments
+/// * `n` 16 bit opcode.
+fn get_first_nibble(n: u16) -> u8 {
+    (n & 0x000fu16) as u8
+}
+
+/// Return the top nibble from opcode.
+/// # Arguments
+/// * `n` 16 bit opcode.
+fn get_fourth_nibble(n: u16) -> u8 {
+    ((n & 0xf000u16) >> 12) as u8
+}
+
+/// Emulate chip8 instruction at emu_state's program counter. Does nothing
+/// once `emu_state.error` is set, by a stack overflow or underflow or by
+/// running past the end of the program. Code the
+/// program writes over is decoded again and run as it is in memory.
+/// # Arguments
+/// * `program` The chip8 program as Instructions.
+/// * `emu_state` The emulator state to change.
+/// * `cur_pressed_keys` Keypad state.
+#[allow(clippy::assign_op_pattern)]
+pub fn emulate(program: &[Instruction], emu_state: &mut InterpreterData,
+           cur_pressed_keys: &[bool; 0x10]) {
+    type I = Instruction;
+
+    if emu_state.error.is_some() {
+        return;
+    }
+    if emu_state.pc as usize >= program.len() {
+        emu_state.error = Some(format!("Ran past the end of the program at 0x{:X}.",
+                                       emu_state.machine.address(emu_state.pc)));
+        return;
+    }
+    let instruction = emu_state.instruction(program, emu_state.pc as usize);
+    let vblank = std::mem::replace(&mut emu_state.vblank, false);
+
+    // Check first nibble, store result of match in the program counter.
+    emu_state.pc = match instruction {
+        I::Sys(..) => {
+            // Ignored on modern interpreters.
+            emu_state.increment_pc(1)
+        },
+        I::Cls => {
+            // Clear the display.
+            emu_state.screen.clear();
+            emu_state.increment_pc(1)
+        },
+        I::Ret => {
+            // Pop the instruction after the call, stay put on underflow.
+            match emu_state.pop_stack() {
+                Ok(pos) => pos,
+                Err(e) => {
+                    emu_state.error = Some(e);
+                    emu_state.pc
+                },
+            }
+        },
+        I::Jp(nnn) => {
+            nnn
+        },
+        // Function call at bottom three nibbles.
+        I::Call(nnn) => {
+            let next = emu_state.increment_pc(1);
+            match emu_state.push_stack(next) {
+                Ok(()) => nnn,
+                Err(e) => {
+                    emu_state.error = Some(e);
+                    emu_state.pc
+                },
+            }
+        },
+        // Skip next instruction if the bottom byte is equal to the value
+        // in V[first nibble].
+        I::Se(x, kk) => {
+            if emu_state.get_register(x) == kk {
+                emu_state.increment_pc(2)
+            } else {
+                emu_state.increment_pc(1)
+            }
+        },
+        // Skip next instruction if V[third nibble] == bottom byte.
+        I::Sne(x, kk) => {
+            if emu_state.get_register(x) != kk {
+                emu_state.increment_pc(2)
+            } else {
+                emu_state.increment_pc(1)
+            }
+        },
+        // If V[third nibble] == V[second nibble] then skip next instruction.
+        I::SeR(x, y) => {
+            if emu_state.get_register(x) == emu_state.get_register(y) {
+                    emu_state.increment_pc(2)
+                } else {
+                    emu_state.increment_pc(1)
+                }
+        },
+        // Put the bottom byte into register V[third nibble].
+        I::Ld(x, kk) => {
+            emu_state.set_register(x, kk);
+            emu_state.increment_pc(1)
+        },
+        // Adds the bottom byte to the value of V[third nibble], then
+        // stores it there.
+        I::Add(x, kk) => {
+            emu_state.set_register(x, emu_state.get_register(x) + kk);
+            emu_state.increment_pc(1)
+        },
+        I::LdR(x, y) => {
+            emu_state.set_register(x, emu_state.get_register(y));
+            emu_state.increment_pc(1)
+        },
+        I::Or(x, y) => {
+            emu_state.set_register(x,
+                                   emu_state.get_register(x) |
+                                   emu_state.get_register(y));
+            emu_state.increment_pc(1)
+        },
+        I::And(x, y) => {
+            emu_state.set_register(x,
+                                   emu_state.get_register(x) &
+                                   emu_state.get_register(y));
+            emu_state.increment_pc(1)
+        },
+        I::Xor(x, y) => {
+            emu_state.set_register(x,
+                                   emu_state.get_register(x) ^
+                                   emu_state.get_register(y));
+            emu_state.increment_pc(1)
+        },
+        I::AddR(x, y) => {
+            emu_state.set_register(x,
+                                   emu_state.get_register(x) +
+                                   emu_state.get_register(y));
+            if emu_state.get_register(x) < emu_state.get_register(y) {
+                emu_state.set_register(0xf, 1);
+            }
+            emu_state.increment_pc(1)
+        },
+        I::Sub(x, y) => {
+            if emu_state.get_register(x) > emu_state.get_register(y) {
+                emu_state.set_register(0xf, 1);
+            }
+            emu_state.set_register(x,
+                                   emu_state.get_register(x) -
+                                   emu_state.get_register(y));
+            emu_state.increment_pc(1)
+        },
+        I::Shr(x, y) => {
+            let y = if emu_state.quirks.shift { x } else { y };
+            if emu_state.get_register(y) & 1 == 1 {
+                emu_state.set_register(0xf, 1);
+            }
+            emu_state.set_register(x,  emu_state.get_register(y) >> 1);
+            emu_state.increment_pc(1)
+        },
+        I::SubN(x, y) => {
+            if emu_state.get_register(x) < emu_state.get_register(y) {
+                emu_state.set_register(0xf, 1);
+            }
+            emu_state.set_register(x,
+                                   emu_state.get_register(y) -
+                                   emu_state.get_register(x));
+            emu_state.increment_pc(1)
+        },
+        I::Shl(x, y) => {
+            let y = if emu_state.quirks.shift { x } else { y };
+            if emu_state.get_register(y) & 0x80 != 0 {
+                emu_state.set_register(0xf, 1);
+            }
+            emu_state.set_register(x,
+                                   emu_state.get_register(y) << 1);
+            emu_state.increment_pc(1)
+        },
+        I::SneR(x, y) => {
+            if emu_state.get_register(x) != emu_state.get_register(y) {
+                emu_state.increment_pc(2)
+            } else {
+                emu_state.increment_pc(1)
+            }
+        },
+        I::LdI(nnn) => {
+            emu_state.i = nnn;
+            emu_state.increment_pc(1)
+        },
+        I::JpI(nnn) => {
+            // SUPER-CHIP jumps by the register of the address's top digit.
+            let x = if emu_state.quirks.jump {
+                (emu_state.machine.address(nnn) >> 8 & 0xf) as u8
+            } else {
+                0
+            };
+            nnn + emu_state.get_register(x) as u16
+        },
+        I::Rnd(x, kk) => {
+            let rn = emu_state.rng.gen::<u8>();
+            emu_state.set_register(x, rn & kk);
+            emu_state.increment_pc(1)
+        },
+        // Display n-byte sprite starting at memory location I at (Vx, Vy),
+        // set VF = collision.
+        // With the display wait, only draw as the first instruction of a
+        // frame, otherwise run it again until then.
+        I::Drw(..) if emu_state.quirks.display_wait && !vblank => {
+            emu_state.pc
+        },
+        I::Drw(x, y, n) => {
+            emu_state.draw_sprite(x, y, n);
+            emu_state.increment_pc(1)
+        },
+        I::Skp(x) => {
+            if cur_pressed_keys[emu_state.get_register(x) as usize] {
+                emu_state.increment_pc(2)
+            } else {
+                emu_state.increment_pc(1)
+            }
+        },
+        I::SkpN(x) => {
+            if !cur_pressed_keys[emu_state.get_register(x) as usize] {
+                emu_state.increment_pc(2)
+            } else {
+                emu_state.increment_pc(1)
+            }
+        },
+        I::LdD(x) => {
+            emu_state.set_register(x, emu_state.delay_timer);
+            emu_state.increment_pc(1)
+        },
+        I::LdW(_) => {
+            // TODO
+            emu_state.increment_pc(1)
+        },
+        I::LdSD(x) => {
+            emu_state.delay_timer = emu_state.get_register(x);
+            emu_state.increment_pc(1)
+        },
+        I::LdS(x) => {
+            emu_state.sound_timer = emu_state.get_register(x);
+            emu_state.increment_pc(1)
+        },
+        I::AddI(x) => {
+            emu_state.i = emu_state.i + emu_state.get_register(x) as u16;
+            emu_state.increment_pc(1)
+        },
+        I::LdSp(x) => {
+            emu_state.i = emu_state.machine.font_address +
+                5 * emu_state.get_register(x) as u16;
+            emu_state.increment_pc(1)
+        },
+        I::LdHf(x) => {
+            emu_state.i = emu_state.machine.big_font_address() +
+                10 * emu_state.get_register(x) as u16;
+            emu_state.increment_pc(1)
+        },
+        I::LdBCD(x) => {
+            let n = emu_state.get_register(x);
+            let i = emu_state.i as usize;
+            emu_state.mem[i] = (n / 100) % 10;
+            emu_state.mem[i + 1] = (n / 10) % 10;
+            emu_state.mem[i + 2] = n % 10;
+            emu_state.rewrite(program.len(), i..i + 3);
+            emu_state.increment_pc(1)
+        },
+        I::LdIR(x) => {
+            for i in 0..=(x as u16) {
+                emu_state.mem[(emu_state.i + i) as usize] =
+                    emu_state.get_register(i as u8);
+            }
+            let i = emu_state.i as usize;
+            emu_state.rewrite(program.len(), i..i + x as usize + 1);
+            if emu_state.quirks.increment_i {
+                emu_state.i = emu_state.i.wrapping_add(x as u16 + 1);
+            }
+            emu_state.increment_pc(1)
+        },
+        I::LdIRM(x) => {
+            for i in 0..=(x as u16) {
+                emu_state.set_register(i as u8,
+                                       emu_state.mem[(emu_state.i + i) as usize]);
+            }
+            if emu_state.quirks.increment_i {
+                emu_state.i = emu_state.i.wrapping_add(x as u16 + 1);
+            }
+            emu_state.increment_pc(1)
+        },
+    };
+}
+
+#[cfg(test)]
+/// Run an entire program, exists for unit tests.
+/// # Arguments
+/// * `program` The program to run.
+/// * `emu_state` Emulator state to change.
+#[allow(clippy::ptr_arg)]
+fn emulate_program(program: &Vec<Instruction>, emu_state: &mut InterpreterData) {
+    for _ in program {
+        emulate(program, emu_state, &[false; 0x10]);
+    }
+}
+
+/// Convert raw chip 8 opcode of a ROM loaded at 0x200 into instruction.
+/// Returns InstructionError if instruction is invalid.
+/// # Arguments
+/// `instruction` Chip 8 opcode.
+pub fn program_to_enum(instruction: u16) -> Result<Instruction, InstructionError> {
+    program_to_enum_at(instruction, &Machine::default())
+}
+
+/// Convert raw chip 8 opcode into instruction, with jump targets relative to
+/// the machine's load address. Returns InstructionError if instruction is
+/// invalid.
+/// # Arguments
+/// `instruction` Chip 8 opcode.
+/// `machine` Memory layout the ROM is loaded in.
+#[allow(clippy::collapsible_match)]
+pub fn program_to_enum_at(instruction: u16, machine: &Machine)
+                          -> Result<Instruction, InstructionError> {
+    type I = Instruction;
+    Ok(match get_fourth_nibble(instruction) {
+        0 => {
+            match get_last_2_nibbles(instruction) {
+                0xE0 => I::Cls,
+                0xEE => I::Ret,
+                _ => I::Sys(get_last_3_nibbles(instruction)),
+            }
+        },
+        // Set PC to bottom three nibbles.
+        1 => {
+            I::Jp(machine.instruction_pos(get_last_3_nibbles(instruction)))
+        },
+        // Function call at bottom three nibbles.
+        2 => {
+            I::Call(machine.instruction_pos(get_last_3_nibbles(instruction)))
+        },
+        // Skip next instruction if the bottom byte is equal to the value
+        // in V[first nibble].
+        3 => {
+            I::Se(get_third_nibble(instruction), get_last_2_nibbles(instruction))
+        },
+        // Skip next instruction if V[third nibble] == bottom byte.
+        4 => {
+            I::Sne(get_third_nibble(instruction), get_last_2_nibbles(instruction))
+        },
+        // If V[third nibble] == V[second nibble] then skip next instruction.
+        5 => {
+            if get_first_nibble(instruction) == 0 {
+                I::SeR(get_third_nibble(instruction), get_second_nibble(instruction))
+            } else {
+                return Err(InstructionError::InvalidInstruction);
+            }
+        },
+        // Put the bottom byte into register V[third nibble].
+        6 => {
+            I::Ld(get_third_nibble(instruction), get_last_2_nibbles(instruction))
+        },
+        // Adds the bottom byte to the value of V[third nibble], then
+        // stores it there.
+        7 => {
+            I::Add(get_third_nibble(instruction), get_last_2_nibbles(instruction))
+        },
+        8 => {
+            match get_first_nibble(instruction) {
+                // Bitwise OR V[third nibble] and V[second nibble], store
+                // result in V[third nibble].
+                0 => {
+                    I::LdR(get_third_nibble(instruction), get_second_nibble(instruction))
+                },
+                1 => {
+                    I::Or(get_third_nibble(instruction), get_second_nibble(instruction))
+                },
+                2 => {
+                    I::And(get_third_nibble(instruction), get_second_nibble(instruction))
+                },
+                3 => {
+                    I::Xor(get_third_nibble(instruction), get_second_nibble(instruction))
+                },
+                4 => {
+                    I::AddR(get_third_nibble(instruction), get_second_nibble(instruction))
+                },
+                5 => {
+                    I::Sub(get_third_nibble(instruction), get_second_nibble(instruction))
+                },
+                6 => {
+                    I::Shr(get_third_nibble(instruction), get_second_nibble(instruction))
+                },
+                7 => {
+                    I::SubN(get_third_nibble(instruction), get_second_nibble(instruction))
+                },
+                0xe => {
+                    I::Shl(get_third_nibble(instruction), get_second_nibble(instruction))
+                },
+                _ => return Err(InstructionError::InvalidInstruction),
+            }
+        },
+        9 => {
+            if get_first_nibble(instruction) == 0 {
+                I::SneR(get_third_nibble(instruction), get_second_nibble(instruction))
+            } else {
+                return Err(InstructionError::InvalidInstruction);
+            }
+        },
+        0xa => {
+            I::LdI(get_last_3_nibbles(instruction))
+        },
+        0xb => {
+            I::JpI(machine.instruction_pos(get_last_3_nibbles(instruction)))
+        },
+        0xc => {
+            I::Rnd(get_third_nibble(instruction), get_last_2_nibbles(instruction))
+        },
+        0xd => {
+            I::Drw(get_third_nibble(instruction),
+                get_second_nibble(instruction),
+                get_first_nibble(instruction))
+        },
+        0xe => {
+            match get_first_nibble(instruction) {
+                0xe => I::Skp(get_third_nibble(instruction)),
+                0x1 => I::SkpN(get_third_nibble(instruction)),
+                _ => return Err(InstructionError::InvalidInstruction),
+            }
+        },
+        0xf => {
+            let third_nibble = get_third_nibble(instruction);
+            match get_last_2_nibbles(instruction) {
+                0x07 => {
+                    I::LdD(third_nibble)
+                },
+                0x0a => {
+                    I::LdW(third_nibble)
+                },
+                0x15 => {
+                    I::LdSD(third_nibble)
+                },
+                0x18 => {
+                    I::LdS(third_nibble)
+                },
+                0x1E => {
+                    I::AddI(third_nibble)
+                },
+                0x29 => {
+                    I::LdSp(third_nibble)
+                },
+                0x30 => {
+                    I::LdHf(third_nibble)
+                },
+                0x33 => {
+                    I::LdBCD(third_nibble)
+                },
+                0x55 => {
+                    I::LdIR(third_nibble)
+                },
+                0x65 => {
+                    I::LdIRM(third_nibble)
+                },
+                _ => return Err(InstructionError::InvalidInstruction),
+            }
+        },
+        // Load bottom byte into V[third nibble].
+        _ => return Err(InstructionError::InvalidInstruction),
+    })
+}
+
+/// Convert vector of chip 8 opcodes into vector of instructions. Returns
+/// string on error.
+/// # Arguments 
+/// * `data` Raw chip 8 opcode vector.
+/// * `machine` Memory layout the program is loaded in.
+#[allow(clippy::ptr_arg)]
+pub fn convert_program(data: &Vec<u16>, machine: &Machine) -> Result<Vec<Instruction>, String> {
+    // HACK this is a bad design. Not only does it mess with JP and CALL
+    // instructions, it also has no way of differentiating sprite/constant
+    // data with actual instructions. A design to avoid in the future.
+    let mut result: Vec<Instruction> = Vec::with_capacity(data.len());
+    for i in data.iter() {
+        match program_to_enum_at(*i, machine) {
+            Ok(d) => result.push(d),
+            // Ignore "invalid instructions", as they could just be sprite data.
+            // Need to push an instruction to preserve order.
+            _ => result.push(Instruction::Sys(0)),
+        }
+    }
+    Ok(result)
+}
+
+/// Convert byte stream into 16 bit opcode array.
+/// Return vector of u16 on success, return string on error.
+pub fn convert_bin_format(bytes: &[u8]) -> Result<Vec<u16>, String> {
+    // Because chip8 instructions are 16 bits its length (in bytes) should be even.
+    if bytes.len() % 2 == 1 {
+        return Err(String::from("Invalid input: the file is not an even length."));
+    }
+
+    let mut result: Vec<u16> = Vec::with_capacity(bytes.len() / 2);
+
+    for i in (0..bytes.len()).step_by(2) {
+        if cfg!(target_endian = "big") {
+            result.push(bytes[i] as u16 | (bytes[i + 1] as u16) << 8);
+        } else {
+            result.push((bytes[i] as u16) << 8 | bytes[i + 1] as u16);
+        }
+    }
+
+    Ok(result)
+}
+
+/// Get the program as a vector of instructions and as a raw byte stream.
+/// Return vectors on success, return string on error.
+/// # Arguments
+/// * `source` Where the ROM comes from.
+/// * `patches` Paths of IPS or BPS patches applied to the ROM, in order.
+/// * `machine` Memory layout the ROM is loaded in, which it must fit.
+pub fn get_program(source: &RomSource, patches: &[String], machine: &Machine)
+                   -> Result<(Vec<Instruction>, Vec<u8>), String>  {
+    println!("Opening binary file {}.", source);
+
+    let mut raw_p = patch::apply_files(source.read(machine)?, patches)?;
+    // Ensure raw_p is even length.
+    if raw_p.len() % 2 == 1 {
+        raw_p.push(0);
+    }
+    machine.check_rom(raw_p.len()).map_err(|e| format!("{}: {}", source, e))?;
+    Ok((convert_program(&convert_bin_format(&raw_p)?, machine)?, raw_p))
+}
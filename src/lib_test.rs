@@ -0,0 +1,3020 @@
+//! Chip 8 emulator unit tests.
+//! (C) Ryan Jeffrey <ryan@ryanmj.xyz>, 2022
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or (at
+// your option) any later version.
+
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+#![allow(arithmetic_overflow)]
+
+
+#[cfg(test)]
+#[path = "lib.rs"]
+mod nibble_tests {
+    use super::super::{*};
+
+    #[test]
+    fn nibble_test() {
+        assert_eq!(get_first_nibble(0x8a34u16), 0x4u8);
+    }
+
+    #[test]
+    fn msn_test() {
+        assert_eq!(get_fourth_nibble(0xf015u16), 0xf);
+    }
+}
+
+
+#[cfg(test)]
+#[path = "lib.rs"]
+mod instruction_tests {
+    use super::super::{*};
+    use std::fmt;
+    type I = Instruction;
+
+    impl fmt::Debug for I {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            // Instruction to string conversion.
+            write!(f, "{}", match *self {
+                I::Sys(nnn) => format!("Sys {}", nnn),
+                I::Cls => String::from("Cls"),
+                I::Ret => String::from("Ret"),
+                I::Jp(i) => format!("Jp {}", i),
+                I::Call(i) => format!("Call {}", i),
+                I::Se(i1, i2) => format!("Se {} {}", i1, i2),
+                I::Sne(i1, i2) => format!("Sne {} {}", i1, i2),
+                I::SeR(i1, i2) => format!("SeR {} {}", i1, i2),
+                I::Ld(i1, i2) => format!("Ld {} {}", i1, i2),
+                I::Add(i1, i2) => format!("Add {} {}", i1, i2),
+                I::LdR(i1, i2) => format!("LdR {} {}", i1, i2),
+                I::Or(i1, i2) => format!("Or {} {}", i1, i2),
+                I::And(i1, i2) => format!("And {} {}", i1, i2),
+                I::Xor(i1, i2) => format!("Xor {} {}", i1, i2),
+                I::AddR(i1, i2) => format!("AddR {} {}", i1, i2),
+                I::Sub(i1, i2) => format!("Sub {} {}", i1, i2),
+                I::Shr(i1, i2) => format!("Shr {} {}", i1, i2),
+                I::SubN(i1, i2) => format!("SubN {} {}", i1, i2),
+                I::Shl(i1, i2) => format!("Shl {} {}", i1, i2),
+                I::SneR(i1, i2) => format!("SneR {} {}", i1, i2),
+                I::LdI(i) => format!("SneR {}", i),
+                I::JpI(i) => format!("JpI {}", i),
+                I::Rnd(i1, i2) => format!("Rnd {} {}", i1, i2),
+                I::Drw(i1, i2, i3) => format!("Rnd {} {} {}", i1, i2, i3),
+                I::Skp(i) => format!("Skp {}", i),
+                I::SkpN(i) => format!("SkpN {}", i),
+                I::LdD(i) => format!("LdD {}", i),
+                I::LdW(i) => format!("LdW {}", i),
+                I::LdS(i) => format!("LdS {}", i),
+                I::LdSD(i) => format!("LdSD {}", i),
+                I::AddI(i) => format!("AddI {}", i),
+                I::LdSp(i) => format!("LdSp {}", i),
+                I::LdHf(i) => format!("LdHf {}", i),
+                I::LdBCD(i) => format!("LdBCD {}", i),
+                I::LdIR(i) => format!("LdIR {}", i),
+                I::LdIRM(i) => format!("LdIRM {}", i),
+            })
+        }
+    }
+
+    impl fmt::Debug for InstructionError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "Invalid instruction")
+        }
+    }
+
+    #[test]
+    fn sys_test() -> Result<(), InstructionError> {
+        assert_eq!(program_to_enum(0x0defu16)?, I::Sys(0xdef));
+        Ok(())
+    }
+
+    #[test]
+    fn cls_test() -> Result<(), InstructionError> {
+        assert_eq!(program_to_enum(0x00e0u16)?, I::Cls);
+        Ok(())
+    }
+
+    #[test]
+    fn ret_test() -> Result<(), InstructionError> {
+        assert_eq!(program_to_enum(0x00EEu16)?, I::Ret);
+        Ok(())
+    }
+
+    #[test]
+    fn jp_test() -> Result<(), InstructionError> {
+        assert_eq!(program_to_enum(0x1def)?, I::Jp((0xdef - 0x200) / 2));
+        Ok(())
+    }
+
+    #[test]
+    fn call_test() -> Result<(), InstructionError> {
+        assert_eq!(program_to_enum(0x2def)?, I::Call((0xdef - 0x200) / 2));
+        Ok(())
+    }
+
+    #[test]
+    fn se_test() -> Result<(), InstructionError> {
+        assert_eq!(program_to_enum(0x3def)?, I::Se(0xd, 0xef));
+        Ok(())
+    }
+
+    #[test]
+    fn sne_test() -> Result<(), InstructionError> {
+        assert_eq!(program_to_enum(0x4def)?, I::Sne(0xd, 0xef));
+        Ok(())
+    }
+
+    #[test]
+    fn ser_test() -> Result<(), InstructionError> {
+        assert_eq!(program_to_enum(0x5de0)?, I::SeR(0xd, 0x0e));
+        Ok(())
+    }
+
+    #[test]
+    fn ld_test() -> Result<(), InstructionError> {
+        assert_eq!(program_to_enum(0x6def)?, I::Ld(0xd, 0xef));
+        Ok(())
+    }
+
+    #[test]
+    fn add_test() -> Result<(), InstructionError> {
+        assert_eq!(program_to_enum(0x7def)?, I::Add(0xd, 0xef));
+        Ok(())
+    }
+
+    #[test]
+    fn ldr_test() -> Result<(), InstructionError> {
+        assert_eq!(program_to_enum(0x8de0)?, I::LdR(0xd, 0x0e));
+        Ok(())
+    }
+
+    #[test]
+    fn or_test() -> Result<(), InstructionError> {
+        assert_eq!(program_to_enum(0x8de1)?, I::Or(0xd, 0x0e));
+        Ok(())
+    }
+
+    #[test]
+    fn and_test() -> Result<(), InstructionError> {
+        assert_eq!(program_to_enum(0x8de2)?, I::And(0xd, 0x0e));
+        Ok(())
+    }
+
+    #[test]
+    fn xor_test() -> Result<(), InstructionError> {
+        assert_eq!(program_to_enum(0x8de3)?, I::Xor(0xd, 0x0e));
+        Ok(())
+    }
+
+    #[test]
+    fn addr_test() -> Result<(), InstructionError> {
+        assert_eq!(program_to_enum(0x8de4)?, I::AddR(0xd, 0x0e));
+        Ok(())
+    }
+
+    #[test]
+    fn sub_test() -> Result<(), InstructionError> {
+        assert_eq!(program_to_enum(0x8de5)?, I::Sub(0xd, 0x0e));
+        Ok(())
+    }
+
+    #[test]
+    fn shr_test() -> Result<(), InstructionError> {
+        assert_eq!(program_to_enum(0x8de6)?, I::Shr(0xd, 0x0e));
+        Ok(())
+    }
+
+    #[test]
+    fn subn_test() -> Result<(), InstructionError> {
+        assert_eq!(program_to_enum(0x8de7)?, I::SubN(0xd, 0x0e));
+        Ok(())
+    }
+
+    #[test]
+    fn shl_test() -> Result<(), InstructionError> {
+        assert_eq!(program_to_enum(0x8dee)?, I::Shl(0xd, 0x0e));
+        Ok(())
+    }
+
+    #[test]
+    fn sner_test() -> Result<(), InstructionError> {
+        assert_eq!(program_to_enum(0x9de0)?, I::SneR(0xd, 0x0e));
+        Ok(())
+    }
+
+    #[test]
+    fn ldi_test() -> Result<(), InstructionError> {
+        assert_eq!(program_to_enum(0xadef)?, I::LdI(0xdef));
+        Ok(())
+    }
+
+    #[test]
+    fn jpi_test() -> Result<(), InstructionError> {
+        assert_eq!(program_to_enum(0xbdef)?, I::JpI((0xdef - 0x200) / 2));
+        Ok(())
+    }
+
+    #[test]
+    fn rnd_test() -> Result<(), InstructionError> {
+        assert_eq!(program_to_enum(0xcdef)?, I::Rnd(0xd, 0xef));
+        Ok(())
+    }
+
+    #[test]
+    fn drw_test() -> Result<(), InstructionError> {
+        assert_eq!(program_to_enum(0xddef)?, I::Drw(0xd, 0xe, 0xf));
+        Ok(())
+    }
+
+    #[test]
+    fn skp_test() -> Result<(), InstructionError> {
+        assert_eq!(program_to_enum(0xed9e)?, I::Skp(0xd));
+        Ok(())
+    }
+
+    #[test]
+    fn skpn_test() -> Result<(), InstructionError> {
+        assert_eq!(program_to_enum(0xeda1)?, I::SkpN(0xd));
+        Ok(())
+    }
+
+    #[test]
+    fn ldd_test() -> Result<(), InstructionError> {
+        assert_eq!(program_to_enum(0xfe07)?, I::LdD(0xe));
+        Ok(())
+    }
+
+    #[test]
+    fn ldw_test() -> Result<(), InstructionError> {
+        assert_eq!(program_to_enum(0xfe0a)?, I::LdW(0xe));
+        Ok(())
+    }
+
+    #[test]
+    fn ldsd_test() -> Result<(), InstructionError> {
+        assert_eq!(program_to_enum(0xfe15)?, I::LdSD(0xe));
+        Ok(())
+    }
+
+    #[test]
+    fn lds_test() -> Result<(), InstructionError> {
+        assert_eq!(program_to_enum(0xfe18)?, I::LdS(0xe));
+        Ok(())
+    }
+
+    #[test]
+    fn addi_test() -> Result<(), InstructionError> {
+        assert_eq!(program_to_enum(0xfe1e)?, I::AddI(0xe));
+        Ok(())
+    }
+
+    #[test]
+    fn ldsp_test() -> Result<(), InstructionError> {
+        assert_eq!(program_to_enum(0xfe29)?, I::LdSp(0xe));
+        Ok(())
+    }
+
+    #[test]
+    fn ldhf_test() -> Result<(), InstructionError> {
+        assert_eq!(program_to_enum(0xfe30)?, I::LdHf(0xe));
+        Ok(())
+    }
+
+    #[test]
+    fn ldbcd_test() -> Result<(), InstructionError> {
+        assert_eq!(program_to_enum(0xfe33)?, I::LdBCD(0xe));
+        Ok(())
+    }
+
+    #[test]
+    fn ldir_test() -> Result<(), InstructionError> {
+        assert_eq!(program_to_enum(0xfe55)?, I::LdIR(0xe));
+        Ok(())
+    }
+
+    #[test]
+    fn ldirm_test() -> Result<(), InstructionError> {
+        assert_eq!(program_to_enum(0xfe65)?, I::LdIRM(0xe));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[path = "lib.rs"]
+mod emulate_tests {
+    use super::super::{*};
+    use std::fmt;
+    type I = Instruction;
+
+    #[allow(clippy::write_with_newline)]
+    impl fmt::Debug for InterpreterData {
+        // Write instruction.
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "v:{:?}, i:{}, pc:{}, sp:{}, stack:{:?}, delay:{}, sound:{}",
+                   self.v, self.i, self.pc, self.sp, self.stack, self.delay_timer, self.sound_timer)?;
+            // Print the screen.
+            write!(f, "\nscreen:")?;
+            for y in 0..self.screen.height() {
+                write!(f, "{:016x?}\n", self.screen.row(0, y))?;
+            }
+            // Print the memory.
+            const STEP: usize = 32;
+            write!(f, "\nmem:")?;
+            for s in (0..self.mem.len()).step_by(STEP) {
+                write!(f, "{:?}\n", &self.mem[s..s+STEP])?;
+            }
+            write!(f, "\n")
+        }
+    }
+
+    impl PartialEq for InterpreterData {
+        fn eq(&self, other: &Self) -> bool {
+            self.v == other.v && self.i == other.i && self.pc == other.pc &&
+                self.sp == other.sp && self.stack == other.stack &&
+                self.delay_timer == other.delay_timer &&
+                self.sound_timer == other.sound_timer &&
+                self.mem == other.mem && self.screen == other.screen
+        }
+    }
+
+    #[test]
+    fn sys_test() -> Result<(), InstructionError> {
+        let mut emu_state = InterpreterData::new();
+        emulate_program(&vec![I::Sys(0xdef)], &mut emu_state);
+        assert_eq!(emu_state, {
+            let mut e = InterpreterData::new();
+            e.pc += 1;
+            e
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn cls_test() -> Result<(), InstructionError> {
+        let mut emu_state = InterpreterData::new();
+        emulate_program(&vec![I::Cls], &mut emu_state);
+        assert_eq!(emu_state, {
+            let mut e = InterpreterData::new();
+            e.pc += 1;
+            e
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn call_test() -> Result<(), InstructionError> {
+        let mut emu_state = InterpreterData::new();
+        emulate_program(&vec![I::Call(0xdef)], &mut emu_state);
+        assert_eq!(emu_state, {
+            let mut e = InterpreterData::new();
+            e.pc = 0xdef;
+            e.sp += 1;
+            e.stack[0] = 1;
+            e
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn ret_test() -> Result<(), InstructionError> {
+        let mut emu_state = InterpreterData::new();
+        emulate_program(&vec![I::Call(0x1), I::Ret], &mut emu_state);
+        assert_eq!(emu_state, {
+            let mut e = InterpreterData::new();
+            e.pc = 1;
+            e.stack[0] = 1;
+            e
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn jp_test() -> Result<(), InstructionError> {
+        let mut emu_state = InterpreterData::new();
+        emulate_program(&vec![I::Jp(0xdef)], &mut emu_state);
+        assert_eq!(emu_state, {
+            let mut e = InterpreterData::new();
+            e.pc = 0xdef;
+            e
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn se_test_neq() -> Result<(), InstructionError> {
+        let mut emu_state = InterpreterData::new();
+        emulate_program(&vec![I::Ld(0, 2), I::Se(0, 1)], &mut emu_state);
+        assert_eq!(emu_state, {
+            let mut e = InterpreterData::new();
+            e.v[0] = 2;
+            e.pc = 2;
+            e
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn se_test_eq() -> Result<(), InstructionError> {
+        let mut emu_state = InterpreterData::new();
+        emulate_program(&vec![I::Ld(0, 2), I::Se(0, 2)], &mut emu_state);
+        assert_eq!(emu_state, {
+            let mut e = InterpreterData::new();
+            e.v[0] = 2;
+            e.pc = 3;
+            e
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn sne_test_neq() -> Result<(), InstructionError> {
+        let mut emu_state = InterpreterData::new();
+        emulate_program(&vec![I::Ld(0, 2), I::Sne(0, 1)], &mut emu_state);
+        assert_eq!(emu_state, {
+            let mut e = InterpreterData::new();
+            e.v[0] = 2;
+            e.pc = 3;
+            e
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn sne_test_eq() -> Result<(), InstructionError> {
+        let mut emu_state = InterpreterData::new();
+        emulate_program(&vec![I::Ld(0, 2), I::Sne(0, 2)], &mut emu_state);
+        assert_eq!(emu_state, {
+            let mut e = InterpreterData::new();
+            e.v[0] = 2;
+            e.pc = 2;
+            e
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn ser_test_neq() -> Result<(), InstructionError> {
+        let mut emu_state = InterpreterData::new();
+        emulate_program(&vec![I::Ld(0, 2), I::Ld(1, 1), I::SeR(0, 1)],
+                        &mut emu_state);
+        assert_eq!(emu_state, {
+            let mut e = InterpreterData::new();
+            e.v[0] = 2;
+            e.v[1] = 1;
+            e.pc = 3;
+            e
+        });
+        Ok(())
+    }
+
+    // Test with two registers that have the same value, should skip
+    // 3rd instruction.
+    #[test]
+    fn ser_test_eq() -> Result<(), InstructionError> {
+        let mut emu_state = InterpreterData::new();
+        emulate_program(&vec![I::Ld(0, 1), I::Ld(1, 1), I::SeR(0, 1)],
+                        &mut emu_state);
+        assert_eq!(emu_state, {
+            let mut e = InterpreterData::new();
+            e.v[0] = 1;
+            e.v[1] = 1;
+            e.pc = 4;
+            e
+        });
+        Ok(())
+    }
+
+    // Test with two registers that have the same value, should skip
+    // 3rd instruction.
+    #[test]
+    fn ld_test() -> Result<(), InstructionError> {
+        let mut emu_state = InterpreterData::new();
+        emulate_program(&vec![I::Ld(0, 1)], &mut emu_state);
+        assert_eq!(emu_state, {
+            let mut e = InterpreterData::new();
+            e.v[0] = 1;
+            e.pc = 1;
+            e
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn add_test() -> Result<(), InstructionError> {
+        let mut emu_state = InterpreterData::new();
+        emulate_program(&vec![I::Ld(0, 1), I::Add(0, 0xde)], &mut emu_state);
+        assert_eq!(emu_state, {
+            let mut e = InterpreterData::new();
+            e.v[0] = 1 + 0xde;
+            e.pc = 2;
+            e
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn ldr_test() -> Result<(), InstructionError> {
+        let mut emu_state = InterpreterData::new();
+        emulate_program(&vec![I::Ld(0, 1), I::LdR(1, 0)], &mut emu_state);
+        assert_eq!(emu_state, {
+            let mut e = InterpreterData::new();
+            e.v[0] = 1;
+            e.v[1] = 1;
+            e.pc = 2;
+            e
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn or_test() -> Result<(), InstructionError> {
+        let mut emu_state = InterpreterData::new();
+        emulate_program(&vec![I::Ld(0, 0xbe), I::Ld(1, 0xde), I::Or(0, 1)],
+                        &mut emu_state);
+        assert_eq!(emu_state, {
+            let mut e = InterpreterData::new();
+            e.v[0] = 0xde | 0xbe;
+            e.v[1] = 0xde;
+            e.pc = 3;
+            e
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn and_test() -> Result<(), InstructionError> {
+        let mut emu_state = InterpreterData::new();
+        emulate_program(&vec![I::Ld(0, 0xbe), I::Ld(1, 0xde), I::And(0, 1)],
+                        &mut emu_state);
+        assert_eq!(emu_state, {
+            let mut e = InterpreterData::new();
+            e.v[0] = 0xde & 0xbe;
+            e.v[1] = 0xde;
+            e.pc = 3;
+            e
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn xor_test() -> Result<(), InstructionError> {
+        let mut emu_state = InterpreterData::new();
+        emulate_program(&vec![I::Ld(0, 0xbe), I::Ld(1, 0xde), I::Xor(0, 1)],
+                        &mut emu_state);
+        assert_eq!(emu_state, {
+            let mut e = InterpreterData::new();
+            e.v[0] = 0xde ^ 0xbe;
+            e.v[1] = 0xde;
+            e.pc = 3;
+            e
+        });
+        Ok(())
+    }
+
+    // AddR with carry.
+    #[test]
+    fn addr_test_carry() -> Result<(), InstructionError> {
+        let mut emu_state = InterpreterData::new();
+        emulate_program(&vec![I::Ld(0, 0xbe), I::Ld(1, 0xde), I::AddR(0, 1)],
+                        &mut emu_state);
+        assert_eq!(emu_state, {
+            let mut e = InterpreterData::new();
+            e.v[0] = 0xde + 0xbe;
+            e.v[1] = 0xde;
+            e.v[0xf] = 1;
+            e.pc = 3;
+            e
+        });
+        Ok(())
+    }
+
+    // AddR with without carry.
+    #[test]
+    fn addr_test() -> Result<(), InstructionError> {
+        let mut emu_state = InterpreterData::new();
+        emulate_program(&vec![I::Ld(0, 0xbe), I::Ld(1, 1), I::AddR(0, 1)],
+                        &mut emu_state);
+        assert_eq!(emu_state, {
+            let mut e = InterpreterData::new();
+            e.v[0] = 0xbe + 1;
+            e.v[1] = 1;
+            e.pc = 3;
+            e
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn ldd_test() -> Result<(), InstructionError> {
+        let mut emu_state = InterpreterData::new();
+        emulate_program(&vec![I::Ld(0, 0xbe), I::LdSD(0), I::LdD(1)],
+                        &mut emu_state);
+        assert_eq!(emu_state, {
+            let mut e = InterpreterData::new();
+            e.v[0] = 0xbe;
+            e.delay_timer = 0xbe;
+            e.v[1] = 0xbe;
+            e.pc = 3;
+            e
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn ldsd_test() -> Result<(), InstructionError> {
+        let mut emu_state = InterpreterData::new();
+        emulate_program(&vec![I::Ld(0, 0xbe), I::LdSD(0)],
+                        &mut emu_state);
+        assert_eq!(emu_state, {
+            let mut e = InterpreterData::new();
+            e.v[0] = 0xbe;
+            e.delay_timer = 0xbe;
+            e.pc = 2;
+            e
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn lds_test() -> Result<(), InstructionError> {
+        let mut emu_state = InterpreterData::new();
+        emulate_program(&vec![I::Ld(0, 0xbe), I::LdS(0)],
+                        &mut emu_state);
+        assert_eq!(emu_state, {
+            let mut e = InterpreterData::new();
+            e.v[0] = 0xbe;
+            e.sound_timer = 0xbe;
+            e.pc = 2;
+            e
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn addi_test() -> Result<(), InstructionError> {
+        let mut emu_state = InterpreterData::new();
+        emulate_program(&vec![I::LdI(0xdef), I::Ld(0, 0xbe), I::AddI(0)],
+                        &mut emu_state);
+        assert_eq!(emu_state, {
+            let mut e = InterpreterData::new();
+            e.v[0] = 0xbe;
+            e.i = 0xdef + 0xbe;
+            e.pc = 3;
+            e
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn ldir_test() -> Result<(), InstructionError> {
+        let mut emu_state = InterpreterData::new();
+        emulate_program(&vec![I::Ld(0, 0xde), I::Ld(1, 0xad),
+                              I::Ld(2, 0xbe), I::Ld(3, 0xef), I::Ld(4, 0x69),
+                              I::LdI(0x1), I::LdIR(4)],
+                        &mut emu_state);
+        assert_eq!(emu_state, {
+            let mut e = InterpreterData::new();
+            e.v[0] = 0xde;
+            e.v[1] = 0xad;
+            e.v[2] = 0xbe;
+            e.v[3] = 0xef;
+            e.v[4] = 0x69;
+
+            e.mem[1] = 0xde;
+            e.mem[2] = 0xad;
+            e.mem[3] = 0xbe;
+            e.mem[4] = 0xef;
+            // TODO not sure if inclusive.
+            e.mem[5] = 0x69;
+            e.i = 1;
+            e.pc = 7;
+            e
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn ldirm_test() -> Result<(), InstructionError> {
+        let mut emu_state = InterpreterData::new();
+        emu_state.mem[1] = 0xde;
+        emu_state.mem[2] = 0xad;
+        emu_state.mem[3] = 0xbe;
+        emu_state.mem[4] = 0xef;
+        // TODO not sure if inclusive.
+        emu_state.mem[5] = 0x69;
+        emulate_program(&vec![I::LdI(1), I::LdIRM(4)],
+                        &mut emu_state);
+        assert_eq!(emu_state, {
+            let mut e = InterpreterData::new();
+            e.v[0] = 0xde;
+            e.v[1] = 0xad;
+            e.v[2] = 0xbe;
+            e.v[3] = 0xef;
+            e.v[4] = 0x69;
+
+            e.mem[1] = 0xde;
+            e.mem[2] = 0xad;
+            e.mem[3] = 0xbe;
+            e.mem[4] = 0xef;
+            e.mem[5] = 0x69;
+            e.i = 1;
+            e.pc = 2;
+            e
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn drw_test() -> Result<(), InstructionError> {
+        let mut emu_state = InterpreterData::new();
+        // A 2 row sprite at (62, 31), wrapping around both edges.
+        emu_state.mem[0x300] = 0xc0;
+        emu_state.mem[0x301] = 0x81;
+        emu_state.v[0] = 62;
+        emu_state.v[1] = 31;
+        let program = [I::LdI(0x300), I::Drw(0, 1, 2), I::Drw(0, 1, 1)];
+        emulate(&program, &mut emu_state, &[false; 0x10]);
+        emulate(&program, &mut emu_state, &[false; 0x10]);
+        let lit: Vec<_> = emu_state.screen.lit().collect();
+        assert_eq!(lit, vec![(5, 0), (62, 0), (62, 31), (63, 31)]);
+        assert_eq!(emu_state.v[0xf], 0);
+        assert!(emu_state.draw);
+
+        // Drawing the first row again clears it and collides.
+        emulate(&program, &mut emu_state, &[false; 0x10]);
+        let lit: Vec<_> = emu_state.screen.lit().collect();
+        assert_eq!(lit, vec![(5, 0), (62, 0)]);
+        assert_eq!(emu_state.v[0xf], 1);
+        Ok(())
+    }
+
+    #[test]
+    fn reload_test() {
+        let mut emu_state = InterpreterData::new();
+        emu_state.load(&[1, 2, 3, 4]);
+        emu_state.mem[0x300] = 9;
+        emu_state.v[3] = 7;
+        emu_state.reload(4, &[5, 6]);
+        assert_eq!(&emu_state.mem[0x200..0x205], &[5, 6, 0, 0, 0]);
+        assert_eq!(emu_state.mem[0x300], 9);
+        assert_eq!(&emu_state.mem[..5], &FONTSET[..5]);
+        assert_eq!(emu_state.v[3], 7);
+    }
+
+    #[test]
+    fn machine_test() -> Result<(), String> {
+        let machine = machine::Machine {
+            load_address: 0x600,
+            font_address: 0x50,
+            ..machine::Machine::chip8()
+        };
+        let mut emu_state = InterpreterData::with_machine(machine.clone());
+        // LD V0, 2; LD F, V0; JP 0x600.
+        let rom = [0x60, 0x02, 0xF0, 0x29, 0x16, 0x00];
+        emu_state.load(&rom);
+        assert_eq!(&emu_state.mem[0x600..0x606], &rom);
+        assert_eq!(&emu_state.mem[0x50..0x50 + FONTSET.len()], &FONTSET[..]);
+        let program = convert_program(&convert_bin_format(&rom)?, &machine)?;
+        assert!(program[2] == I::Jp(0));
+        for _ in 0..3 {
+            emulate(&program, &mut emu_state, &[false; 0x10]);
+        }
+        assert_eq!(emu_state.i, 0x50 + 2 * 5);
+        assert_eq!(emu_state.pc, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn big_font_test() {
+        let machine = machine::Machine::xo_chip();
+        let mut emu_state = InterpreterData::with_machine(machine.clone());
+        emu_state.load(&[]);
+        // The big glyphs follow the small ones.
+        assert_eq!(machine.big_font_address(), 80);
+        assert_eq!(&emu_state.mem[80..90], &[0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7,
+                                             0x7E, 0x3C]);
+        emu_state.v[1] = 3;
+        emulate(&[I::LdHf(1), I::LdSp(1)], &mut emu_state, &[false; 0x10]);
+        assert_eq!(emu_state.i, 80 + 3 * 10);
+        emulate(&[I::LdHf(1), I::LdSp(1)], &mut emu_state, &[false; 0x10]);
+        assert_eq!(emu_state.i, 3 * 5);
+    }
+
+    #[test]
+    fn stack_test() {
+        // All 16 levels are usable.
+        let mut emu_state = InterpreterData::new();
+        for _ in 0..16 {
+            emulate(&[I::Call(0)], &mut emu_state, &[false; 0x10]);
+        }
+        assert_eq!((emu_state.sp, emu_state.error.clone()), (16, None));
+
+        // The VIP's 12 levels overflow on the 13th call, which stops there.
+        let machine = machine::Machine { stack_address: None, ..machine::Machine::vip() };
+        let mut emu_state = InterpreterData::with_machine(machine);
+        for _ in 0..13 {
+            emulate(&[I::Call(0)], &mut emu_state, &[false; 0x10]);
+        }
+        assert_eq!(emu_state.sp, 12);
+        assert_eq!(emu_state.error,
+                   Some(String::from("Stack overflow at 0x200, all 12 levels are in use.")));
+        emulate(&[I::Cls], &mut emu_state, &[false; 0x10]);
+        assert_eq!(emu_state.pc, 0);
+
+        let mut emu_state = InterpreterData::new();
+        emulate(&[I::Cls, I::Ret], &mut emu_state, &[false; 0x10]);
+        emulate(&[I::Cls, I::Ret], &mut emu_state, &[false; 0x10]);
+        assert_eq!((emu_state.pc, emu_state.sp), (1, 0));
+        assert_eq!(emu_state.error,
+                   Some(String::from("Stack underflow at 0x202, returning with an empty stack.")));
+    }
+
+    #[test]
+    fn end_of_program_test() {
+        // Jumping past the last instruction stops there.
+        let mut emu_state = InterpreterData::new();
+        emulate(&[I::Jp(2), I::Cls], &mut emu_state, &[false; 0x10]);
+        emulate(&[I::Jp(2), I::Cls], &mut emu_state, &[false; 0x10]);
+        assert_eq!(emu_state.pc, 2);
+        assert_eq!(emu_state.error, Some(String::from("Ran past the end of the program at 0x204.")));
+    }
+
+    #[test]
+    fn memory_stack_test() {
+        let mut emu_state = InterpreterData::with_machine(machine::Machine::vip());
+        assert!(emu_state.stack.is_empty());
+        let program = [I::Call(3), I::Cls, I::Cls, I::Ret];
+        emulate(&program, &mut emu_state, &[false; 0x10]);
+        // The return address is in memory, big endian.
+        assert_eq!(&emu_state.mem[0xEA0..0xEA2], &[0x02, 0x02]);
+        // A ROM changing it returns elsewhere.
+        emu_state.mem[0xEA1] = 0x04;
+        emulate(&program, &mut emu_state, &[false; 0x10]);
+        assert_eq!((emu_state.pc, emu_state.sp), (2, 0));
+        assert_eq!(emu_state.error, None);
+    }
+
+    #[test]
+    fn display_wait_test() -> Result<(), InstructionError> {
+        let mut emu_state = InterpreterData::new();
+        emu_state.quirks = quirks::Quirks::vip();
+        emu_state.mem[0] = 0x80;
+        let program = [I::Drw(0, 0, 1), I::Drw(0, 0, 1), I::Cls];
+        // The first draw waits for the next frame.
+        for _ in 0..3 {
+            emulate(&program, &mut emu_state, &[false; 0x10]);
+            assert_eq!(emu_state.pc, 0);
+        }
+        emu_state.tick_frame();
+        emulate(&program, &mut emu_state, &[false; 0x10]);
+        assert_eq!(emu_state.pc, 1);
+        assert!(emu_state.screen.get(0, 0));
+        // So does the second, even right after the first.
+        emulate(&program, &mut emu_state, &[false; 0x10]);
+        assert_eq!(emu_state.pc, 1);
+        emu_state.tick_frame();
+        emulate(&program, &mut emu_state, &[false; 0x10]);
+        assert_eq!(emu_state.pc, 2);
+
+        // Without it, drawing does not wait.
+        emu_state.quirks = quirks::Quirks::modern();
+        emu_state.pc = 0;
+        emulate(&program, &mut emu_state, &[false; 0x10]);
+        assert_eq!(emu_state.pc, 1);
+        Ok(())
+    }
+
+    /// Run one instruction with the VIP's and SUPER-CHIP's quirks, from the
+    /// state `setup` leaves, and return both states.
+    fn run_quirks(instruction: Instruction, setup: fn(&mut InterpreterData))
+                  -> (InterpreterData, InterpreterData) {
+        let mut vip = InterpreterData::new();
+        let mut schip = InterpreterData::new();
+        vip.quirks = quirks::Quirks::vip();
+        schip.quirks = quirks::Quirks::schip();
+        // Draw at once.
+        vip.tick_frame();
+        for emu_state in [&mut vip, &mut schip].iter_mut() {
+            setup(emu_state);
+            emulate(&[instruction], emu_state, &[false; 0x10]);
+        }
+        (vip, schip)
+    }
+
+    #[test]
+    fn shift_quirk_test() {
+        let (vip, schip) = run_quirks(I::Shr(0, 1), |e| {
+            e.v[0] = 0x04;
+            e.v[1] = 0x03;
+        });
+        // The VIP shifts Vy into Vx, SUPER-CHIP shifts Vx.
+        assert_eq!((vip.v[0], vip.v[0xf]), (0x01, 1));
+        assert_eq!((schip.v[0], schip.v[0xf]), (0x02, 0));
+        let (vip, schip) = run_quirks(I::Shl(0, 1), |e| {
+            e.v[0] = 0x81;
+            e.v[1] = 0x03;
+        });
+        assert_eq!((vip.v[0], vip.v[0xf]), (0x06, 0));
+        assert_eq!((schip.v[0], schip.v[0xf]), (0x02, 1));
+    }
+
+    #[test]
+    fn load_store_quirk_test() {
+        let (vip, schip) = run_quirks(I::LdIR(2), |e| e.i = 0x300);
+        assert_eq!((vip.i, schip.i), (0x303, 0x300));
+        let (vip, schip) = run_quirks(I::LdIRM(0), |e| e.i = 0x300);
+        assert_eq!((vip.i, schip.i), (0x301, 0x300));
+        assert!(!quirks::Quirks::modern().increment_i);
+    }
+
+    #[test]
+    fn jump_quirk_test() -> Result<(), InstructionError> {
+        // JP V0, 0x302, which SUPER-CHIP reads as JP V3, 0x302.
+        let instruction = program_to_enum(0xB302)?;
+        let (vip, schip) = run_quirks(instruction, |e| {
+            e.v[0] = 4;
+            e.v[3] = 2;
+        });
+        assert_eq!((vip.pc, schip.pc), (0x81 + 4, 0x81 + 2));
+        Ok(())
+    }
+
+    #[test]
+    fn clip_quirk_test() {
+        // Two rows of 0xC3 from (126, 31), starting at (62, 31) wrapped.
+        let setup: fn(&mut InterpreterData) = |e| {
+            e.v[0] = 126;
+            e.v[1] = 31;
+            e.i = 0x300;
+            e.mem[0x300] = 0xC3;
+            e.mem[0x301] = 0xC3;
+        };
+        let (vip, schip) = run_quirks(I::Drw(0, 1, 2), setup);
+        assert_eq!(vip.screen.lit().collect::<Vec<_>>(), vec![(62, 31), (63, 31)]);
+        assert!(schip.screen == vip.screen);
+
+        // Without clipping the sprite wraps around both edges.
+        let mut modern = InterpreterData::new();
+        setup(&mut modern);
+        emulate(&[I::Drw(0, 1, 2)], &mut modern, &[false; 0x10]);
+        assert_eq!(modern.screen.lit().collect::<Vec<_>>(),
+                   vec![(4, 0), (5, 0), (62, 0), (63, 0), (4, 31), (5, 31), (62, 31), (63, 31)]);
+    }
+
+    #[test]
+    fn quirk_presets_test() {
+        assert!(quirks::Quirks::schip() != quirks::Quirks::modern());
+        assert!(quirks::Quirks::vip() != quirks::Quirks::schip());
+    }
+}
+
+#[cfg(test)]
+#[path = "lib.rs"]
+mod analysis_tests {
+    use super::super::{*};
+    use symbols::SymbolTable;
+    use analysis::{analyse, Edge, EdgeKind, Exit};
+
+    /// Convert a list of opcodes into ROM bytes.
+    fn rom(opcodes: &[u16]) -> Vec<u8> {
+        opcodes.iter().flat_map(|o| vec![(o >> 8) as u8, *o as u8]).collect()
+    }
+
+    #[test]
+    fn straight_line_test() {
+        // LD V0, 1; ADD V0, 1; JP 0x202
+        let cfg = analyse(&rom(&[0x6001, 0x7001, 0x1202]), 0x200);
+        let blocks = cfg.blocks().collect::<Vec<_>>();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].start, 0x200);
+        assert_eq!(blocks[0].exit, Exit::FallThrough);
+        assert_eq!(blocks[1].start, 0x202);
+        assert_eq!(blocks[1].end(), 0x206);
+        assert_eq!(blocks[1].exit, Exit::Jump);
+        assert_eq!(blocks[1].successors,
+                   vec![Edge { target: 0x202, kind: EdgeKind::Jump }]);
+    }
+
+    #[test]
+    fn data_after_jump_test() {
+        // JP 0x204; sprite data; CLS; JP 0x206
+        let cfg = analyse(&rom(&[0x1204, 0xF090, 0x00E0, 0x1206]), 0x200);
+        assert!(cfg.is_code(0x200));
+        assert!(!cfg.is_code(0x202));
+        assert!(!cfg.is_code(0x203));
+        assert!(cfg.is_code(0x204));
+        assert_eq!(cfg.data_ranges(), vec![0x202..0x204]);
+    }
+
+    #[test]
+    fn skip_test() {
+        // SE V0, 1; JP 0x200; RET
+        let cfg = analyse(&rom(&[0x3001, 0x1200, 0x00EE]), 0x200);
+        let block = cfg.block(0x200).unwrap();
+        assert_eq!(block.exit, Exit::Skip);
+        assert_eq!(block.successors,
+                   vec![Edge { target: 0x202, kind: EdgeKind::FallThrough },
+                        Edge { target: 0x204, kind: EdgeKind::Skip }]);
+        assert_eq!(cfg.block(0x204).unwrap().exit, Exit::Return);
+    }
+
+    #[test]
+    fn call_test() {
+        // CALL 0x206; JP 0x202; data; RET
+        let cfg = analyse(&rom(&[0x2206, 0x1202, 0xFFFF, 0x00EE]), 0x200);
+        assert_eq!(cfg.block(0x200).unwrap().successors,
+                   vec![Edge { target: 0x206, kind: EdgeKind::Call },
+                        Edge { target: 0x202, kind: EdgeKind::FallThrough }]);
+        assert_eq!(cfg.block(0x206).unwrap().exit, Exit::Return);
+        assert_eq!(cfg.data_ranges(), vec![0x204..0x206]);
+    }
+
+    #[test]
+    fn indirect_test() {
+        // LD V0, 2; JP V0, 0x300
+        let cfg = analyse(&rom(&[0x6002, 0xB300]), 0x200);
+        assert_eq!(cfg.indirect_jumps(), vec![0x202]);
+        assert_eq!(cfg.block_containing(0x202).unwrap().exit, Exit::Indirect);
+        assert!(cfg.block(0x200).unwrap().successors.is_empty());
+    }
+
+    #[test]
+    fn join_test() {
+        // SNE V0, 0; LD V1, 1; LD V2, 2; JP 0x206
+        let cfg = analyse(&rom(&[0x4000, 0x6101, 0x6202, 0x1206]), 0x200);
+        // 0x204 is both a skip target and the fall through of 0x202.
+        assert_eq!(cfg.block(0x202).unwrap().exit, Exit::FallThrough);
+        assert_eq!(cfg.block(0x204).unwrap().end(), 0x206);
+        assert_eq!(cfg.block_containing(0x204).unwrap().start, 0x204);
+    }
+
+    #[test]
+    fn end_and_invalid_test() {
+        let cfg = analyse(&rom(&[0x6001]), 0x200);
+        assert_eq!(cfg.block(0x200).unwrap().exit, Exit::End);
+        let cfg = analyse(&rom(&[0x6001, 0x5001]), 0x200);
+        assert_eq!(cfg.block(0x200).unwrap().exit, Exit::Invalid);
+        assert_eq!(cfg.data_ranges(), vec![0x202..0x204]);
+    }
+
+    #[test]
+    fn dot_test() {
+        let cfg = analyse(&rom(&[0x3001, 0x1200, 0xB300]), 0x200);
+        let dot = cfg.to_dot();
+        assert!(dot.starts_with("digraph chip8 {\n"));
+        assert!(dot.contains("\"0x200\" [label=\"0x200: SE V0, 0x01\\l\"];"));
+        assert!(dot.contains("\"0x200\" -> \"0x204\" [label=\"skip\", style=dashed];"));
+        assert!(dot.contains("\"0x202\" -> \"0x200\" [label=\"jump\"];"));
+        assert!(dot.contains("xlabel=\"indirect\""));
+    }
+
+    #[test]
+    fn disassemble_test() {
+        let bytes = rom(&[0x1204, 0xF090, 0xD015]);
+        let cfg = analyse(&bytes, 0x200);
+        assert_eq!(disasm::disassemble(&bytes, &cfg, &SymbolTable::new()),
+                   "0x200: 1204  JP 0x204\n\
+                    0x202:       DB 0xF0, 0x90\n\
+                    \n\
+                    0x204: D015  DRW V0, V1, 5\n");
+    }
+
+    #[test]
+    fn mnemonic_test() {
+        assert_eq!(disasm::mnemonic(0x00E0), "CLS");
+        assert_eq!(disasm::mnemonic(0x2ABC), "CALL 0xABC");
+        assert_eq!(disasm::mnemonic(0x8AB6), "SHR VA, VB");
+        assert_eq!(disasm::mnemonic(0xF330), "LD HF, V3");
+        assert_eq!(disasm::mnemonic(0xFA55), "LD [I], VA");
+        assert_eq!(disasm::mnemonic(0xFFFF), "DW 0xFFFF");
+    }
+}
+
+#[cfg(test)]
+#[path = "lib.rs"]
+mod options_tests {
+    use filter;
+    use machine::Machine;
+    use options::Options;
+    use quirks;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        Options::parse(args.iter().map(|a| a.to_string()))
+    }
+
+    #[test]
+    fn default_rom_test() -> Result<(), String> {
+        assert_eq!(parse(&[])?.rom, None);
+        assert!(parse(&[])?.rom().is_err());
+        assert_eq!(parse(&["pong.ch8"])?.rom(), Ok("pong.ch8"));
+        Ok(())
+    }
+
+    #[test]
+    fn watch_options_test() -> Result<(), String> {
+        let options = parse(&["--watch", "--keep-state", "game.ch8"])?;
+        assert!(options.watch && options.keep_state);
+        assert!(!parse(&[])?.watch);
+        Ok(())
+    }
+
+    #[test]
+    fn zip_entry_options_test() -> Result<(), String> {
+        let options = parse(&["--zip-entry", "PONG", "games.zip"])?;
+        assert_eq!(options.zip_entry, Some(String::from("PONG")));
+        assert_eq!(parse(&[])?.zip_entry, None);
+        assert!(parse(&["--zip-entry"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn patch_options_test() -> Result<(), String> {
+        let options = parse(&["--patch", "fix.ips", "--patch", "english.bps", "game.ch8"])?;
+        assert_eq!(options.patches, ["fix.ips", "english.bps"]);
+        assert!(parse(&[])?.patches.is_empty());
+        assert!(parse(&["--patch"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn machine_options_test() -> Result<(), String> {
+        let options = parse(&["--font-address", "0x50", "--machine", "eti-660"])?;
+        assert_eq!(options.machine, Machine { font_address: 0x50, ..Machine::eti660() });
+        let options = parse(&["--load-address", "1536", "--memory-size", "0x10000"])?;
+        assert_eq!((options.machine.load_address, options.machine.memory_size), (0x600, 0x10000));
+        assert_eq!(parse(&[])?.machine, Machine::chip8());
+        let options = parse(&["--font", "vip", "--machine", "eti-660"])?;
+        assert_eq!((options.machine.load_address, options.machine.font.name.as_str()),
+                   (0x600, "vip"));
+        assert_eq!(parse(&["--machine", "eti-660"])?.machine.font.name, "eti-660");
+        assert!(parse(&["--font", "missing"]).is_err());
+        assert!(parse(&["--machine", "apple"]).is_err());
+        assert!(parse(&["--load-address", "0x10000"]).is_err());
+        assert!(parse(&["--font-address"]).is_err());
+        // The machine is checked once every option is read.
+        assert_eq!(parse(&["--load-address", "0x20"]),
+                   Err(String::from("The font at 0x0 overlaps the ROM at 0x20.")));
+        assert!(parse(&["--memory-size", "2048"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn stack_options_test() -> Result<(), String> {
+        let options = parse(&["--stack-depth", "12", "--memory-stack"])?;
+        assert_eq!((options.machine.stack_depth, options.machine.stack_address), (12, Some(0xEA0)));
+        // Overrides apply on top of the machine, in any order.
+        let options = parse(&["--no-memory-stack", "--machine", "vip"])?;
+        assert_eq!((options.machine.stack_depth, options.machine.stack_address), (12, None));
+        assert!(parse(&["--stack-depth", "0"]).is_err());
+        assert!(parse(&["--stack-depth", "deep"]).is_err());
+        assert!(parse(&["--stack-depth"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn rom_dir_options_test() -> Result<(), String> {
+        assert_eq!(parse(&["--rom-dir", "roms"])?.rom_dir, Some(String::from("roms")));
+        assert_eq!(parse(&[])?.rom_dir, None);
+        assert!(parse(&["--rom-dir"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn analysis_options_test() -> Result<(), String> {
+        let options = parse(&["--dot", "out.dot", "--disassemble", "pong.ch8"])?;
+        assert_eq!(options.dot, Some(String::from("out.dot")));
+        assert!(options.disassemble);
+        assert_eq!(options.rom(), Ok("pong.ch8"));
+        Ok(())
+    }
+
+    #[test]
+    fn palette_options_test() -> Result<(), String> {
+        let options = parse(&["--palette", "amber", "--foreground", "#FF8000",
+                              "--background", "102030"])?;
+        assert_eq!(options.palette, Some(String::from("amber")));
+        assert_eq!(options.foreground, Some([0xff, 0x80, 0x00]));
+        assert_eq!(options.background, Some([0x10, 0x20, 0x30]));
+        Ok(())
+    }
+
+    #[test]
+    fn display_options_test() -> Result<(), String> {
+        let options = parse(&["--integer-scale", "--persistence", "4"])?;
+        assert!(options.integer_scale);
+        assert_eq!(options.persistence, 4);
+        assert_eq!(parse(&[])?.persistence, 0);
+        assert!(parse(&["--persistence", "long"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn filter_options_test() -> Result<(), String> {
+        let options = parse(&["--filter", "scale2x", "--mask", "scanlines"])?;
+        assert_eq!(options.upscaler.scaler, filter::Scaler::Scale2x);
+        assert_eq!(options.upscaler.mask, filter::Mask::Scanlines);
+        assert_eq!(parse(&[])?.upscaler, filter::Upscaler::default());
+        assert!(parse(&["--filter", "hq4x"]).is_err());
+        assert!(parse(&["--mask", "grille"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn screenshot_options_test() -> Result<(), String> {
+        let options = parse(&["--headless", "--screenshot-at-frame", "120",
+                              "--image-scale", "4", "ibm.ch8"])?;
+        assert!(options.headless);
+        assert_eq!(options.screenshot_at_frame, Some(120));
+        assert_eq!(options.image_scale, 4);
+        assert_eq!(parse(&[])?.image_scale, 10);
+        assert!(parse(&["--image-scale", "0"]).is_err());
+        assert!(parse(&["--screenshot-at-frame", "-1"]).is_err());
+        let options = parse(&["--headless", "--record", "--frames", "600"])?;
+        assert!(options.record);
+        assert_eq!(options.frames, Some(600));
+        Ok(())
+    }
+
+    #[test]
+    fn quirks_options_test() -> Result<(), String> {
+        assert_eq!(parse(&[])?.quirks, quirks::Quirks::modern());
+        assert_eq!(parse(&["--quirks", "vip"])?.quirks, quirks::Quirks::vip());
+        assert!(parse(&["--quirks", "vip"])?.quirks.display_wait);
+        assert!(!parse(&["--no-display-wait", "--quirks", "vip"])?.quirks.display_wait);
+        assert!(parse(&["--display-wait"])?.quirks.display_wait);
+        assert!(parse(&["--quirks", "eti"]).is_err());
+        assert!(parse(&["--quirks"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn speed_options_test() -> Result<(), String> {
+        assert_eq!(parse(&[])?.speed, 700);
+        assert_eq!(parse(&["--speed", "1200"])?.speed, 1200);
+        assert!(parse(&["--speed", "0"]).is_err());
+        assert!(parse(&["--speed"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn bad_option_test() {
+        assert!(parse(&["--frobnicate"]).is_err());
+        assert!(parse(&["--dot"]).is_err());
+        assert!(parse(&["--keymap"]).is_err());
+        assert!(parse(&["--foreground", "#12345"]).is_err());
+    }
+}
+
+#[cfg(test)]
+#[path = "lib.rs"]
+mod symbols_tests {
+    use super::super::{*};
+    use debugger::Debugger;
+    use symbols::SymbolTable;
+    use analysis::analyse;
+    use symbols::Monitor;
+
+    const SYMBOLS: &str = "# Exported by Octo\n\
+                           main 0x200\n\
+                           draw-player 0x206\n\
+                           player-sprite 520\n\
+                           :breakpoint check-input 0x204\n\
+                           :monitor score 0x300 3\n";
+
+    #[test]
+    fn parse_test() -> Result<(), String> {
+        let symbols = SymbolTable::parse(SYMBOLS)?;
+        assert_eq!(symbols.label(0x206), Some("draw-player"));
+        assert_eq!(symbols.label(0x208), Some("player-sprite"));
+        assert_eq!(symbols.label(0x20a), None);
+        assert_eq!(symbols.breakpoints, vec![(String::from("check-input"), 0x204)]);
+        assert_eq!(symbols.monitors, vec![Monitor {
+            name: String::from("score"),
+            address: 0x300,
+            length: 3,
+        }]);
+        Ok(())
+    }
+
+    #[test]
+    fn parse_error_test() {
+        assert!(SymbolTable::parse("main zzz").is_err());
+        assert!(SymbolTable::parse(":monitor score 0x300").is_err());
+    }
+
+    #[test]
+    fn describe_test() -> Result<(), String> {
+        let symbols = SymbolTable::parse(SYMBOLS)?;
+        assert_eq!(symbols.describe(0x206), "draw-player");
+        assert_eq!(symbols.describe(0x204), "main+4");
+        assert_eq!(symbols.name(0x204), "0x204");
+        assert_eq!(SymbolTable::new().describe(0x204), "0x204");
+        Ok(())
+    }
+
+    #[test]
+    fn labelled_disassemble_test() -> Result<(), String> {
+        let symbols = SymbolTable::parse(SYMBOLS)?;
+        let bytes = vec![0xA2, 0x08, 0x22, 0x06, 0x12, 0x04, 0xD0, 0x11, 0x00, 0xEE, 0x80];
+        let cfg = analyse(&bytes, 0x200);
+        let listing = disasm::disassemble(&bytes, &cfg, &symbols);
+        assert!(listing.starts_with(": main\n0x200: A208  LD I, player-sprite\n"));
+        assert!(listing.contains("0x202: 2206  CALL draw-player\n"));
+        assert!(listing.contains(": draw-player\n0x206: D011  DRW V0, V1, 1\n"));
+        assert!(listing.contains(": player-sprite\n0x208: 00EE  RET\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn breakpoint_test() -> Result<(), String> {
+        let mut debugger = Debugger::new(SymbolTable::parse(SYMBOLS)?, false);
+        let mut emu_state = InterpreterData::new();
+        emu_state.pc = 1;
+        assert_eq!(debugger.check_breakpoint(&emu_state), None);
+        emu_state.pc = 2;
+        assert_eq!(debugger.check_breakpoint(&emu_state), Some(String::from("check-input")));
+        assert!(debugger.paused);
+        debugger.resume();
+        assert!(!debugger.paused);
+        // Continuing must not break on the same instruction again.
+        assert_eq!(debugger.check_breakpoint(&emu_state), None);
+        Ok(())
+    }
+
+    #[test]
+    fn trace_test() -> Result<(), String> {
+        let debugger = Debugger::new(SymbolTable::parse(SYMBOLS)?, true);
+        let mut emu_state = InterpreterData::new();
+        emu_state.mem[0x202] = 0x22;
+        emu_state.mem[0x203] = 0x06;
+        emu_state.pc = 1;
+        assert_eq!(debugger.trace_line(&emu_state), "0x202: CALL draw-player");
+        assert!(debugger.state(&emu_state).starts_with("PC 0x202 (main+2)"));
+
+        emu_state.v[0xB] = 0x2A;
+        let lines = debugger.state_lines(&emu_state);
+        assert_eq!(lines.len(), 12);
+        assert_eq!(lines[0], "PC 0x202 main+2");
+        assert_eq!(lines[1], "I  0x000");
+        assert_eq!(lines[2], "SP 0  DT 0   ST 0");
+        assert_eq!(lines[8], "VA 00  VB 2A");
+        assert_eq!(lines[11], "0x202: CALL draw-player");
+        Ok(())
+    }
+
+    #[test]
+    fn monitor_test() -> Result<(), String> {
+        let mut debugger = Debugger::new(SymbolTable::parse(SYMBOLS)?, false);
+        let mut mem = [0u8; 4096];
+        assert_eq!(debugger.changed_monitors(&mem), vec![String::from("score 0x300: 00 00 00\n")]);
+        assert!(debugger.changed_monitors(&mem).is_empty());
+        mem[0x302] = 7;
+        assert_eq!(debugger.changed_monitors(&mem), vec![String::from("score 0x300: 00 00 07\n")]);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[path = "lib.rs"]
+mod engine_tests {
+    use super::super::{*};
+    use engine::BlockEngine;
+
+    /// Convert ROM bytes into a program and a state with the ROM loaded.
+    fn load(rom: &[u8]) -> (Vec<Instruction>, InterpreterData) {
+        let mut emu_state = InterpreterData::new();
+        for (i, b) in FONTSET.iter().enumerate() {
+            emu_state.mem[i] = *b;
+        }
+        for (i, b) in rom.iter().enumerate() {
+            emu_state.mem[i + 0x200] = *b;
+        }
+        let program = convert_program(&convert_bin_format(rom).ok().unwrap(), &Machine::default())
+            .unwrap();
+        (program, emu_state)
+    }
+
+    /// Run `rom` with the display wait for `frames` frames of `per_frame`
+    /// instructions with both engines and compare after every frame.
+    fn compare_frames(rom: &[u8], frames: usize, per_frame: usize) {
+        let (program, mut reference) = load(rom);
+        let (_, mut cached) = load(rom);
+        reference.quirks = quirks::Quirks::vip();
+        cached.quirks = quirks::Quirks::vip();
+        let keys = [false; 0x10];
+        let mut engine = BlockEngine::new();
+        for _ in 0..frames {
+            for _ in 0..per_frame {
+                emulate(&program, &mut reference, &keys);
+            }
+            engine.run(&program, &mut cached, &keys, per_frame);
+            assert_eq!(cached, reference);
+            reference.tick_frame();
+            cached.tick_frame();
+        }
+    }
+
+    /// Run `rom` for `count` instructions with both engines and compare.
+    /// Return the cached engine's state.
+    fn compare(rom: &[u8], count: usize) -> InterpreterData {
+        compare_quirks(rom, count, quirks::Quirks::default())
+    }
+
+    /// Run `rom` for `count` instructions with both engines and `quirks` and
+    /// compare, as `compare` does. Return the cached engine's state.
+    fn compare_quirks(rom: &[u8], count: usize, quirks: quirks::Quirks) -> InterpreterData {
+        let (program, mut reference) = load(rom);
+        let (_, mut cached) = load(rom);
+        reference.quirks = quirks;
+        cached.quirks = quirks;
+        let keys = [false; 0x10];
+        for _ in 0..count {
+            emulate(&program, &mut reference, &keys);
+        }
+        BlockEngine::new().run(&program, &mut cached, &keys, count);
+        assert_eq!(cached, reference);
+        assert!(cached.screen == reference.screen);
+        assert!((0..program.len()).all(|pos| cached.instruction(&program, pos) ==
+                                       reference.instruction(&program, pos)));
+        cached
+    }
+
+    const ALU_LOOP: [u8; 16] = [
+        0x60, 0x01, 0x61, 0x03, 0x80, 0x14, 0x81, 0x05,
+        0x82, 0x0E, 0x83, 0x27, 0xF0, 0x1E, 0x12, 0x04,
+    ];
+
+    // Calls, returns, skips and sprites.
+    const SUBROUTINES: [u8; 20] = [
+        0x22, 0x0C, // 0x200: CALL 0x20C
+        0x30, 0x05, // 0x202: SE V0, 0x05
+        0x12, 0x00, // 0x204: JP 0x200
+        0x00, 0xE0, // 0x206: CLS
+        0x12, 0x06, // 0x208: JP 0x206
+        0x00, 0x00, // 0x20A: SYS 0
+        0x70, 0x01, // 0x20C: ADD V0, 0x01
+        0xF0, 0x29, // 0x20E: LD F, V0
+        0xD1, 0x25, // 0x210: DRW V1, V2, 5
+        0x00, 0xEE, // 0x212: RET
+    ];
+
+    // Overwrites LD V3, 0x01 further down its loop with LD V3, V1.
+    const SELF_MODIFYING: [u8; 16] = [
+        0x60, 0x63, // 0x200: LD V0, 0x63
+        0x61, 0x09, // 0x202: LD V1, 0x09
+        0xA2, 0x0A, // 0x204: LD I, 0x20A
+        0xF1, 0x55, // 0x206: LD [I], V1
+        0x62, 0x05, // 0x208: LD V2, 0x05
+        0x63, 0x01, // 0x20A: LD V3, 0x01
+        0x71, 0x01, // 0x20C: ADD V1, 0x01
+        0x12, 0x06, // 0x20E: JP 0x206
+    ];
+
+    #[test]
+    fn alu_loop_test() {
+        for count in 0..100 {
+            compare(&ALU_LOOP, count);
+        }
+        compare(&ALU_LOOP, 10_000);
+    }
+
+    #[test]
+    fn subroutine_test() {
+        for count in 0..60 {
+            compare(&SUBROUTINES, count);
+        }
+    }
+
+    #[test]
+    fn display_wait_test() {
+        for per_frame in 1..15 {
+            compare_frames(&SUBROUTINES, 20, per_frame);
+        }
+    }
+
+    #[test]
+    fn self_modifying_test() {
+        for count in 0..40 {
+            compare(&SELF_MODIFYING, count);
+        }
+        // The rewritten instruction runs, in the block that rewrote it.
+        assert_eq!(compare(&SELF_MODIFYING, 6).v[3], 0x09);
+        // And again on every pass through the loop.
+        assert_eq!(compare(&SELF_MODIFYING, 11).v[3], 0x0A);
+        assert_eq!(compare(&SELF_MODIFYING, 21).v[3], 0x0C);
+
+        // The reference interpreter runs it on its own too, until the ROM is
+        // loaded again.
+        let (program, mut emu_state) = load(&SELF_MODIFYING);
+        for _ in 0..6 {
+            emulate(&program, &mut emu_state, &[false; 0x10]);
+        }
+        assert_eq!(emu_state.v[3], 0x09);
+        emu_state.load(&SELF_MODIFYING);
+        assert!(emu_state.instruction(&program, 5) == Instruction::Ld(3, 0x01));
+    }
+
+    #[test]
+    fn quirks_test() {
+        for count in 0..40 {
+            compare_quirks(&ALU_LOOP, count, quirks::Quirks::schip());
+            compare_quirks(&SELF_MODIFYING, count, quirks::Quirks::schip());
+        }
+        let vip = quirks::Quirks { display_wait: false, ..quirks::Quirks::vip() };
+        for count in 0..40 {
+            compare_quirks(&ALU_LOOP, count, vip);
+        }
+    }
+
+    #[test]
+    fn end_of_program_test() {
+        let (program, mut emu_state) = load(&ALU_LOOP[..4]);
+        BlockEngine::new().run(&program, &mut emu_state, &[false; 0x10], 10);
+        assert_eq!(emu_state.error, Some(String::from("Ran past the end of the program at 0x204.")));
+        assert_eq!(emu_state.pc, 2);
+        assert_eq!(emu_state.v[..2], [0x01, 0x03]);
+    }
+
+    #[test]
+    fn reset_test() {
+        let keys = [false; 0x10];
+        let mut engine = BlockEngine::new();
+        let (program, mut emu_state) = load(&ALU_LOOP);
+        engine.run(&program, &mut emu_state, &keys, 3);
+        assert!(engine.is_cached(0));
+
+        // Another program of the same length runs its own blocks.
+        let mut rom = ALU_LOOP;
+        rom[1] = 0x05;
+        let (other, mut emu_state) = load(&rom);
+        engine.run(&other, &mut emu_state, &keys, 1);
+        assert_eq!(emu_state.v[0], 0x05);
+
+        // The same program, loaded again after it wrote over its code.
+        let (program, mut emu_state) = load(&SELF_MODIFYING);
+        engine.run(&program, &mut emu_state, &keys, 12);
+        assert!(engine.is_cached(4));
+        emu_state.load(&SELF_MODIFYING);
+        emu_state.pc = 4;
+        engine.reset();
+        assert!(!engine.is_cached(4));
+        engine.run(&program, &mut emu_state, &keys, 2);
+        assert_eq!(emu_state.v[3], 0x01);
+    }
+
+    #[test]
+    fn invalidation_test() {
+        let (program, mut emu_state) = load(&SELF_MODIFYING);
+        let mut engine = BlockEngine::new();
+        // Run up to LD [I], V1, caching the block at 0x200, which runs up to
+        // the jump.
+        engine.run(&program, &mut emu_state, &[false; 0x10], 3);
+        assert!(engine.is_cached(0));
+        assert_eq!(emu_state.pc, 3);
+        // Cache the jump on its own.
+        emu_state.pc = 7;
+        engine.run(&program, &mut emu_state, &[false; 0x10], 1);
+        assert!(engine.is_cached(7));
+        assert_eq!(emu_state.pc, 3);
+        // LD [I], V1 writes over 0x20A and 0x20B, covered by the block at
+        // 0x200 and the running one but not by the jump.
+        engine.run(&program, &mut emu_state, &[false; 0x10], 1);
+        assert!(!engine.is_cached(0) && !engine.is_cached(3));
+        assert!(engine.is_cached(7));
+        assert!(emu_state.instruction(&program, 5) == Instruction::Ld(3, 0x09));
+    }
+}
+
+#[cfg(test)]
+#[path = "lib.rs"]
+mod display_tests {
+    use super::super::{*};
+    use display::{FRAME_BYTES, fit_screen, screen_to_rgb};
+    use palette::Palette;
+
+    #[test]
+    fn screen_to_rgb_test() {
+        let palette = Palette {
+            background: [4, 5, 6],
+            fill: [1, 2, 3],
+            fill2: [7, 8, 9],
+            blend: [10, 11, 12],
+            ..Palette::default()
+        };
+        let mut screen = Framebuffer::new(NUM_COLS, NUM_ROWS, 2);
+        screen.set(0, 1, 0, true);
+        screen.set(0, 0, 1, true);
+        let mut pixels = vec![0xaa; FRAME_BYTES];
+        screen_to_rgb(&screen, &palette, &mut pixels);
+        // Row-major, so (1, 0) is the second pixel and (0, 1) starts row 1.
+        assert_eq!(&pixels[0..6], &[4, 5, 6, 1, 2, 3]);
+        assert_eq!(&pixels[NUM_COLS * 3..NUM_COLS * 3 + 3], &[1, 2, 3]);
+        assert!(pixels[6..NUM_COLS * 3].chunks(3).all(|p| p == [4, 5, 6]));
+
+        // Cells of the second plane and of both.
+        screen.set(1, 2, 0, true);
+        screen.set(1, 1, 0, true);
+        screen_to_rgb(&screen, &palette, &mut pixels);
+        assert_eq!(&pixels[3..9], &[10, 11, 12, 7, 8, 9]);
+    }
+
+    #[test]
+    fn half_blocks_test() {
+        let mut screen = Framebuffer::new(NUM_COLS, NUM_ROWS, 1);
+        screen.set(0, 0, 0, true);
+        screen.set(0, 1, 1, true);
+        screen.set(0, 2, 0, true);
+        screen.set(0, 2, 1, true);
+        screen.set(0, NUM_COLS - 1, NUM_ROWS - 1, true);
+        let lines = display::screen_to_half_blocks(&screen);
+        assert_eq!(lines.len(), NUM_ROWS / 2);
+        assert!(lines.iter().all(|l| l.chars().count() == NUM_COLS));
+        assert!(lines[0].starts_with("\u{2580}\u{2584}\u{2588} "));
+        assert!(lines[NUM_ROWS / 2 - 1].ends_with(" \u{2584}"));
+        assert!(lines[1].chars().all(|c| c == ' '));
+    }
+
+    #[test]
+    fn fit_screen_test() {
+        assert_eq!(fit_screen(800, 400, false), (0, 0, 800, 400));
+        assert_eq!(fit_screen(1000, 400, false), (100, 0, 800, 400));
+        assert_eq!(fit_screen(640, 480, false), (0, 80, 640, 320));
+        assert_eq!(fit_screen(1000, 400, true), (116, 8, 768, 384));
+        assert_eq!(fit_screen(1920, 1080, true), (0, 60, 1920, 960));
+        // Never smaller than one pixel per cell.
+        assert_eq!(fit_screen(32, 16, true), (-16, -8, 64, 32));
+    }
+}
+
+#[cfg(test)]
+#[path = "lib.rs"]
+mod framebuffer_tests {
+    use framebuffer::Framebuffer;
+
+    #[test]
+    fn get_set_test() {
+        let mut fb = Framebuffer::new(128, 64, 2);
+        assert_eq!((fb.width(), fb.height(), fb.planes()), (128, 64, 2));
+        fb.set(0, 0, 0, true);
+        fb.set(1, 64, 3, true);
+        fb.set(0, 127, 63, true);
+        fb.set(1, 127, 63, true);
+        assert!(fb.get(0, 0) && !fb.get(1, 0));
+        assert_eq!(fb.planes_at(64, 3), 2);
+        assert_eq!(fb.planes_at(127, 63), 3);
+        assert_eq!(fb.row(0, 0), &[1 << 63, 0]);
+        assert_eq!(fb.row(1, 3), &[0, 1 << 63]);
+        fb.set(0, 0, 0, false);
+        assert_eq!(fb.lit().collect::<Vec<_>>(), vec![(64, 3), (127, 63)]);
+        fb.clear();
+        assert_eq!(fb.lit().count(), 0);
+    }
+
+    #[test]
+    fn xor_sprite_row_test() {
+        let mut fb = Framebuffer::new(128, 64, 1);
+        // Straddling two words.
+        assert!(!fb.xor_sprite_row(0, 60, 1, 0xff, 8));
+        assert_eq!(fb.row(0, 1), &[0xf, 0xf << 60]);
+        // Wrapping around the right edge and the bottom.
+        assert!(!fb.xor_sprite_row(0, 124, 65, 0xf0f0, 16));
+        assert_eq!(fb.row(0, 1), &[0xf | 0xf << 56, 0xf << 60 | 0xf]);
+        // Clearing a cell collides.
+        assert!(fb.xor_sprite_row(0, 63, 1, 0x80, 8));
+        assert_eq!(fb.row(0, 1), &[0xe | 0xf << 56, 0xf << 60 | 0xf]);
+        assert!(!fb.xor_sprite_row(0, 0, 2, 0x80, 8));
+    }
+
+    #[test]
+    fn scroll_test() {
+        let mut fb = Framebuffer::new(100, 10, 1);
+        fb.set(0, 0, 0, true);
+        fb.set(0, 63, 5, true);
+        fb.set(0, 99, 9, true);
+        fb.scroll(4, 2);
+        assert_eq!(fb.lit().collect::<Vec<_>>(), vec![(4, 2), (67, 7)]);
+        fb.scroll(-70, -7);
+        assert_eq!(fb.lit().collect::<Vec<_>>(), vec![]);
+        fb.set(0, 99, 9, true);
+        fb.scroll(-99, 0);
+        assert_eq!(fb.lit().collect::<Vec<_>>(), vec![(0, 9)]);
+        // Bits past the width stay clear, so equal screens compare equal.
+        fb.set(0, 99, 0, true);
+        fb.scroll(1, 0);
+        assert_eq!(fb, {
+            let mut f = Framebuffer::new(100, 10, 1);
+            f.set(0, 1, 9, true);
+            f
+        });
+    }
+
+    #[test]
+    fn hash_diff_test() {
+        let a = Framebuffer::new(64, 32, 1);
+        let mut b = a.clone();
+        assert_eq!(a.hash(), b.hash());
+        assert_eq!(a.diff(&b), vec![]);
+        b.set(0, 3, 1, true);
+        b.set(0, 63, 0, true);
+        assert_ne!(a.hash(), b.hash());
+        assert_eq!(a.diff(&b), vec![(63, 0), (3, 1)]);
+        assert_ne!(a.hash(), Framebuffer::new(32, 64, 1).hash());
+        assert_eq!(a.diff(&Framebuffer::new(2, 2, 1)).len(), 64 * 32);
+        assert_eq!(a.iter().count(), 64 * 32);
+    }
+}
+
+#[cfg(test)]
+#[path = "lib.rs"]
+mod keymap_tests {
+    use keymap::Keymap;
+
+    #[test]
+    fn default_layout_test() {
+        let keymap = Keymap::default();
+        assert_eq!(keymap.key("1"), Some(0x1));
+        assert_eq!(keymap.key("4"), Some(0xC));
+        assert_eq!(keymap.key("q"), Some(0x4));
+        assert_eq!(keymap.key("X"), Some(0x0));
+        assert_eq!(keymap.key("V"), Some(0xF));
+        assert_eq!(keymap.key("P"), None);
+        assert_eq!(keymap.bindings().count(), 0x10);
+    }
+
+    #[test]
+    fn parse_test() -> Result<(), String> {
+        let keymap = Keymap::parse("# Arrows\nUp = 5\nKeypad 8 = 8 # down\n= = f\n")?;
+        assert_eq!(keymap.key("up"), Some(0x5));
+        assert_eq!(keymap.key("Keypad 8"), Some(0x8));
+        assert_eq!(keymap.key("="), Some(0xF));
+        assert!(Keymap::parse("Q 4").is_err());
+        assert!(Keymap::parse("Q = 10").is_err());
+        assert!(Keymap::parse(" = 1").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn merge_test() -> Result<(), String> {
+        let mut keymap = Keymap::default();
+        keymap.merge(&Keymap::parse("Q = 5\nUp = 5")?);
+        assert_eq!(keymap.key("Q"), Some(0x5));
+        assert_eq!(keymap.key("Up"), Some(0x5));
+        assert_eq!(keymap.key("W"), Some(0x5));
+        assert_eq!(Keymap::load_for_rom("no/such/rom.ch8")?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn pad_test() -> Result<(), String> {
+        let mut keymap = Keymap::default();
+        assert_eq!(keymap.pad_key(0, "dpup"), Some(0x5));
+        assert_eq!(keymap.pad_key(0, "leftx+"), Some(0x9));
+        // The second pad defaults to the second player's keys.
+        assert_eq!(keymap.pad_key(1, "dpup"), Some(0xC));
+        assert_eq!(keymap.pad_key(1, "leftx+"), Some(0xF));
+        assert_eq!(keymap.deadzone(), 8000);
+
+        // Pong, left paddle on pad 1 and right paddle on pad 2.
+        let pong = Keymap::parse("pad1 dpup = 1\nPAD1 dpdown = 4\n\
+                                  pad2 DPUP = c\npad2 dpdown = d\n:deadzone 4000")?;
+        assert_eq!(pong.pad_key(0, "dpup"), Some(0x1));
+        assert_eq!(pong.pad_key(1, "dpup"), Some(0xC));
+        assert_eq!(pong.key("dpup"), None);
+        keymap.merge(&pong);
+        assert_eq!(keymap.pad_key(1, "dpdown"), Some(0xD));
+        assert_eq!(keymap.pad_key(1, "a"), Some(0xB));
+        assert_eq!(keymap.deadzone(), 4000);
+
+        assert!(Keymap::parse(":deadzone 40000").is_err());
+        assert!(Keymap::parse("pad3 a = 1")?.key("pad3 a").is_some());
+        assert_eq!(Keymap::parse("pad2 a = 1")?.pad_key(5, "a"), None);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[path = "lib.rs"]
+mod keypad_tests {
+    use super::super::{*};
+    use keypad::{KeyPoll, key_at, key_position, polled_key};
+
+    #[test]
+    fn polled_key_test() {
+        type I = Instruction;
+        let program = [I::Skp(0), I::SkpN(1), I::LdW(2), I::Cls];
+        let mut emu_state = InterpreterData::new();
+        emu_state.v[0] = 0xC;
+        emu_state.v[1] = 0x10;
+        assert_eq!(polled_key(&program, &emu_state), Some(KeyPoll::Key(0xC)));
+        emu_state.pc = 1;
+        assert_eq!(polled_key(&program, &emu_state), None);
+        emu_state.pc = 2;
+        assert_eq!(polled_key(&program, &emu_state), Some(KeyPoll::Any));
+        emu_state.pc = 3;
+        assert_eq!(polled_key(&program, &emu_state), None);
+        emu_state.pc = 4;
+        assert_eq!(polled_key(&program, &emu_state), None);
+    }
+
+    #[test]
+    fn layout_test() {
+        assert_eq!(key_position(0x1), (0, 0));
+        assert_eq!(key_position(0xC), (3, 0));
+        assert_eq!(key_position(0x0), (1, 3));
+        assert_eq!(key_at(0, 0, 400), Some(0x1));
+        assert_eq!(key_at(399, 0, 400), Some(0xC));
+        assert_eq!(key_at(150, 399, 400), Some(0x0));
+        assert_eq!(key_at(400, 0, 400), None);
+        assert_eq!(key_at(-1, 0, 400), None);
+        for key in 0..0x10 {
+            let (column, row) = key_position(key);
+            assert_eq!(key_at(column as i32 * 100 + 50, row as i32 * 100 + 50, 400), Some(key));
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "lib.rs"]
+mod palette_tests {
+    use palette::{Palette, parse_color};
+
+    #[test]
+    fn builtin_test() {
+        let names = Palette::builtins().into_iter().map(|p| p.name).collect::<Vec<String>>();
+        assert_eq!(names, ["classic", "green", "amber", "lcd", "high-contrast"]);
+        assert_eq!(Palette::default().fill, [0xff, 0xff, 0xff]);
+        assert_eq!(Palette::default().background, [0, 0, 0]);
+        assert_eq!(Palette::builtin("amber").unwrap().fill, [0xff, 0xb0, 0x00]);
+        assert_eq!(Palette::builtin("sepia"), None);
+        assert!(Palette::from_arg("no/such/palette").is_err());
+    }
+
+    #[test]
+    fn parse_test() -> Result<(), String> {
+        let palette = Palette::parse("ocean", "# Deep blue\n\
+                                              background = #001020\n\
+                                              fill = 40C0FF # cyan\n\
+                                              blend = #FFFFFF\n")?;
+        assert_eq!(palette.name, "ocean");
+        assert_eq!(palette.color(0), [0x00, 0x10, 0x20]);
+        assert_eq!(palette.color(1), [0x40, 0xc0, 0xff]);
+        assert_eq!(palette.color(2), Palette::default().fill2);
+        assert_eq!(palette.color(3), [0xff, 0xff, 0xff]);
+        assert!(Palette::parse("x", "fill #FFFFFF").is_err());
+        assert!(Palette::parse("x", "outline = #FFFFFF").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn parse_color_test() {
+        assert_eq!(parse_color("#0A0b0C"), Ok([0x0a, 0x0b, 0x0c]));
+        assert_eq!(parse_color("FFFFFF"), Ok([0xff, 0xff, 0xff]));
+        assert!(parse_color("#FFF").is_err());
+        assert!(parse_color("#GGGGGG").is_err());
+        assert!(parse_color("+12345").is_err());
+    }
+}
+
+#[cfg(test)]
+#[path = "lib.rs"]
+mod filter_tests {
+    use super::super::{*};
+    use display::FRAME_BYTES;
+    use filter::{Mask, Persistence, Scaler, Upscaler};
+    use palette::Palette;
+
+    const W: [u8; 3] = [0xff, 0xff, 0xff];
+    const B: [u8; 3] = [0, 0, 0];
+
+    /// Flatten rows of colours into RGB pixel data.
+    fn rgb(rows: &[&[[u8; 3]]]) -> Vec<u8> {
+        rows.iter().flat_map(|r| r.iter().flatten().copied()).collect()
+    }
+
+    #[test]
+    fn persistence_test() {
+        let mut screen = Framebuffer::new(NUM_COLS, NUM_ROWS, 1);
+        let mut persistence = Persistence::new(3);
+        assert!(!persistence.update(&screen));
+
+        // Set cells light up at once.
+        screen.set(0, 2, 1, true);
+        assert!(persistence.update(&screen));
+        assert_eq!(persistence.intensity(2, 1), 0xff);
+        assert!(!persistence.update(&screen));
+
+        // Cleared cells fade out over 3 frames.
+        screen.set(0, 2, 1, false);
+        assert!(persistence.update(&screen));
+        assert_eq!(persistence.intensity(2, 1), 0xaa);
+        assert!(persistence.update(&screen));
+        assert_eq!(persistence.intensity(2, 1), 0x55);
+        assert!(persistence.update(&screen));
+        assert_eq!(persistence.intensity(2, 1), 0);
+        assert!(!persistence.update(&screen));
+        assert_eq!(persistence.intensity(0, 0), 0);
+    }
+
+    #[test]
+    fn persistence_rgb_test() {
+        let mut screen = Framebuffer::new(NUM_COLS, NUM_ROWS, 1);
+        let mut persistence = Persistence::new(2);
+        screen.set(0, 0, 0, true);
+        screen.set(0, 1, 0, true);
+        persistence.update(&screen);
+        screen.set(0, 1, 0, false);
+        persistence.update(&screen);
+
+        let palette = Palette {
+            background: [0x00, 0x00, 0x40],
+            fill: [0xff, 0x80, 0x00],
+            fill2: [0x00, 0xff, 0x00],
+            ..Palette::default()
+        };
+        let mut pixels = vec![0; FRAME_BYTES];
+        persistence.to_rgb(&palette, &mut pixels);
+        assert_eq!(&pixels[0..3], &[0xff, 0x80, 0x00]);
+        assert_eq!(&pixels[3..6], &[0x7f, 0x3f, 0x21]);
+        assert_eq!(&pixels[6..9], &[0x00, 0x00, 0x40]);
+    }
+
+    #[test]
+    fn persistence_planes_test() {
+        let mut screen = Framebuffer::new(NUM_COLS, NUM_ROWS, 2);
+        let mut persistence = Persistence::new(2);
+        screen.set(0, 0, 0, true);
+        persistence.update(&screen);
+        // Moving a lit cell to another plane changes its colour.
+        screen.set(0, 0, 0, false);
+        screen.set(1, 0, 0, true);
+        assert!(persistence.update(&screen));
+        // It fades out in the colour of the plane it was last set in.
+        screen.set(1, 0, 0, false);
+        persistence.update(&screen);
+        let palette = Palette { fill2: [0x00, 0xff, 0x00], ..Palette::default() };
+        let mut pixels = vec![0; FRAME_BYTES];
+        persistence.to_rgb(&palette, &mut pixels);
+        assert_eq!(&pixels[0..3], &[0x00, 0x7f, 0x00]);
+    }
+
+    #[test]
+    fn nearest_test() {
+        let frame = rgb(&[&[W, B]]);
+        assert_eq!(Upscaler::default().factor(), 1);
+        assert_eq!(Upscaler::default().apply(&frame, 2, 1), frame);
+
+        let masked = Upscaler { scaler: Scaler::Nearest, mask: Mask::Scanlines };
+        assert_eq!(masked.factor(), 3);
+        let half = [0x7f, 0x7f, 0x7f];
+        assert_eq!(masked.apply(&frame, 2, 1), rgb(&[&[W, W, W, B, B, B],
+                                                      &[W, W, W, B, B, B],
+                                                      &[half, half, half, B, B, B]]));
+    }
+
+    /// The top left corner of a block, which scalers round off.
+    fn corner() -> Vec<u8> {
+        rgb(&[&[B, B, B],
+              &[B, W, W],
+              &[B, W, W]])
+    }
+
+    #[test]
+    fn scale2x_test() {
+        let scale2x = Upscaler { scaler: Scaler::Scale2x, mask: Mask::None };
+        let scaled = scale2x.apply(&corner(), 3, 3);
+        assert_eq!(scaled.len(), 6 * 6 * 3);
+        let at = |x: usize, y: usize| &scaled[(y * 6 + x) * 3..(y * 6 + x) * 3 + 3];
+        assert_eq!(at(2, 2), B);
+        assert_eq!(at(3, 2), W);
+        assert_eq!(at(2, 3), W);
+        assert_eq!(at(3, 3), W);
+
+        // Isolated pixels stay square.
+        let dot = rgb(&[&[B, B, B], &[B, W, B], &[B, B, B]]);
+        let scaled = scale2x.apply(&dot, 3, 3);
+        assert_eq!(&scaled[(2 * 6 + 2) * 3..(2 * 6 + 4) * 3], &rgb(&[&[W, W]])[..]);
+
+        let dots = Upscaler { scaler: Scaler::Scale2x, mask: Mask::DotMatrix };
+        let half = [0x7f, 0x7f, 0x7f];
+        assert_eq!(dots.apply(&rgb(&[&[W]]), 1, 1), rgb(&[&[W, half], &[half, half]]));
+    }
+
+    #[test]
+    fn scale3x_test() {
+        let scale3x = Upscaler { scaler: Scaler::Scale3x, mask: Mask::None };
+        let scaled = scale3x.apply(&corner(), 3, 3);
+        assert_eq!(scaled.len(), 9 * 9 * 3);
+        let at = |x: usize, y: usize| &scaled[(y * 9 + x) * 3..(y * 9 + x) * 3 + 3];
+        assert_eq!(at(3, 3), B);
+        assert_eq!(at(4, 3), B);
+        assert_eq!(at(5, 3), W);
+        assert_eq!(at(3, 4), B);
+        assert_eq!(at(4, 4), W);
+        assert_eq!(at(3, 5), W);
+        assert!(Scaler::parse("scale3x").is_ok());
+        assert!(Mask::parse("dot-matrix").is_ok());
+    }
+}
+
+#[cfg(test)]
+#[path = "lib.rs"]
+mod image_tests {
+    use super::super::{*};
+    use filter::{Mask, Scaler, Upscaler};
+    use image::{encode_png, render, screenshot};
+    use palette::Palette;
+    use png;
+
+    #[test]
+    fn render_test() {
+        let mut screen = Framebuffer::new(NUM_COLS, NUM_ROWS, 1);
+        screen.set(0, 1, 0, true);
+        let amber = Palette::builtin("amber").unwrap();
+        let image = render(&screen, &amber, &Upscaler::default(), 2);
+        assert_eq!((image.width, image.height), (NUM_COLS * 2, NUM_ROWS * 2));
+        assert_eq!(&image.pixels[0..6], &[amber.background, amber.background].concat()[..]);
+        assert_eq!(&image.pixels[6..12], &[amber.fill, amber.fill].concat()[..]);
+        let row = NUM_COLS * 2 * 3;
+        assert_eq!(&image.pixels[row + 6..row + 12], &image.pixels[6..12]);
+
+        let scale2x = Upscaler { scaler: Scaler::Scale2x, mask: Mask::None };
+        let image = render(&screen, &amber, &scale2x, 3);
+        assert_eq!((image.width, image.height), (NUM_COLS * 6, NUM_ROWS * 6));
+    }
+
+    #[test]
+    fn png_test() -> Result<(), String> {
+        let mut screen = Framebuffer::new(NUM_COLS, NUM_ROWS, 1);
+        screen.set(0, NUM_COLS - 1, NUM_ROWS - 1, true);
+        let bytes = screenshot(&screen, &Palette::default(), &Upscaler::default(), 1)?;
+        assert_eq!(&bytes[0..8], b"\x89PNG\r\n\x1a\n");
+
+        let mut reader = png::Decoder::new(&bytes[..]).read_info().map_err(|e| e.to_string())?;
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).map_err(|e| e.to_string())?;
+        assert_eq!((info.width, info.height), (NUM_COLS as u32, NUM_ROWS as u32));
+        assert_eq!(info.color_type, png::ColorType::Rgb);
+        let image = render(&screen, &Palette::default(), &Upscaler::default(), 1);
+        assert_eq!(&pixels[..info.buffer_size()], &image.pixels[..]);
+        assert_eq!(encode_png(&image)?, bytes);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[path = "lib.rs"]
+mod recording_tests {
+    use super::super::{*};
+    use filter::Upscaler;
+    use gif;
+    use palette::Palette;
+    use recording::Recorder;
+
+    #[test]
+    fn merge_frames_test() {
+        let mut screen = Framebuffer::new(NUM_COLS, NUM_ROWS, 1);
+        let mut recorder = Recorder::new(Upscaler::default(), 1);
+        for _ in 0..3 {
+            recorder.capture(&screen, &Palette::default());
+        }
+        screen.set(0, 0, 0, true);
+        recorder.capture(&screen, &Palette::default());
+        recorder.capture(&screen, &Palette::builtin("amber").unwrap());
+        assert_eq!(recorder.frames(), 5);
+        assert_eq!(recorder.shots(), 3);
+    }
+
+    /// Return the delays of the frames of a GIF.
+    /// # Arguments
+    /// * `bytes` The GIF.
+    fn gif_delays(bytes: &[u8]) -> Result<Vec<u16>, String> {
+        let mut decoder = gif::DecodeOptions::new().read_info(bytes).map_err(|e| e.to_string())?;
+        let mut result = Vec::new();
+        while let Some(frame) = decoder.read_next_frame().map_err(|e| e.to_string())? {
+            result.push(frame.delay);
+        }
+        Ok(result)
+    }
+
+    #[test]
+    fn short_frames_test() -> Result<(), String> {
+        // A screen changing every frame, 11 frames or 18.33 hundredths of a
+        // second.
+        let mut screen = Framebuffer::new(NUM_COLS, NUM_ROWS, 1);
+        let mut recorder = Recorder::new(Upscaler::default(), 1);
+        for frame in 0..11 {
+            screen.set(0, frame, 0, true);
+            recorder.capture(&screen, &Palette::default());
+        }
+        let delays = gif_delays(&recorder.encode_gif()?)?;
+        assert_eq!(delays, [2, 3, 2, 3, 2, 3, 3]);
+        assert_eq!(delays.iter().sum::<u16>(), 18);
+
+        // Every length of recording keeps its time, none below 2.
+        for frames in 1..31 {
+            let mut recorder = Recorder::new(Upscaler::default(), 1);
+            for frame in 0..frames {
+                screen.set(0, 0, 1, frame % 2 == 0);
+                recorder.capture(&screen, &Palette::default());
+            }
+            let delays = gif_delays(&recorder.encode_gif()?)?;
+            assert!(delays.iter().all(|&d| d >= 2));
+            assert_eq!(delays.iter().map(|&d| d as usize).sum::<usize>(), (frames * 100 + 30) / 60);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn gif_test() -> Result<(), String> {
+        let mut screen = Framebuffer::new(NUM_COLS, NUM_ROWS, 1);
+        let amber = Palette::builtin("amber").unwrap();
+        let mut recorder = Recorder::new(Upscaler::default(), 2);
+        // 1 frame, then 2, then 3, at 60 frames per second.
+        recorder.capture(&screen, &amber);
+        screen.set(0, 1, 0, true);
+        recorder.capture(&screen, &amber);
+        recorder.capture(&screen, &amber);
+        screen.set(0, 1, 0, false);
+        for _ in 0..3 {
+            recorder.capture(&screen, &amber);
+        }
+        let bytes = recorder.encode_gif()?;
+
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::RGBA);
+        let mut decoder = options.read_info(&bytes[..]).map_err(|e| e.to_string())?;
+        assert_eq!((decoder.width(), decoder.height()), (NUM_COLS as u16 * 2, NUM_ROWS as u16 * 2));
+        let mut delays = Vec::new();
+        let mut pixels = Vec::new();
+        while let Some(frame) = decoder.read_next_frame().map_err(|e| e.to_string())? {
+            delays.push(frame.delay);
+            pixels.push(frame.buffer[8..12].to_vec());
+        }
+        // Rounded from 1/60, 3/60 and 6/60 seconds.
+        assert_eq!(delays, [2, 3, 5]);
+        let fill = [amber.fill[0], amber.fill[1], amber.fill[2], 0xff];
+        let background = [amber.background[0], amber.background[1], amber.background[2], 0xff];
+        assert_eq!(pixels, [background.to_vec(), fill.to_vec(), background.to_vec()]);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[path = "lib.rs"]
+mod launcher_tests {
+    use get_program;
+    use launcher::{Exit, Menu, Platform, RomEntry, Session, format_size, scan, title};
+    use machine::Machine;
+    use source::RomSource;
+    use std::env;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use text::{glyph, text_pixels};
+
+    /// Return an empty directory for the test, removing what an earlier run
+    /// left in it.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("chip8-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn entry(title: &str) -> RomEntry {
+        RomEntry {
+            path: PathBuf::from(format!("{}.ch8", title)),
+            title: String::from(title),
+            size: 100,
+            platform: Platform::Chip8,
+            entry: None,
+        }
+    }
+
+    #[test]
+    fn detect_test() {
+        // CLS, JP 0x202.
+        assert_eq!(Platform::detect(&[0x00, 0xE0, 0x12, 0x02]), Platform::Chip8);
+        // HIGH, JP 0x202.
+        assert_eq!(Platform::detect(&[0x00, 0xFF, 0x12, 0x02]), Platform::SuperChip);
+        // LD I, long 0x0300 stops the analysis but counts.
+        assert_eq!(Platform::detect(&[0x00, 0xFF, 0xF0, 0x00, 0x03, 0x00]), Platform::XoChip);
+        // JP 0x204 over data that looks like HIGH.
+        assert_eq!(Platform::detect(&[0x12, 0x04, 0x00, 0xFF, 0x12, 0x04]), Platform::Chip8);
+        assert_eq!(Platform::from_extension("SC8"), Some(Platform::SuperChip));
+        assert_eq!(Platform::from_extension("bin"), None);
+    }
+
+    #[test]
+    fn scan_test() -> Result<(), String> {
+        let dir = test_dir("scan");
+        fs::write(dir.join("space_invaders.ch8"), [0x00, 0xE0, 0x12, 0x02]).unwrap();
+        fs::write(dir.join("Car.sc8"), [0x00, 0xE0]).unwrap();
+        fs::write(dir.join("big-demo.ch8"), vec![0x00; 3000]).unwrap();
+        fs::write(dir.join("notes.txt"), "").unwrap();
+        fs::write(dir.join("recording.gif"), "GIF89a").unwrap();
+        // ROMs that cannot be decoded are left out.
+        fs::write(dir.join("broken.hex"), ":0102").unwrap();
+        fs::write(dir.join("odd.ihx"), ":0202000000E01B").unwrap();
+        fs::create_dir(dir.join("dir.ch8")).unwrap();
+        let entries = scan(&dir)?;
+        let titles: Vec<&str> = entries.iter().map(|e| e.title.as_str()).collect();
+        assert_eq!(titles, ["big demo", "Car", "space invaders"]);
+        assert_eq!(entries[1].platform, Platform::SuperChip);
+        assert_eq!(entries[2].size, 4);
+        assert_eq!(entries[2].path, dir.join("space_invaders.ch8"));
+        assert!(scan(&dir.join("missing")).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn menu_test() {
+        let mut menu = Menu::new(["a", "b", "c", "d", "e"].iter().map(|t| entry(t)).collect());
+        assert_eq!(menu.selected().unwrap().title, "a");
+        menu.move_by(-1);
+        assert_eq!(menu.selected().unwrap().title, "a");
+        menu.move_by(3);
+        // Scrolled so the selection is the last line.
+        let (lines, selected) = menu.lines(30, 2);
+        assert_eq!(lines, ["c               100 B   CHIP-8",
+                           "d               100 B   CHIP-8"]);
+        assert!(lines.iter().all(|l| l.len() == 30));
+        assert_eq!(selected, Some(1));
+        menu.move_by(10);
+        assert_eq!(menu.selected().unwrap().title, "e");
+        menu.select(Path::new("b.ch8"), None);
+        assert_eq!(menu.lines(30, 2).1, Some(0));
+        assert_eq!(Menu::new(Vec::new()).lines(30, 2), (vec![], None));
+    }
+
+    #[test]
+    fn session_test() {
+        let mut session = Session::new(Some((String::from("pong.ch8"), None)));
+        assert_eq!(session.take_next(), Some((String::from("pong.ch8"), None)));
+        session.start("pong.ch8", None);
+        // A ROM that fails to load goes back to the menu, with the error.
+        assert!(session.end(Err(String::from("pong.ch8: Not found."))));
+        assert_eq!(session.take_next(), None);
+        assert_eq!(session.error(), Some("pong.ch8: Not found."));
+        assert_eq!(session.last(), Some(("pong.ch8", None)));
+        // Choosing another forgets it.
+        session.start("pack.zip", Some("tank.ch8"));
+        assert_eq!(session.error(), None);
+        assert!(session.end(Ok(Exit::Menu)));
+        assert_eq!(session.take_next(), None);
+        assert!(!session.end(Ok(Exit::Quit)));
+    }
+
+    #[test]
+    fn failed_load_test() {
+        let dir = test_dir("failed-load");
+        let rom = dir.join("huge.ch8");
+        fs::write(&rom, vec![0; 0x10000]).unwrap();
+        let rom = rom.to_string_lossy().into_owned();
+        let mut session = Session::new(Some((String::from("pong.ch8"), None)));
+        session.take_next();
+        session.start("pong.ch8", None);
+        // A file dropped while playing is loaded next.
+        assert!(session.end(Ok(Exit::Load(rom.clone(), None))));
+        assert_eq!(session.take_next(), Some((rom.clone(), None)));
+        session.start(&rom, None);
+        // It does not fit, which goes back to the menu instead of quitting.
+        let machine = Machine::default();
+        let result = RomSource::open(&rom, None)
+            .and_then(|source| get_program(&source, &[], &machine))
+            .map(|_| Exit::Menu);
+        assert!(result.is_err());
+        assert!(session.end(result));
+        assert_eq!(session.take_next(), None);
+        assert!(session.error().is_some());
+        assert_eq!(session.last(), Some((rom.as_str(), None)));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn format_test() {
+        assert_eq!(format_size(246), "246 B");
+        assert_eq!(format_size(3584), "3.5 KB");
+        assert_eq!(title(Path::new("roms/br8kout_v2-1.ch8")), "br8kout v2 1");
+    }
+
+    #[test]
+    fn text_test() {
+        assert_eq!(glyph('a'), glyph('A'));
+        assert_eq!(glyph('~'), glyph('?'));
+        // "-" is the middle row, the next character starts 4 pixels on.
+        assert_eq!(text_pixels("-."), [(0, 2), (1, 2), (2, 2), (5, 4)]);
+    }
+}
+
+#[cfg(test)]
+#[path = "lib.rs"]
+mod watch_tests {
+    use std::env;
+    use std::fs;
+    use std::time::{Duration, SystemTime};
+    use watch::FileWatcher;
+
+    #[test]
+    fn poll_test() {
+        let path = env::temp_dir().join(format!("chip8-watch-{}.ch8", std::process::id()));
+        fs::write(&path, [0x00, 0xE0]).unwrap();
+        let name = path.to_string_lossy().into_owned();
+        let mut watcher = FileWatcher::new(&name);
+        assert!(!watcher.poll());
+
+        // Reported once the new build is the same on two polls.
+        fs::write(&path, [0x00, 0xE0, 0x12, 0x00]).unwrap();
+        assert!(!watcher.poll());
+        assert!(watcher.poll());
+        assert!(!watcher.poll());
+
+        // Rebuilt with the same size, a second later.
+        let file = fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(1)).unwrap();
+        assert!(!watcher.poll());
+        assert!(watcher.poll());
+
+        // Deleted files are ignored until they are written again.
+        fs::remove_file(&path).unwrap();
+        assert!(!watcher.poll());
+        assert!(!watcher.poll());
+        fs::write(&path, [0x00, 0xE0]).unwrap();
+        assert!(!watcher.poll());
+        assert!(watcher.poll());
+        fs::remove_file(&path).unwrap();
+    }
+}
+
+#[cfg(test)]
+#[path = "lib.rs"]
+mod octo_tests {
+    use octo::assemble;
+
+    #[test]
+    fn assemble_test() -> Result<(), String> {
+        let rom = assemble("
+            : main
+              v0 := 5
+              v1 += 0x10   # Comment.
+              i := data
+              sprite v0 v1 2
+              loop again
+            : data 0xFF 0b10000001")?;
+        assert_eq!(rom, [0x12, 0x02, 0x60, 0x05, 0x71, 0x10, 0xA2, 0x0C, 0xD0, 0x12,
+                         0x12, 0x0A, 0xFF, 0x81]);
+        // Without main, from the first instruction.
+        assert_eq!(assemble("clear")?, [0x12, 0x02, 0x00, 0xE0]);
+        Ok(())
+    }
+
+    #[test]
+    fn control_flow_test() -> Result<(), String> {
+        let rom = assemble("
+            : main
+              if v0 == 1 then v1 := 2
+              if v0 != v2 begin
+                v3 := 1
+              else
+                v3 := 2
+              end
+              loop
+                v4 += 1
+                while v4 < 10
+              again
+              sub
+            : sub ;")?;
+        assert_eq!(rom, [0x12, 0x02, 0x40, 0x01, 0x61, 0x02, 0x90, 0x20, 0x12, 0x0E,
+                         0x63, 0x01, 0x12, 0x10, 0x63, 0x02, 0x74, 0x01, 0x6F, 0x0A,
+                         0x8F, 0x47, 0x3F, 0x00, 0x12, 0x1C, 0x12, 0x10, 0x22, 0x1E,
+                         0x00, 0xEE]);
+        // vf := v2, vf -= v1, skip unless vf == 1.
+        assert_eq!(assemble("if v1 <= v2 then v0 += 1")?[2..],
+                   [0x8F, 0x20, 0x8F, 0x15, 0x4F, 0x01, 0x70, 0x01]);
+        assert_eq!(assemble("if v1 -key then v0 -= 1")?[2..], [0xE1, 0x9E, 0x70, 0xFF]);
+        Ok(())
+    }
+
+    #[test]
+    fn directives_test() -> Result<(), String> {
+        let rom = assemble("
+            :const SPEED 3
+            :alias counter v5
+            :macro add-speed reg { reg += SPEED }
+            # Right to left: SPEED * ( 2 + 1 ).
+            :calc NINE { SPEED * 2 + 1 }
+            : main
+              add-speed counter
+              counter := NINE
+              :unpack 0xA table
+              :next target v0 := 0x42
+              i := long table
+              :byte { @ target }
+            : table
+              :org 0x300
+              7")?;
+        assert_eq!(rom[..0x12], [0x12, 0x02, 0x75, 0x03, 0x65, 0x09, 0x60, 0xA2, 0x61, 0x11,
+                                 0x60, 0x42, 0xF0, 0x00, 0x02, 0x11, 0x42, 0x00]);
+        assert_eq!(rom.len(), 0x101);
+        assert_eq!(rom[0x100], 7);
+        Ok(())
+    }
+
+    #[test]
+    fn macro_test() -> Result<(), String> {
+        let rom = assemble("
+            :macro twice reg { reg += 1 reg += 1 }
+            :macro set reg value { reg := { value + 1 } }
+            # CALLS counts the expansions before this one.
+            :macro tag { :byte CALLS }
+            : main
+              twice v2
+              twice v3
+              set v4 9
+              tag tag")?;
+        assert_eq!(rom, [0x12, 0x02, 0x72, 0x01, 0x72, 0x01, 0x73, 0x01, 0x73, 0x01,
+                         0x64, 0x0A, 0x00, 0x01]);
+        assert!(assemble(":macro twice reg { reg += 1 }\ntwice").is_err());
+        assert!(assemble(":macro open { clear").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn calc_test() -> Result<(), String> {
+        let rom = assemble("
+            :calc A { 2 * 3 + 1 }
+            :calc B { ( 2 * 3 ) + 1 }
+            :calc C { - 3 + 5 }
+            :calc D { 0xF0 >> 4 | 1 }
+            :calc E { floor ( 7 / 2 ) }
+            : start
+            :calc F { ( start + 1 ) & 0xFF }
+            :calc G { HERE - 0x200 }
+            :assert \"A-is-eight\" { A == 8 }
+            :byte A :byte B :byte C :byte D :byte E :byte F :byte G")?;
+        assert_eq!(rom, [0x12, 0x02, 0x08, 0x07, 0x02, 0x07, 0x03, 0x03, 0x02]);
+        assert_eq!(assemble(":assert \"small\" { 1 > 2 }"), Err(String::from("Line 1: small.")));
+        assert_eq!(assemble(":calc X { 1 ? 2 }"), Err(String::from("Line 1: Unknown operator ?.")));
+        Ok(())
+    }
+
+    #[test]
+    fn org_test() -> Result<(), String> {
+        let rom = assemble("
+            : main
+              v0 := 1
+            :org 0x208
+            : far
+              v1 := 2
+              jump far")?;
+        assert_eq!(rom, [0x12, 0x02, 0x60, 0x01, 0x00, 0x00, 0x00, 0x00, 0x61, 0x02,
+                         0x12, 0x08]);
+        assert_eq!(assemble(":org 0x10000"), Err(String::from("Line 1: Address 65536 is outside memory.")));
+        Ok(())
+    }
+
+    #[test]
+    fn next_test() -> Result<(), String> {
+        // Labels the byte of v1 := 0, before and after it is defined.
+        let rom = assemble("
+            : main
+              i := slot
+              :next slot v1 := 0
+              v0 := 7
+              i := slot
+              save v0")?;
+        assert_eq!(rom, [0x12, 0x02, 0xA2, 0x05, 0x61, 0x00, 0x60, 0x07, 0xA2, 0x05,
+                         0xF0, 0x55]);
+        Ok(())
+    }
+
+    #[test]
+    fn conditional_test() -> Result<(), String> {
+        let rom = assemble("
+            : main
+              if v1 < 5 then v0 := 1
+              if v1 > v2 then v0 := 2
+              if v1 >= v2 then v0 := 3
+              if v1 key then v0 := 4
+              if v1 == v2 begin
+                v0 := 5
+              end
+              if v0 != 1 begin
+                if v1 == 2 begin
+                  v2 := 3
+                end
+              end")?;
+        assert_eq!(rom[2..], [0x6F, 0x05, 0x8F, 0x17, 0x4F, 0x00, 0x60, 0x01,
+                              0x8F, 0x20, 0x8F, 0x15, 0x4F, 0x00, 0x60, 0x02,
+                              0x8F, 0x10, 0x8F, 0x25, 0x4F, 0x01, 0x60, 0x03,
+                              0xE1, 0xA1, 0x60, 0x04,
+                              0x51, 0x20, 0x12, 0x24, 0x60, 0x05,
+                              0x40, 0x01, 0x12, 0x2E, 0x31, 0x02, 0x12, 0x2E, 0x62, 0x03]);
+        assert_eq!(assemble("else"), Err(String::from("Line 1: else without if ... begin.")));
+        assert!(assemble("if v0 == 1 begin\n  v1 := 2").is_err());
+        assert!(assemble("if v0 == 1 v1 := 2").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn forward_reference_test() -> Result<(), String> {
+        let rom = assemble("
+            : main
+              jump later
+              later2
+              i := table
+              i := long table
+              :unpack long table
+              :call later
+            : later
+              ;
+            : later2
+              return
+            : table
+              1 2")?;
+        assert_eq!(rom, [0x12, 0x02, 0x12, 0x12, 0x22, 0x14, 0xA2, 0x16, 0xF0, 0x00,
+                         0x02, 0x16, 0x60, 0x02, 0x61, 0x16, 0x22, 0x12, 0x00, 0xEE,
+                         0x00, 0xEE, 0x01, 0x02]);
+        Ok(())
+    }
+
+    #[test]
+    fn error_test() {
+        assert_eq!(assemble(": main\n  jump nowhere"), Err(String::from("Line 2: Undefined name nowhere.")));
+        assert_eq!(assemble("v0 := 300"), Err(String::from("Line 1: 300 does not fit in a byte.")));
+        assert_eq!(assemble("loop"), Err(String::from("Line 1: loop without again.")));
+        assert_eq!(assemble(": a\n: a"), Err(String::from("Line 2: Label a is defined twice.")));
+        assert!(assemble("v0 +").is_err());
+        assert!(assemble(":stringmode").is_err());
+    }
+}
+
+#[cfg(test)]
+#[path = "lib.rs"]
+mod cartridge_tests {
+    use cartridge::Cartridge;
+    use gif;
+    use options::Options;
+    use palette::Palette;
+    use std::borrow::Cow;
+    use std::env;
+    use std::fs;
+
+    /// Return a cartridge carrying `bytes`, over frames of 32x4 pixels
+    /// whose colour indices have label bits above the data.
+    fn encode(bytes: &[u8]) -> Vec<u8> {
+        let pixels: Vec<u8> = bytes.iter().flat_map(|b| [0x30 | b >> 4, 0x20 | b & 0xF]).collect();
+        let mut result = Vec::new();
+        {
+            let mut encoder = gif::Encoder::new(&mut result, 32, 4, &[0; 64 * 3]).unwrap();
+            for chunk in pixels.chunks(128) {
+                let mut buffer = chunk.to_vec();
+                buffer.resize(128, 0);
+                let frame = gif::Frame { width: 32, height: 4, buffer: Cow::Owned(buffer),
+                                         ..gif::Frame::default() };
+                encoder.write_frame(&frame).unwrap();
+            }
+        }
+        result
+    }
+
+    /// Return a cartridge carrying `json`.
+    fn cartridge(json: &str) -> Vec<u8> {
+        let mut bytes = (json.len() as u32).to_be_bytes().to_vec();
+        bytes.extend_from_slice(json.as_bytes());
+        encode(&bytes)
+    }
+
+    const JSON: &str = r##"{"program": ": main\n  v0 := 1\n",
+        "options": {"tickrate": 20, "vBlankQuirks": true, "shiftQuirks": false,
+                    "loadStoreQuirks": true, "jumpQuirks": true,
+                    "fillColor": "#FFCC00", "backgroundColor": "#996600",
+                    "touchInputMode": "none", "maxSize": 3584, "list": [1, -2.5e0, null]}}"##;
+
+    #[test]
+    fn decode_test() -> Result<(), String> {
+        let bytes = cartridge(JSON);
+        assert!(Cartridge::is_cartridge(&bytes));
+        let cartridge = Cartridge::decode(&bytes)?;
+        assert_eq!(cartridge.source, ": main\n  v0 := 1\n");
+        assert_eq!(cartridge.tickrate, Some(20));
+        assert_eq!(cartridge.display_wait, Some(true));
+        assert_eq!((cartridge.shift, cartridge.increment_i), (Some(false), Some(false)));
+        assert_eq!((cartridge.jump, cartridge.clip), (Some(true), None));
+        let palette = cartridge.palette.clone().unwrap();
+        assert_eq!(palette.fill, [0xFF, 0xCC, 0x00]);
+        assert_eq!(palette.background, [0x99, 0x66, 0x00]);
+        assert_eq!(palette.fill2, Palette::default().fill2);
+        assert_eq!(cartridge.rom()?, [0x12, 0x02, 0x60, 0x01]);
+
+        let bare = Cartridge::decode(&self::cartridge(r#"{"program": "clear"}"#))?;
+        assert_eq!((bare.tickrate, bare.display_wait, bare.palette), (None, None, None));
+        assert!(!Cartridge::is_cartridge(&[0x00, 0xE0]));
+        Ok(())
+    }
+
+    #[test]
+    fn bad_cartridge_test() {
+        assert!(Cartridge::decode(b"GIF89a").is_err());
+        assert_eq!(Cartridge::decode(&cartridge(r#"{"options": {}}"#)),
+                   Err(String::from("The cartridge has no program.")));
+        assert!(Cartridge::decode(&cartridge(r#"{"program": "#)).is_err());
+        // The length says there is more than the frames hold.
+        assert_eq!(Cartridge::decode(&encode(&[0x00, 0x00, 0x03, 0xE8, b'{'])),
+                   Err(String::from("The cartridge is cut short.")));
+    }
+
+    #[test]
+    fn options_test() -> Result<(), String> {
+        let path = env::temp_dir().join(format!("chip8-cartridge-{}.gif", std::process::id()));
+        fs::write(&path, cartridge(JSON)).unwrap();
+        let name = path.to_string_lossy().into_owned();
+        let options = Options::parse(vec![String::from("--palette"), String::from("amber"),
+                                          name.clone()].into_iter())?;
+        let applied = options.with_cartridge()?;
+        assert_eq!(applied.speed, 1200);
+        assert!(applied.quirks.display_wait);
+        assert!(applied.quirks.jump && !applied.quirks.increment_i);
+        // The palette chosen on the command line wins over the cartridge's.
+        assert_eq!(applied.palette()?.name, "amber");
+        let mut unchosen = options.clone();
+        unchosen.palette = None;
+        assert_eq!(unchosen.with_cartridge()?.palette()?.fill, [0xFF, 0xCC, 0x00]);
+        assert_eq!(options.source()?.read(&options.machine)?, [0x12, 0x02, 0x60, 0x01]);
+
+        // Raw ROMs change nothing.
+        fs::write(&path, [0x00, 0xE0]).unwrap();
+        assert_eq!(options.with_cartridge()?, options);
+        fs::remove_file(&path).unwrap();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[path = "lib.rs"]
+mod source_tests {
+    use launcher::{scan, scan_archive};
+    use machine::Machine;
+    use source::{Format, RomSource, zip_entries};
+    use std::env;
+    use std::fs;
+    use std::io::Write;
+    use std::path::{Path, PathBuf};
+    use zip::ZipWriter;
+    use zip::write::FileOptions;
+
+    /// Write a ZIP archive of `files` to `path`.
+    fn write_zip(path: &Path, files: &[(&str, &[u8])]) {
+        let mut zip = ZipWriter::new(fs::File::create(path).unwrap());
+        for (name, bytes) in files {
+            zip.start_file(*name, FileOptions::default()).unwrap();
+            zip.write_all(bytes).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    /// Return an empty directory for the test.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("chip8-source-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn detect_test() {
+        let (rom, text) = (Path::new("rom"), Path::new("listing.TXT"));
+        assert_eq!(Format::detect(&[0x00, 0xE0, 0x12, 0x00], rom), Format::Binary);
+        assert_eq!(Format::detect(b"GIF89a", rom), Format::Cartridge);
+        assert_eq!(Format::detect(b":0202000000E01C\n:00000001FF\n", rom), Format::IntelHex);
+        assert_eq!(Format::detect(b"00E0 1200\n", text), Format::HexText);
+        assert_eq!(Format::detect(b"0x00 0xE0", rom), Format::HexText);
+        // Text that is not hex, and an odd digit.
+        assert_eq!(Format::detect(b"hello", text), Format::Binary);
+        assert_eq!(Format::detect(b"00E 0", text), Format::Binary);
+        assert_eq!(Format::detect(b"  \n", text), Format::Binary);
+        // Binaries whose bytes all happen to be hex digits or spaces.
+        assert_eq!(Format::detect(b"00E0 1200\n", rom), Format::Binary);
+        assert_eq!(Format::detect(b"AB 1", Path::new("game.ch8")), Format::Binary);
+    }
+
+    #[test]
+    fn hex_text_test() -> Result<(), String> {
+        let machine = Machine::default();
+        assert_eq!(Format::HexText.decode(b"00E0 a22A\n0x12 0x00".to_vec(), &machine)?,
+                   [0x00, 0xE0, 0xA2, 0x2A, 0x12, 0x00]);
+        Ok(())
+    }
+
+    #[test]
+    fn intel_hex_test() -> Result<(), String> {
+        let machine = Machine::default();
+        let decode = |text: &str| Format::IntelHex.decode(text.as_bytes().to_vec(), &machine);
+        assert_eq!(decode(":00000001FF\n")?.len(), 0);
+        // Records at 0x200 and 0x206, the gap cleared.
+        assert_eq!(decode(":0202000000E01C\n:020206001200E4\n:00000001FF")?,
+                   [0x00, 0xE0, 0x00, 0x00, 0x00, 0x00, 0x12, 0x00]);
+        // An extended segment address of 0x20 puts offset 0 at 0x200.
+        assert_eq!(decode(":020000020020DC\n:01000000609F")?, [0x60]);
+        assert_eq!(decode(":0202000000E01B"),
+                   Err(String::from("Line 1: The checksum is wrong.")));
+        assert_eq!(decode(":0201000000E01D"),
+                   Err(String::from("Line 1: Address 0x100 is outside the program.")));
+        assert_eq!(decode(":0302000000E01C"),
+                   Err(String::from("Line 1: The record length is wrong.")));
+        Ok(())
+    }
+
+    #[test]
+    fn zip_test() -> Result<(), String> {
+        let machine = Machine::default();
+        let dir = test_dir("zip");
+        let single = dir.join("single.zip");
+        write_zip(&single, &[("PONG", &[0x00, 0xE0]), ("README.txt", b"Pong")]);
+        let source = RomSource::open(&single, None)?;
+        assert_eq!(source, RomSource::Zip { archive: single.clone(), entry: String::from("PONG") });
+        assert_eq!(source.read(&machine)?, [0x00, 0xE0]);
+        assert_eq!(source.name(), Path::new("PONG"));
+        assert_eq!(source.to_string(), format!("{} (PONG)", single.display()));
+
+        let pack = dir.join("pack.zip");
+        write_zip(&pack, &[("games/tank.ch8", b"1200\n"), ("games/", b""),
+                           ("__MACOSX/games/._tank.ch8", b""), ("blitz.ch8", &[0x12, 0x00])]);
+        assert_eq!(zip_entries(&pack)?, ["blitz.ch8", "games/tank.ch8"]);
+        assert_eq!(RomSource::open(&pack, None),
+                   Err(format!("{} has several ROMs, choose one of blitz.ch8, games/tank.ch8.",
+                               pack.display())));
+        // Entries are decoded as files are.
+        assert_eq!(RomSource::open(&pack, Some("games/tank.ch8"))?.read(&machine)?,
+                   [0x12, 0x00]);
+        assert!(RomSource::open(&pack, Some("pong.ch8")).is_err());
+
+        let rom = dir.join("rom.ch8");
+        fs::write(&rom, [0x00, 0xE0]).unwrap();
+        assert_eq!(RomSource::open(&rom, None)?, RomSource::File(rom.clone()));
+        assert!(RomSource::open(&rom, Some("PONG")).is_err());
+
+        // The launcher lists the ROMs in archives, but for those that cannot
+        // be decoded.
+        let mixed = dir.join("mixed.zip");
+        write_zip(&mixed, &[("ok.ch8", &[0x00, 0xE0]), ("broken.hex", b":0102")]);
+        let titles: Vec<String> = scan_archive(&mixed)?.into_iter().map(|e| e.title).collect();
+        assert_eq!(titles, ["ok"]);
+        let titles: Vec<String> = scan(&dir)?.into_iter().map(|e| e.title).collect();
+        assert_eq!(titles, ["blitz", "ok", "PONG", "rom", "tank"]);
+        fs::remove_dir_all(&dir).unwrap();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[path = "lib.rs"]
+mod patch_tests {
+    use patch::{apply, apply_files, crc32};
+    use std::env;
+    use std::fs;
+
+    /// Encode a BPS number.
+    fn number(mut n: usize, out: &mut Vec<u8>) {
+        loop {
+            let byte = (n & 0x7F) as u8;
+            n >>= 7;
+            if n == 0 {
+                out.push(byte | 0x80);
+                return;
+            }
+            out.push(byte);
+            n -= 1;
+        }
+    }
+
+    /// Return a BPS patch of `actions` turning `source` into `target`.
+    fn bps(source: &[u8], target: &[u8], actions: &[u8]) -> Vec<u8> {
+        let mut result = b"BPS1".to_vec();
+        number(source.len(), &mut result);
+        number(target.len(), &mut result);
+        number(0, &mut result);
+        result.extend_from_slice(actions);
+        result.extend_from_slice(&crc32(source).to_le_bytes());
+        result.extend_from_slice(&crc32(target).to_le_bytes());
+        let checksum = crc32(&result);
+        result.extend_from_slice(&checksum.to_le_bytes());
+        result
+    }
+
+    #[test]
+    fn crc32_test() {
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn ips_test() -> Result<(), String> {
+        let rom = [0x00, 0xE0, 0x12, 0x00];
+        // Two bytes at 2, then three 0xFF at 5, growing the ROM.
+        let patch = b"PATCH\x00\x00\x02\x00\x02\x60\x01\x00\x00\x05\x00\x00\x00\x03\xFFEOF";
+        assert_eq!(apply(&rom, patch)?, [0x00, 0xE0, 0x60, 0x01, 0x00, 0xFF, 0xFF, 0xFF]);
+        // Cut to 2 bytes after the records.
+        assert_eq!(apply(&rom, b"PATCHEOF\x00\x00\x02")?, [0x00, 0xE0]);
+        assert_eq!(apply(&rom, b"PATCH\x00\x00\x02\x00\x02\x60"),
+                   Err(String::from("The patch is cut short.")));
+        assert_eq!(apply(&rom, b"GIF89a"), Err(String::from("Not an IPS or BPS patch.")));
+        Ok(())
+    }
+
+    #[test]
+    fn bps_test() -> Result<(), String> {
+        let source = [0x00, 0xE0, 0xA2, 0x2A, 0x12, 0x00];
+        let target = [0x00, 0xE0, 0x60, 0x05, 0x60, 0x05, 0x60, 0x05, 0x12, 0x00];
+        let mut actions = Vec::new();
+        // Read 2 bytes of the source, 2 from the patch, copy those twice
+        // more from the target, then copy the source's last 2.
+        number((2 - 1) << 2, &mut actions);
+        number((2 - 1) << 2 | 1, &mut actions);
+        actions.extend_from_slice(&[0x60, 0x05]);
+        number((4 - 1) << 2 | 3, &mut actions);
+        number(2 << 1, &mut actions);
+        number((2 - 1) << 2 | 2, &mut actions);
+        number(4 << 1, &mut actions);
+        let patch = bps(&source, &target, &actions);
+        assert_eq!(apply(&source, &patch)?, target);
+
+        assert_eq!(apply(&target, &patch), Err(String::from(
+            "The ROM's checksum is wrong, the patch is for another ROM.")));
+        let mut damaged = patch.clone();
+        damaged[8] ^= 1;
+        assert_eq!(apply(&source, &damaged), Err(String::from(
+            "The patch's checksum is wrong, it is damaged.")));
+        // The checksums match, but the result does not.
+        let wrong = bps(&source, &[0x00, 0xE0, 0xA2, 0x2A, 0x12, 0x01], &actions[..1]);
+        assert!(apply(&source, &wrong).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn apply_files_test() -> Result<(), String> {
+        let dir = env::temp_dir();
+        let first = dir.join(format!("chip8-patch-{}-1.ips", std::process::id()));
+        let second = dir.join(format!("chip8-patch-{}-2.ips", std::process::id()));
+        fs::write(&first, b"PATCH\x00\x00\x00\x00\x01\x11EOF").unwrap();
+        fs::write(&second, b"PATCH\x00\x00\x00\x00\x01\x22\x00\x00\x01\x00\x01\x33EOF").unwrap();
+        let paths = [first.to_string_lossy().into_owned(), second.to_string_lossy().into_owned()];
+        // Applied in order, the second overwriting the first.
+        assert_eq!(apply_files(vec![0x00, 0xE0], &paths)?, [0x22, 0x33]);
+        assert_eq!(apply_files(vec![0x00, 0xE0], &[])?, [0x00, 0xE0]);
+        fs::remove_file(&first).unwrap();
+        let error = apply_files(vec![0x00, 0xE0], &paths).unwrap_err();
+        assert!(error.starts_with(&paths[0]));
+        fs::remove_file(&second).unwrap();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[path = "lib.rs"]
+mod machine_tests {
+    use get_program;
+    use machine::Machine;
+    use source::RomSource;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn presets_test() -> Result<(), String> {
+        assert_eq!(Machine::parse("chip8")?, Machine::default());
+        assert_eq!(Machine::parse("eti-660")?.load_address, 0x600);
+        assert_eq!(Machine::parse("xo-chip")?.memory_size, 0x10000);
+        assert_eq!(Machine::parse("vip")?.stack_depth, 12);
+        assert_eq!(Machine::parse("vip")?.stack_address, Some(0xEA0));
+        assert_eq!(Machine::parse("schip")?.font.name, "schip");
+        assert!(Machine::parse("apple").is_err());
+        for machine in [Machine::chip8(), Machine::vip(), Machine::eti660(), Machine::schip(),
+                        Machine::xo_chip()].iter() {
+            machine.validate()?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn validate_test() {
+        let machine = Machine { memory_size: 0x800, ..Machine::chip8() };
+        assert_eq!(machine.validate(),
+                   Err(String::from("Memory size 2048 is not between 4096 and 65536.")));
+        let machine = Machine { font_address: 0x1C0, ..Machine::chip8() };
+        assert_eq!(machine.validate(),
+                   Err(String::from("The font at 0x1C0 overlaps the ROM at 0x200.")));
+        // Above the ROM, the font ends the space it has.
+        let machine = Machine { font_address: 0xF00, ..Machine::chip8() };
+        assert_eq!(machine.validate(), Ok(()));
+        assert_eq!(machine.rom_space(), 0xD00);
+        let machine = Machine { font_address: 0xFD0, ..Machine::chip8() };
+        assert_eq!(machine.validate(),
+                   Err(String::from("The font at 0xFD0 does not fit in memory.")));
+        let machine = Machine { font_address: 0xF00, ..Machine::vip() };
+        assert_eq!(machine.validate(), Ok(()));
+        assert_eq!(machine.rom_space(), 0xCA0);
+        let machine = Machine { font_address: 0xE80, ..Machine::vip() };
+        assert_eq!(machine.validate(),
+                   Err(String::from("The font at 0xE80 overlaps the stack at 0xEA0.")));
+        let machine = Machine { stack_address: Some(0x1F0), ..Machine::vip() };
+        assert_eq!(machine.validate(),
+                   Err(String::from("The stack at 0x1F0 overlaps the ROM at 0x200.")));
+        let machine = Machine { load_address: 0x1000, ..Machine::chip8() };
+        assert_eq!(machine.validate(),
+                   Err(String::from("Load address 0x1000 is outside the 4096 bytes of memory.")));
+        let machine = Machine { stack_depth: 0, ..Machine::chip8() };
+        assert_eq!(machine.validate(),
+                   Err(String::from("Stack depth 0 is not between 1 and 255.")));
+        let machine = Machine { stack_address: Some(0xFF0), ..Machine::vip() };
+        assert_eq!(machine.validate(),
+                   Err(String::from("The stack at 0xFF0 does not fit in memory.")));
+    }
+
+    #[test]
+    fn addresses_test() {
+        let machine = Machine::eti660();
+        assert_eq!(machine.instruction_pos(0x604), 2);
+        assert_eq!(machine.address(2), 0x604);
+        assert_eq!(machine.rom_space(), 0xA00);
+        assert!(machine.check_rom(0xA00).is_ok());
+        assert_eq!(machine.check_rom(0xA01),
+                   Err(String::from("The ROM is 2561 bytes, only 2560 fit from 0x600.")));
+    }
+
+    #[test]
+    fn rom_size_test() -> Result<(), String> {
+        let path = env::temp_dir().join(format!("chip8-machine-{}.ch8", std::process::id()));
+        fs::write(&path, vec![0x00; 0xE00]).unwrap();
+        let source = RomSource::File(path.clone());
+        assert!(get_program(&source, &[], &Machine::chip8()).is_ok());
+        // Too big for the ETI-660, but not for XO-CHIP.
+        assert!(get_program(&source, &[], &Machine::eti660()).is_err());
+        // The VIP's stack and variables take the top of memory.
+        assert_eq!(Machine::vip().rom_space(), 0xCA0);
+        assert!(get_program(&source, &[], &Machine::vip()).is_err());
+        assert!(get_program(&source, &[], &Machine { load_address: 0x600, ..Machine::xo_chip() })
+                .is_ok());
+        fs::remove_file(&path).unwrap();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[path = "lib.rs"]
+mod font_tests {
+    use FONTSET;
+    use font::Font;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn builtins_test() {
+        let names: Vec<String> = Font::builtins().into_iter().map(|f| f.name).collect();
+        assert_eq!(names, ["modern", "vip", "dream-6800", "eti-660", "schip"]);
+        assert_eq!(Font::default().small, FONTSET);
+        for font in Font::builtins() {
+            // Every glyph is 4 pixels wide at most, 8 for the big ones.
+            assert!(font.small.iter().all(|row| row & 0x0F == 0));
+            assert!(font.big.len() % 10 == 0);
+        }
+        // VIP 4 has its stem in the middle.
+        let vip = Font::builtin("vip").unwrap();
+        assert_eq!(&vip.small[4 * 5..5 * 5], &[0xA0, 0xA0, 0xF0, 0x20, 0x20]);
+        assert_eq!(Font::builtin("schip").unwrap().size(), 80 + 100);
+        assert!(Font::builtin("octo").is_none());
+    }
+
+    #[test]
+    fn parse_test() -> Result<(), String> {
+        let mut bytes = vec![0xF0; 80];
+        assert!(Font::parse("custom", &bytes)?.big.is_empty());
+        bytes.extend_from_slice(&[0xFF; 20]);
+        let font = Font::parse("custom", &bytes)?;
+        assert_eq!((font.name.as_str(), font.big.len()), ("custom", 20));
+        assert_eq!(font.bytes(), bytes);
+        assert!(Font::parse("short", &[0xF0; 79]).is_err());
+        assert!(Font::parse("odd", &[0xF0; 85]).is_err());
+        assert!(Font::parse("long", &[0xF0; 80 + 170]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn from_arg_test() -> Result<(), String> {
+        assert_eq!(Font::from_arg("eti-660")?.name, "eti-660");
+        let path = env::temp_dir().join(format!("chip8-font-{}.bin", std::process::id()));
+        fs::write(&path, [0x60; 80]).unwrap();
+        let font = Font::from_arg(&path.to_string_lossy())?;
+        assert_eq!(font.name, format!("chip8-font-{}", std::process::id()));
+        assert_eq!(font.small, [0x60; 80]);
+        fs::remove_file(&path).unwrap();
+        assert!(Font::from_arg("missing").unwrap_err().contains("modern, vip"));
+        Ok(())
+    }
+}
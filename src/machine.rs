@@ -0,0 +1,211 @@
+//! Chip 8 machine configuration.
+//! (C) Ryan Jeffrey <ryan@ryanmj.xyz>, 2022
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or (at
+// your option) any later version.
+
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use font::Font;
+
+/// Smallest memory, enough for every 12 bit address.
+const MIN_MEMORY_SIZE: usize = 0x1000;
+/// Largest memory, that of XO-CHIP.
+const MAX_MEMORY_SIZE: usize = 0x10000;
+/// Deepest stack, as many levels as the stack pointer counts.
+const MAX_STACK_DEPTH: usize = 0xFF;
+/// Address of the COSMAC VIP's stack, below its variables and display.
+pub const VIP_STACK_ADDRESS: u16 = 0xEA0;
+
+/// Memory layout of the computer a ROM was written for: where the ROM and
+/// the font are loaded, how much memory there is, the font's glyphs and the
+/// call stack.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Machine {
+    /// Address the ROM is loaded at and run from.
+    pub load_address: u16,
+    /// Address of the font sprites `LdSp` points I at.
+    pub font_address: u16,
+    /// Bytes of memory.
+    pub memory_size: usize,
+    /// Glyphs of the font.
+    pub font: Font,
+    /// Number of return addresses the stack holds.
+    pub stack_depth: usize,
+    /// Address of the stack in memory, where ROMs can read and change it,
+    /// None to keep it apart. Return addresses are stored big endian from
+    /// the bottom of the stack up.
+    pub stack_address: Option<u16>,
+}
+
+impl Machine {
+    /// Return the layout of the COSMAC VIP and most interpreters since.
+    pub fn chip8() -> Self {
+        Self {
+            load_address: 0x200,
+            font_address: 0,
+            memory_size: 0x1000,
+            font: Font::default(),
+            stack_depth: 16,
+            stack_address: None,
+        }
+    }
+
+    /// Return the COSMAC VIP as it was: its font and 12 levels of stack at
+    /// 0xEA0 in memory.
+    pub fn vip() -> Self {
+        Self {
+            font: Font::builtin("vip").unwrap_or_default(),
+            stack_depth: 12,
+            stack_address: Some(VIP_STACK_ADDRESS),
+            ..Self::chip8()
+        }
+    }
+
+    /// Return the layout of the ETI-660, whose ROMs start at 0x600, with its
+    /// font.
+    pub fn eti660() -> Self {
+        Self {
+            load_address: 0x600,
+            font: Font::builtin("eti-660").unwrap_or_default(),
+            ..Self::chip8()
+        }
+    }
+
+    /// Return SUPER-CHIP's layout, with its font and 16 levels of stack.
+    pub fn schip() -> Self {
+        Self {
+            font: Font::builtin("schip").unwrap_or_default(),
+            ..Self::chip8()
+        }
+    }
+
+    /// Return the layout of XO-CHIP, with 64 KB of memory and the SUPER-CHIP
+    /// font.
+    pub fn xo_chip() -> Self {
+        Self {
+            memory_size: MAX_MEMORY_SIZE,
+            font: Font::builtin("schip").unwrap_or_default(),
+            ..Self::chip8()
+        }
+    }
+
+    /// Parse a preset name, `chip8`, `vip`, `eti-660`, `schip` or
+    /// `xo-chip`. Return string on error.
+    /// # Arguments
+    /// * `name` Preset name.
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "chip8" => Ok(Self::chip8()),
+            "vip" => Ok(Self::vip()),
+            "eti-660" => Ok(Self::eti660()),
+            "schip" => Ok(Self::schip()),
+            "xo-chip" => Ok(Self::xo_chip()),
+            _ => Err(format!("Unknown machine {}, expected chip8, vip, eti-660, schip or xo-chip.",
+                             name)),
+        }
+    }
+
+    /// Check that the memory size and stack depth are supported and that the
+    /// font, a ROM and the stack fit in memory without overlapping. The ROM
+    /// takes from the load address up to the font or stack above it. Return
+    /// string if not, naming the parts that collide.
+    pub fn validate(&self) -> Result<(), String> {
+        if !(MIN_MEMORY_SIZE..=MAX_MEMORY_SIZE).contains(&self.memory_size) {
+            return Err(format!("Memory size {} is not between {} and {}.", self.memory_size,
+                               MIN_MEMORY_SIZE, MAX_MEMORY_SIZE));
+        }
+        let load = self.load_address as usize;
+        if load >= self.memory_size {
+            return Err(format!("Load address 0x{:X} is outside the {} bytes of memory.",
+                               self.load_address, self.memory_size));
+        }
+        if !(1..=MAX_STACK_DEPTH).contains(&self.stack_depth) {
+            return Err(format!("Stack depth {} is not between 1 and {}.", self.stack_depth,
+                               MAX_STACK_DEPTH));
+        }
+        let reserved = self.reserved();
+        for &(name, start, end) in &reserved {
+            if end > self.memory_size {
+                return Err(format!("The {} at 0x{:X} does not fit in memory.", name, start));
+            }
+            if (start..end).contains(&load) {
+                return Err(format!("The {} at 0x{:X} overlaps the ROM at 0x{:X}.", name, start,
+                                   load));
+            }
+        }
+        if let [(font, font_start, font_end), (stack, stack_start, stack_end)] = reserved[..] {
+            if font_start < stack_end && stack_start < font_end {
+                return Err(format!("The {} at 0x{:X} overlaps the {} at 0x{:X}.", font,
+                                   font_start, stack, stack_start));
+            }
+        }
+        Ok(())
+    }
+
+    /// Return the parts of memory the font and the stack, if it is in
+    /// memory, take: their names and the addresses they start and end at.
+    fn reserved(&self) -> Vec<(&'static str, usize, usize)> {
+        let font = self.font_address as usize;
+        let mut result = vec![("font", font, font + self.font.size())];
+        if let Some(address) = self.stack_address {
+            result.push(("stack", address as usize, address as usize + 2 * self.stack_depth));
+        }
+        result
+    }
+
+    /// Return the address of the big font sprites `LdHf` points I at, right
+    /// after the small ones.
+    pub fn big_font_address(&self) -> u16 {
+        self.font_address.wrapping_add(self.font.small.len() as u16)
+    }
+
+    /// Return the bytes available to a ROM, from the load address to the end
+    /// of memory or to the font or stack in memory above it.
+    pub fn rom_space(&self) -> usize {
+        let load = self.load_address as usize;
+        let end = self.reserved().into_iter()
+            .map(|(_, start, _)| start)
+            .filter(|&start| start >= load)
+            .fold(self.memory_size, usize::min);
+        end.saturating_sub(load)
+    }
+
+    /// Check that a ROM fits in memory. Return string if it does not.
+    /// # Arguments
+    /// * `len` Size of the ROM in bytes.
+    pub fn check_rom(&self, len: usize) -> Result<(), String> {
+        if len > self.rom_space() {
+            return Err(format!("The ROM is {} bytes, only {} fit from 0x{:X}.", len,
+                               self.rom_space(), self.load_address));
+        }
+        Ok(())
+    }
+
+    /// Return the instruction position of a memory address.
+    /// # Arguments
+    /// * `address` Memory address.
+    pub fn instruction_pos(&self, address: u16) -> u16 {
+        address.wrapping_sub(self.load_address) / 2
+    }
+
+    /// Return the memory address of an instruction position.
+    /// # Arguments
+    /// * `pos` Instruction position.
+    pub fn address(&self, pos: u16) -> u16 {
+        pos.wrapping_mul(2).wrapping_add(self.load_address)
+    }
+}
+
+impl Default for Machine {
+    fn default() -> Self {
+        Self::chip8()
+    }
+}
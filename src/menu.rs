@@ -0,0 +1,100 @@
+//! Chip 8 SDL ROM launcher.
+//! (C) Ryan Jeffrey <ryan@ryanmj.xyz>, 2022
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or (at
+// your option) any later version.
+
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use chip8::launcher::Menu;
+use input::Input;
+use renderer::Renderer;
+use sdl2::EventPump;
+use sdl2::controller::Button;
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+use toggle_fullscreen;
+
+/// Entries Page Up and Page Down move by.
+const PAGE: isize = 10;
+
+/// Text at the bottom of the launcher.
+const FOOTER: &str = "Enter: play  Esc: quit  Esc in a game: back here";
+
+/// Show ROMs and let the player choose one with the keyboard or a
+/// controller, or drop one on the window. Return the path of the chosen ROM
+/// and its name in its ZIP archive, None if the window was closed. Return
+/// string on error.
+/// # Arguments
+/// * `menu` The ROMs to choose from, with the one to select first.
+/// * `header` Text at the top, where the ROMs are.
+/// * `error` Why the last ROM could not be run, shown at the bottom until a
+///   key is pressed.
+/// * `canvas` SDL canvas of the window.
+/// * `renderer` Draws the menu in the palette's colours.
+/// * `event_pump` SDL event pump.
+/// * `input` Keyboard and controller state, kept up to date with controllers
+///   being plugged in.
+pub fn choose(mut menu: Menu, header: &str, error: Option<&str>, canvas: &mut Canvas<Window>,
+              renderer: &Renderer, event_pump: &mut EventPump, input: &mut Input)
+              -> Result<Option<(String, Option<String>)>, String> {
+    let mut footer = error.unwrap_or(FOOTER);
+
+    loop {
+        renderer.draw_menu(&mut menu, header, footer, canvas)?;
+        // Nothing changes until there is an event.
+        let first = match event_pump.wait_event_timeout(100) {
+            Some(event) => event,
+            None => continue,
+        };
+        let events: Vec<Event> = Some(first).into_iter().chain(event_pump.poll_iter()).collect();
+        for event in events {
+            if let Event::KeyDown { .. } | Event::ControllerButtonDown { .. } = event {
+                footer = FOOTER;
+            }
+            match event {
+                Event::Quit {..} |
+                Event::KeyDown { keycode: Some(Keycode::Escape), .. } => return Ok(None),
+                Event::KeyDown { keycode: Some(Keycode::Up), .. } |
+                Event::ControllerButtonDown { button: Button::DPadUp, .. } => menu.move_by(-1),
+                Event::KeyDown { keycode: Some(Keycode::Down), .. } |
+                Event::ControllerButtonDown { button: Button::DPadDown, .. } => menu.move_by(1),
+                Event::KeyDown { keycode: Some(Keycode::PageUp), .. } |
+                Event::ControllerButtonDown { button: Button::LeftShoulder, .. } => {
+                    menu.move_by(-PAGE);
+                },
+                Event::KeyDown { keycode: Some(Keycode::PageDown), .. } |
+                Event::ControllerButtonDown { button: Button::RightShoulder, .. } => {
+                    menu.move_by(PAGE);
+                },
+                Event::KeyDown { keycode: Some(Keycode::Home), .. } => {
+                    menu.move_by(-(menu.entries().len() as isize));
+                },
+                Event::KeyDown { keycode: Some(Keycode::End), .. } => {
+                    menu.move_by(menu.entries().len() as isize);
+                },
+                Event::KeyDown { keycode: Some(Keycode::F11), .. } => toggle_fullscreen(canvas)?,
+                Event::KeyDown { keycode: Some(Keycode::Return), .. } |
+                Event::KeyDown { keycode: Some(Keycode::KpEnter), .. } |
+                Event::ControllerButtonDown { button: Button::A, .. } |
+                Event::ControllerButtonDown { button: Button::Start, .. } => {
+                    if let Some(entry) = menu.selected() {
+                        let path = entry.path.to_string_lossy().into_owned();
+                        return Ok(Some((path, entry.entry.clone())));
+                    }
+                },
+                Event::DropFile { filename, .. } => return Ok(Some((filename, None))),
+                _ => input.handle(&event),
+            }
+        }
+    }
+}
@@ -0,0 +1,906 @@
+//! Chip 8 Octo assembler.
+//! (C) Ryan Jeffrey <ryan@ryanmj.xyz>, 2022
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or (at
+// your option) any later version.
+
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+
+/// Address programs are assembled at.
+const START: usize = 0x200;
+/// Size of the XO-CHIP address space, the most a program can fill.
+const MEMORY_SIZE: usize = 0x10000;
+
+/// A word of the source and the line it is on.
+#[derive(Clone)]
+struct Token {
+    text: String,
+    line: usize,
+}
+
+/// How a label defined after it is used is written once it is known.
+#[derive(Copy, Clone)]
+enum Patch {
+    /// The low 12 bits of the instruction at the address.
+    Address,
+    /// The 16 bit word at the address, after `i := long`.
+    Long,
+    /// The `v0 :=` and `v1 :=` of `:unpack` at the address, with the nibble
+    /// put above the address, None for `:unpack long`.
+    Unpack(Option<u8>),
+}
+
+/// A use of a label defined later.
+struct Fixup {
+    name: String,
+    address: usize,
+    patch: Patch,
+    line: usize,
+}
+
+/// A macro: its argument names and the tokens it expands to.
+struct Macro {
+    args: Vec<String>,
+    body: Vec<Token>,
+    /// Times it was expanded, the value of `CALLS` in its body.
+    calls: usize,
+}
+
+/// Right hand side of a comparison.
+#[derive(Copy, Clone)]
+enum Operand {
+    Register(u8),
+    Byte(u8),
+}
+
+/// Condition of an `if` or `while`, with `<`, `>`, `<=` and `>=` turned
+/// into tests of vf.
+#[derive(Copy, Clone)]
+enum Test {
+    Eq(u8, Operand),
+    Ne(u8, Operand),
+    Key(u8),
+    NotKey(u8),
+}
+
+/// Split source into tokens: words separated by whitespace, with double
+/// quoted strings kept whole and `#` starting a comment.
+/// # Arguments
+/// * `source` Octo source.
+fn tokenize(source: &str) -> Vec<Token> {
+    let mut result = Vec::new();
+    for (n, line) in source.lines().enumerate() {
+        let mut chars = line.chars().peekable();
+        while let Some(&c) = chars.peek() {
+            if c.is_whitespace() {
+                chars.next();
+                continue;
+            }
+            if c == '#' {
+                break;
+            }
+            let mut text = String::new();
+            if c == '"' {
+                text.push(c);
+                chars.next();
+                for c in chars.by_ref() {
+                    text.push(c);
+                    if c == '"' {
+                        break;
+                    }
+                }
+            } else {
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() {
+                        break;
+                    }
+                    text.push(c);
+                    chars.next();
+                }
+            }
+            result.push(Token { text, line: n + 1 });
+        }
+    }
+    result
+}
+
+/// Parse a decimal, `0x` hexadecimal or `0b` binary number, with an
+/// optional minus sign.
+/// # Arguments
+/// * `text` The number.
+fn parse_number(text: &str) -> Option<f64> {
+    let (sign, digits) = match text.strip_prefix('-') {
+        Some(digits) => (-1.0, digits),
+        None => (1.0, text),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()? as f64
+    } else if let Some(binary) = digits.strip_prefix("0b") {
+        i64::from_str_radix(binary, 2).ok()? as f64
+    } else if digits.starts_with(|c: char| c.is_ascii_digit()) {
+        digits.parse().ok()?
+    } else {
+        return None;
+    };
+    Some(sign * value)
+}
+
+/// Apply a binary operator of `:calc`.
+/// # Arguments
+/// * `op` The operator.
+/// * `a` Left operand.
+/// * `b` Right operand.
+fn binary(op: &str, a: f64, b: f64) -> Option<f64> {
+    let (x, y) = (a as i64, b as i64);
+    Some(match op {
+        "+" => a + b,
+        "-" => a - b,
+        "*" => a * b,
+        "/" => a / b,
+        "%" => a % b,
+        "&" => (x & y) as f64,
+        "|" => (x | y) as f64,
+        "^" => (x ^ y) as f64,
+        "<<" => (x << (y & 63)) as f64,
+        ">>" => (x >> (y & 63)) as f64,
+        "pow" => a.powf(b),
+        "min" => a.min(b),
+        "max" => a.max(b),
+        "<" => (a < b) as i64 as f64,
+        ">" => (a > b) as i64 as f64,
+        "<=" => (a <= b) as i64 as f64,
+        ">=" => (a >= b) as i64 as f64,
+        "==" => (a == b) as i64 as f64,
+        "!=" => (a != b) as i64 as f64,
+        _ => return None,
+    })
+}
+
+/// Apply a unary operator of `:calc`, None if `op` is not one.
+/// # Arguments
+/// * `op` The operator.
+/// * `a` The operand.
+fn unary(op: &str, a: f64) -> Option<f64> {
+    Some(match op {
+        "-" => -a,
+        "~" => !(a as i64) as f64,
+        "!" => (a == 0.0) as i64 as f64,
+        "sin" => a.sin(),
+        "cos" => a.cos(),
+        "tan" => a.tan(),
+        "exp" => a.exp(),
+        "log" => a.ln(),
+        "abs" => a.abs(),
+        "sqrt" => a.sqrt(),
+        "sign" if a == 0.0 => 0.0,
+        "sign" => a.signum(),
+        "ceil" => a.ceil(),
+        "floor" => a.floor(),
+        _ => return None,
+    })
+}
+
+/// Return whether `op` is a unary operator of `:calc`.
+/// # Arguments
+/// * `op` The token.
+fn is_unary(op: &str) -> bool {
+    unary(op, 0.0).is_some()
+}
+
+/// Assembler state while going through the tokens once, filling in labels
+/// used before they are defined at the end.
+struct Assembler {
+    /// Tokens left, the next one last.
+    tokens: Vec<Token>,
+    /// Line of the last token read.
+    line: usize,
+    memory: Vec<u8>,
+    /// Address the next byte is written to.
+    here: usize,
+    /// Address after the last byte written.
+    end: usize,
+    labels: HashMap<String, usize>,
+    constants: HashMap<String, f64>,
+    aliases: HashMap<String, u8>,
+    macros: HashMap<String, Macro>,
+    fixups: Vec<Fixup>,
+    /// Start of every open `loop` and the jumps of its `while`s.
+    loops: Vec<(usize, Vec<usize>)>,
+    /// Jumps of every open `if ... begin` or `else`.
+    branches: Vec<usize>,
+}
+
+impl Assembler {
+    /// Create an assembler for Octo source.
+    /// # Arguments
+    /// * `source` Octo source.
+    fn new(source: &str) -> Self {
+        let mut tokens = tokenize(source);
+        tokens.reverse();
+        Self {
+            tokens,
+            line: 1,
+            memory: vec![0; MEMORY_SIZE],
+            here: START,
+            end: START,
+            labels: HashMap::new(),
+            constants: HashMap::new(),
+            aliases: HashMap::new(),
+            macros: HashMap::new(),
+            fixups: Vec::new(),
+            loops: Vec::new(),
+            branches: Vec::new(),
+        }
+    }
+
+    /// Return the next token. Return string at the end of the source.
+    fn next(&mut self) -> Result<String, String> {
+        let token = self.tokens.pop().ok_or("Unexpected end of the program.")?;
+        self.line = token.line;
+        Ok(token.text)
+    }
+
+    /// Return the next token without reading it.
+    fn peek(&self) -> Option<&str> {
+        self.tokens.last().map(|t| t.text.as_str())
+    }
+
+    /// Read the next token, which must be `text`. Return string otherwise.
+    /// # Arguments
+    /// * `text` The expected token.
+    fn expect(&mut self, text: &str) -> Result<(), String> {
+        match self.next()? {
+            ref t if t == text => Ok(()),
+            t => Err(format!("Expected {}, got {}.", text, t)),
+        }
+    }
+
+    /// Write a byte at the current address. Return string past the end of
+    /// memory.
+    /// # Arguments
+    /// * `byte` The byte.
+    fn emit(&mut self, byte: u8) -> Result<(), String> {
+        if self.here >= MEMORY_SIZE {
+            return Err(String::from("The program does not fit in memory."));
+        }
+        self.memory[self.here] = byte;
+        self.here += 1;
+        self.end = self.end.max(self.here);
+        Ok(())
+    }
+
+    /// Write an instruction at the current address. Return string past the
+    /// end of memory.
+    /// # Arguments
+    /// * `opcode` 16 bit opcode.
+    fn emit_op(&mut self, opcode: u16) -> Result<(), String> {
+        self.emit((opcode >> 8) as u8)?;
+        self.emit(opcode as u8)
+    }
+
+    /// Point the jump at `at` to `target`. Return string if `target` does not
+    /// fit in 12 bits.
+    /// # Arguments
+    /// * `at` Address of the jump.
+    /// * `target` Address to jump to.
+    fn patch_jump(&mut self, at: usize, target: usize) -> Result<(), String> {
+        if target > 0xFFF {
+            return Err(format!("Address 0x{:X} does not fit in 12 bits.", target));
+        }
+        self.memory[at] = 0x10 | (target >> 8) as u8;
+        self.memory[at + 1] = target as u8;
+        Ok(())
+    }
+
+    /// Return the register a token names, `v0` to `vf` or an alias.
+    /// # Arguments
+    /// * `text` The token.
+    fn parse_register(&self, text: &str) -> Option<u8> {
+        if let Some(&r) = self.aliases.get(text) {
+            return Some(r);
+        }
+        let mut chars = text.chars();
+        match (chars.next(), chars.next(), chars.next()) {
+            (Some('v') | Some('V'), Some(d), None) => d.to_digit(16).map(|d| d as u8),
+            _ => None,
+        }
+    }
+
+    /// Read a register. Return string if the next token is not one.
+    fn register(&mut self) -> Result<u8, String> {
+        let text = self.next()?;
+        self.parse_register(&text).ok_or_else(|| format!("Expected a register, got {}.", text))
+    }
+
+    /// Return the value of a number, constant, label defined so far or, for
+    /// `{`, a calc expression, None for other tokens. Return string on error.
+    /// # Arguments
+    /// * `text` The token, already read.
+    fn parse_value(&mut self, text: &str) -> Result<Option<f64>, String> {
+        if text == "{" {
+            return self.calc_block().map(Some);
+        }
+        Ok(parse_number(text)
+           .or_else(|| self.constants.get(text).copied())
+           .or_else(|| self.labels.get(text).map(|&a| a as f64)))
+    }
+
+    /// Read a value. Return string if the next token has none.
+    fn value(&mut self) -> Result<f64, String> {
+        let text = self.next()?;
+        self.parse_value(&text)?.ok_or_else(|| format!("Expected a number, got {}.", text))
+    }
+
+    /// Return the value of a token as a byte, from -128 to 255. Return string
+    /// on error.
+    /// # Arguments
+    /// * `text` The token, already read.
+    fn byte_of(&mut self, text: &str) -> Result<u8, String> {
+        match self.parse_value(text)? {
+            Some(v) if (-128.0..256.0).contains(&v) => Ok(v as i64 as u8),
+            Some(v) => Err(format!("{} does not fit in a byte.", v)),
+            None => Err(format!("Expected a number, got {}.", text)),
+        }
+    }
+
+    /// Read a byte. Return string on error.
+    fn byte(&mut self) -> Result<u8, String> {
+        let text = self.next()?;
+        self.byte_of(&text)
+    }
+
+    /// Read a value from 0 to 15. Return string on error.
+    fn nibble(&mut self) -> Result<u8, String> {
+        match self.value()? {
+            v if (0.0..16.0).contains(&v) => Ok(v as u8),
+            v => Err(format!("{} does not fit in a nibble.", v)),
+        }
+    }
+
+    /// Read the address of a jump, call, `i :=` or `:unpack`, writing it
+    /// later if it is a label defined further on. Return string on error.
+    /// # Arguments
+    /// * `patch` How the address is written, at the current address.
+    fn address(&mut self, patch: Patch) -> Result<u16, String> {
+        let text = self.next()?;
+        let value = match self.parse_value(&text)? {
+            Some(v) => v as i64 as usize,
+            None if self.parse_register(&text).is_some() => {
+                return Err(format!("Expected an address, got {}.", text));
+            },
+            None => {
+                self.fixups.push(Fixup { name: text, address: self.here, patch, line: self.line });
+                0
+            },
+        };
+        let bits = match patch {
+            Patch::Long | Patch::Unpack(None) => 0xFFFF,
+            _ => 0xFFF,
+        };
+        if value > bits {
+            return Err(format!("Address 0x{:X} does not fit in {} bits.", value,
+                               if bits == 0xFFF { 12 } else { 16 }));
+        }
+        Ok(value as u16)
+    }
+
+    /// Define a label at an address. Return string if it already is one.
+    /// # Arguments
+    /// * `name` Label name.
+    /// * `address` Its address.
+    fn define(&mut self, name: String, address: usize) -> Result<(), String> {
+        if self.labels.contains_key(&name) {
+            return Err(format!("Label {} is defined twice.", name));
+        }
+        self.labels.insert(name, address);
+        Ok(())
+    }
+
+    /// Read a calc expression up to its closing brace, the `{` already read.
+    /// Return string on error.
+    fn calc_block(&mut self) -> Result<f64, String> {
+        let result = self.calc_expr()?;
+        self.expect("}")?;
+        Ok(result)
+    }
+
+    /// Read a calc expression. Operators have the same precedence and are
+    /// applied right to left, as in Octo. Return string on error.
+    fn calc_expr(&mut self) -> Result<f64, String> {
+        let left = self.calc_term()?;
+        match self.peek() {
+            Some(")") | Some("}") | None => return Ok(left),
+            _ => {},
+        }
+        let op = self.next()?;
+        let right = self.calc_expr()?;
+        binary(&op, left, right).ok_or_else(|| format!("Unknown operator {}.", op))
+    }
+
+    /// Read a calc term: a value, a unary operator and its term or an
+    /// expression in parentheses. Return string on error.
+    fn calc_term(&mut self) -> Result<f64, String> {
+        let text = self.next()?;
+        match text.as_str() {
+            "(" => {
+                let result = self.calc_expr()?;
+                self.expect(")")?;
+                Ok(result)
+            },
+            "HERE" => Ok(self.here as f64),
+            "PI" => Ok(std::f64::consts::PI),
+            "E" => Ok(std::f64::consts::E),
+            // The byte assembled at an address.
+            "@" => {
+                let address = self.calc_term()? as i64 as usize;
+                Ok(self.memory.get(address).copied().unwrap_or(0) as f64)
+            },
+            op if is_unary(op) => {
+                let a = self.calc_term()?;
+                Ok(unary(op, a).unwrap_or(a))
+            },
+            _ => self.parse_value(&text)?.ok_or_else(|| format!("Expected a number, got {}.", text)),
+        }
+    }
+
+    /// Read the condition of an `if` or `while`, writing the instructions
+    /// comparing through vf, and return the test left to skip on. Return
+    /// string on error.
+    fn condition(&mut self) -> Result<Test, String> {
+        let x = self.register()?;
+        let op = self.next()?;
+        match op.as_str() {
+            "key" => return Ok(Test::Key(x)),
+            "-key" => return Ok(Test::NotKey(x)),
+            _ => {},
+        }
+        let text = self.next()?;
+        let rhs = match self.parse_register(&text) {
+            Some(y) => Operand::Register(y),
+            None => Operand::Byte(self.byte_of(&text)?),
+        };
+        let (x16, f) = ((x as u16) << 8, 0x8F00);
+        match op.as_str() {
+            "==" => return Ok(Test::Eq(x, rhs)),
+            "!=" => return Ok(Test::Ne(x, rhs)),
+            // vf = x >= rhs, the carry of x - rhs.
+            "<" | ">=" => match rhs {
+                Operand::Register(y) => {
+                    self.emit_op(f | x16 >> 4)?;
+                    self.emit_op(f | (y as u16) << 4 | 5)?;
+                },
+                Operand::Byte(n) => {
+                    self.emit_op(0x6F00 | n as u16)?;
+                    self.emit_op(f | x16 >> 4 | 7)?;
+                },
+            },
+            // vf = rhs >= x, the carry of rhs - x.
+            ">" | "<=" => match rhs {
+                Operand::Register(y) => {
+                    self.emit_op(f | (y as u16) << 4)?;
+                    self.emit_op(f | x16 >> 4 | 5)?;
+                },
+                Operand::Byte(n) => {
+                    self.emit_op(0x6F00 | n as u16)?;
+                    self.emit_op(f | x16 >> 4 | 5)?;
+                },
+            },
+            _ => return Err(format!("Unknown comparison {}.", op)),
+        }
+        let holds = (op == ">=" || op == "<=") as u8;
+        Ok(Test::Eq(0xF, Operand::Byte(holds)))
+    }
+
+    /// Write the skip running the next instruction only if `test` holds, or
+    /// only if it does not when `negate`.
+    /// # Arguments
+    /// * `test` The test.
+    /// * `negate` Run the next instruction when the test fails instead.
+    fn skip(&mut self, test: Test, negate: bool) -> Result<(), String> {
+        type O = Operand;
+        let op = |base: u16, x: u8, low: u16| base | (x as u16) << 8 | low;
+        let opcode = match (test, negate) {
+            (Test::Eq(x, O::Byte(n)), false) | (Test::Ne(x, O::Byte(n)), true) => op(0x4000, x, n as u16),
+            (Test::Ne(x, O::Byte(n)), false) | (Test::Eq(x, O::Byte(n)), true) => op(0x3000, x, n as u16),
+            (Test::Eq(x, O::Register(y)), false) | (Test::Ne(x, O::Register(y)), true) => {
+                op(0x9000, x, (y as u16) << 4)
+            },
+            (Test::Ne(x, O::Register(y)), false) | (Test::Eq(x, O::Register(y)), true) => {
+                op(0x5000, x, (y as u16) << 4)
+            },
+            (Test::Key(x), false) | (Test::NotKey(x), true) => op(0xE0A1, x, 0),
+            (Test::NotKey(x), false) | (Test::Key(x), true) => op(0xE09E, x, 0),
+        };
+        self.emit_op(opcode)
+    }
+
+    /// Read and assemble the statement of a register: an assignment or
+    /// arithmetic. Return string on error.
+    /// # Arguments
+    /// * `x` The register.
+    fn register_statement(&mut self, x: u8) -> Result<(), String> {
+        let x16 = (x as u16) << 8;
+        let op = self.next()?;
+        let text = self.next()?;
+        let y = self.parse_register(&text);
+        let opcode = match (op.as_str(), text.as_str(), y) {
+            (":=", _, Some(y)) => 0x8000 | x16 | (y as u16) << 4,
+            (":=", "random", _) => 0xC000 | x16 | self.byte()? as u16,
+            (":=", "key", _) => 0xF00A | x16,
+            (":=", "delay", _) => 0xF007 | x16,
+            (":=", _, None) => 0x6000 | x16 | self.byte_of(&text)? as u16,
+            ("+=", _, None) => 0x7000 | x16 | self.byte_of(&text)? as u16,
+            ("-=", _, None) => 0x7000 | x16 | self.byte_of(&text)?.wrapping_neg() as u16,
+            (_, _, Some(y)) => {
+                let n = match op.as_str() {
+                    "|=" => 1,
+                    "&=" => 2,
+                    "^=" => 3,
+                    "+=" => 4,
+                    "-=" => 5,
+                    ">>=" => 6,
+                    "=-" => 7,
+                    "<<=" => 0xE,
+                    _ => return Err(format!("Unknown operator {}.", op)),
+                };
+                0x8000 | x16 | (y as u16) << 4 | n
+            },
+            _ => return Err(format!("Unknown operator {} {}.", op, text)),
+        };
+        self.emit_op(opcode)
+    }
+
+    /// Read and assemble a directive, a token starting with a colon. Return
+    /// string on error.
+    /// # Arguments
+    /// * `directive` The directive.
+    fn directive(&mut self, directive: &str) -> Result<(), String> {
+        match directive {
+            ":" => {
+                let name = self.next()?;
+                let here = self.here;
+                self.define(name, here)?;
+            },
+            // Label the second byte of the next instruction.
+            ":next" => {
+                let name = self.next()?;
+                let here = self.here + 1;
+                self.define(name, here)?;
+            },
+            ":const" => {
+                let name = self.next()?;
+                let value = self.value()?;
+                self.constants.insert(name, value);
+            },
+            ":calc" => {
+                let name = self.next()?;
+                self.expect("{")?;
+                let value = self.calc_block()?;
+                self.constants.insert(name, value);
+            },
+            ":alias" => {
+                let name = self.next()?;
+                let register = self.register()?;
+                self.aliases.insert(name, register);
+            },
+            ":org" => {
+                let address = self.value()?;
+                if !(0.0..MEMORY_SIZE as f64).contains(&address) {
+                    return Err(format!("Address {} is outside memory.", address));
+                }
+                self.here = address as usize;
+            },
+            ":byte" => {
+                let byte = self.byte()?;
+                self.emit(byte)?;
+            },
+            ":call" => {
+                let address = self.address(Patch::Address)?;
+                self.emit_op(0x2000 | address)?;
+            },
+            // v0 and v1 hold the address, the nibble above it.
+            ":unpack" => {
+                let nibble = match self.peek() {
+                    Some("long") => {
+                        self.next()?;
+                        None
+                    },
+                    _ => Some(self.nibble()?),
+                };
+                let address = self.address(Patch::Unpack(nibble))?;
+                let high = (nibble.unwrap_or(0) << 4) as u16 | address >> 8;
+                self.emit_op(0x6000 | high)?;
+                self.emit_op(0x6100 | (address & 0xFF))?;
+            },
+            ":macro" => {
+                let name = self.next()?;
+                let mut args = Vec::new();
+                loop {
+                    match self.next()? {
+                        ref t if t == "{" => break,
+                        t => args.push(t),
+                    }
+                }
+                let mut body = Vec::new();
+                let mut depth = 1;
+                loop {
+                    let token = self.tokens.pop().ok_or("Unexpected end of the program.")?;
+                    match token.text.as_str() {
+                        "{" => depth += 1,
+                        "}" if depth == 1 => break,
+                        "}" => depth -= 1,
+                        _ => {},
+                    }
+                    body.push(token);
+                }
+                self.macros.insert(name, Macro { args, body, calls: 0 });
+            },
+            ":assert" => {
+                let message = match self.peek() {
+                    Some(t) if t.starts_with('"') => self.next()?,
+                    _ => String::from("Assertion failed"),
+                };
+                self.expect("{")?;
+                if self.calc_block()? == 0.0 {
+                    return Err(format!("{}.", message.trim_matches('"')));
+                }
+            },
+            // Debugger directives, which do not change the program.
+            ":breakpoint" => {
+                self.next()?;
+            },
+            ":monitor" => {
+                self.next()?;
+                self.next()?;
+            },
+            _ => return Err(format!("Unsupported directive {}.", directive)),
+        }
+        Ok(())
+    }
+
+    /// Expand a macro, putting its body with the arguments in place of their
+    /// names before the tokens left. Return string on error.
+    /// # Arguments
+    /// * `name` Macro name.
+    fn expand(&mut self, name: &str) -> Result<(), String> {
+        let (params, body, calls) = match self.macros.get_mut(name) {
+            Some(m) => {
+                m.calls += 1;
+                (m.args.clone(), m.body.clone(), m.calls - 1)
+            },
+            None => return Err(format!("Unknown macro {}.", name)),
+        };
+        let args = params.iter().map(|_| self.next()).collect::<Result<Vec<String>, String>>()?;
+        for token in body.into_iter().rev() {
+            let text = match params.iter().position(|p| *p == token.text) {
+                Some(i) => args[i].clone(),
+                None if token.text == "CALLS" => calls.to_string(),
+                None => token.text,
+            };
+            self.tokens.push(Token { text, line: token.line });
+        }
+        Ok(())
+    }
+
+    /// Read and assemble a statement. Return string on error.
+    fn statement(&mut self) -> Result<(), String> {
+        let text = self.next()?;
+        match text.as_str() {
+            t if t.starts_with(':') => self.directive(t)?,
+            "return" | ";" => self.emit_op(0x00EE)?,
+            "clear" => self.emit_op(0x00E0)?,
+            "exit" => self.emit_op(0x00FD)?,
+            "lores" => self.emit_op(0x00FE)?,
+            "hires" => self.emit_op(0x00FF)?,
+            "scroll-left" => self.emit_op(0x00FC)?,
+            "scroll-right" => self.emit_op(0x00FB)?,
+            "scroll-down" => {
+                let n = self.nibble()?;
+                self.emit_op(0x00C0 | n as u16)?;
+            },
+            "scroll-up" => {
+                let n = self.nibble()?;
+                self.emit_op(0x00D0 | n as u16)?;
+            },
+            "audio" => self.emit_op(0xF002)?,
+            "plane" => {
+                let n = self.nibble()?;
+                self.emit_op(0xF001 | (n as u16) << 8)?;
+            },
+            "jump" | "jump0" | "native" => {
+                let address = self.address(Patch::Address)?;
+                let base = match text.as_str() {
+                    "jump" => 0x1000,
+                    "jump0" => 0xB000,
+                    _ => 0x0000,
+                };
+                self.emit_op(base | address)?;
+            },
+            "sprite" => {
+                let x = self.register()?;
+                let y = self.register()?;
+                let n = self.nibble()?;
+                self.emit_op(0xD000 | (x as u16) << 8 | (y as u16) << 4 | n as u16)?;
+            },
+            "bcd" | "saveflags" | "loadflags" => {
+                let x = self.register()? as u16;
+                let low = match text.as_str() {
+                    "bcd" => 0x33,
+                    "saveflags" => 0x75,
+                    _ => 0x85,
+                };
+                self.emit_op(0xF000 | x << 8 | low)?;
+            },
+            "save" | "load" => {
+                let x = self.register()? as u16;
+                if self.peek() == Some("-") {
+                    self.next()?;
+                    let y = self.register()? as u16;
+                    let n = if text == "save" { 2 } else { 3 };
+                    self.emit_op(0x5000 | x << 8 | y << 4 | n)?;
+                } else {
+                    self.emit_op(0xF000 | x << 8 | if text == "save" { 0x55 } else { 0x65 })?;
+                }
+            },
+            "delay" | "buzzer" | "pitch" => {
+                self.expect(":=")?;
+                let x = self.register()? as u16;
+                let low = match text.as_str() {
+                    "delay" => 0x15,
+                    "buzzer" => 0x18,
+                    _ => 0x3A,
+                };
+                self.emit_op(0xF000 | x << 8 | low)?;
+            },
+            "i" => match self.next()?.as_str() {
+                "+=" => {
+                    let x = self.register()? as u16;
+                    self.emit_op(0xF01E | x << 8)?;
+                },
+                ":=" => match self.peek() {
+                    Some("hex") | Some("bighex") => {
+                        let low = if self.next()? == "hex" { 0x29 } else { 0x30 };
+                        let x = self.register()? as u16;
+                        self.emit_op(0xF000 | x << 8 | low)?;
+                    },
+                    Some("long") => {
+                        self.next()?;
+                        self.emit_op(0xF000)?;
+                        let address = self.address(Patch::Long)?;
+                        self.emit_op(address)?;
+                    },
+                    _ => {
+                        let address = self.address(Patch::Address)?;
+                        self.emit_op(0xA000 | address)?;
+                    },
+                },
+                op => return Err(format!("Unknown operator i {}.", op)),
+            },
+            "if" => {
+                let test = self.condition()?;
+                match self.next()?.as_str() {
+                    "then" => self.skip(test, false)?,
+                    // Jump past the block unless the test holds.
+                    "begin" => {
+                        self.skip(test, true)?;
+                        self.branches.push(self.here);
+                        self.emit_op(0x1000)?;
+                    },
+                    t => return Err(format!("Expected then or begin, got {}.", t)),
+                }
+            },
+            "else" => {
+                let jump = self.branches.pop().ok_or("else without if ... begin.")?;
+                self.branches.push(self.here);
+                self.emit_op(0x1000)?;
+                let here = self.here;
+                self.patch_jump(jump, here)?;
+            },
+            "end" => {
+                let jump = self.branches.pop().ok_or("end without if ... begin.")?;
+                let here = self.here;
+                self.patch_jump(jump, here)?;
+            },
+            "loop" => self.loops.push((self.here, Vec::new())),
+            // Jump past the loop unless the test holds.
+            "while" => {
+                let test = self.condition()?;
+                self.skip(test, true)?;
+                let here = self.here;
+                self.loops.last_mut().ok_or("while outside of a loop.")?.1.push(here);
+                self.emit_op(0x1000)?;
+            },
+            "again" => {
+                let (start, whiles) = self.loops.pop().ok_or("again without loop.")?;
+                let here = self.here;
+                self.emit_op(0x1000)?;
+                self.patch_jump(here, start)?;
+                let end = self.here;
+                for jump in whiles {
+                    self.patch_jump(jump, end)?;
+                }
+            },
+            t if self.macros.contains_key(t) => self.expand(t)?,
+            t if self.parse_register(t).is_some() => {
+                let x = self.parse_register(t).unwrap_or(0);
+                self.register_statement(x)?;
+            },
+            // Numbers alone are data.
+            t if parse_number(t).is_some() || self.constants.contains_key(t) => {
+                let byte = self.byte_of(t)?;
+                self.emit(byte)?;
+            },
+            // Any other name calls the label.
+            _ => {
+                self.tokens.push(Token { text, line: self.line });
+                let address = self.address(Patch::Address)?;
+                self.emit_op(0x2000 | address)?;
+            },
+        }
+        Ok(())
+    }
+
+    /// Assemble every statement, then fill in the labels used before they
+    /// were defined. Return string on error.
+    fn run(&mut self) -> Result<(), String> {
+        // Jump to main, filled in at the end.
+        self.emit_op(0x1000)?;
+        while !self.tokens.is_empty() {
+            self.statement()?;
+        }
+        if !self.loops.is_empty() {
+            return Err(String::from("loop without again."));
+        }
+        if !self.branches.is_empty() {
+            return Err(String::from("if ... begin without end."));
+        }
+        // Without main, run from the first instruction after the jump.
+        let main = self.labels.get("main").copied().unwrap_or(START + 2);
+        self.patch_jump(START, main)?;
+
+        for fixup in std::mem::take(&mut self.fixups) {
+            self.line = fixup.line;
+            let value = *self.labels.get(&fixup.name)
+                .ok_or_else(|| format!("Undefined name {}.", fixup.name))?;
+            let at = fixup.address;
+            match fixup.patch {
+                Patch::Address | Patch::Unpack(Some(_)) if value > 0xFFF => {
+                    return Err(format!("Address 0x{:X} does not fit in 12 bits.", value));
+                },
+                Patch::Address => {
+                    self.memory[at] = self.memory[at] & 0xF0 | (value >> 8) as u8;
+                    self.memory[at + 1] = value as u8;
+                },
+                Patch::Long => {
+                    self.memory[at] = (value >> 8) as u8;
+                    self.memory[at + 1] = value as u8;
+                },
+                Patch::Unpack(nibble) => {
+                    self.memory[at + 1] = nibble.unwrap_or(0) << 4 | (value >> 8) as u8;
+                    self.memory[at + 3] = value as u8;
+                },
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Assemble Octo source into a ROM to load at 0x200. The ROM starts with a
+/// jump to the `main` label, or to the next instruction if there is none.
+/// Return string on error, with the line it is on.
+/// # Arguments
+/// * `source` Octo source.
+pub fn assemble(source: &str) -> Result<Vec<u8>, String> {
+    let mut assembler = Assembler::new(source);
+    assembler.run().map_err(|e| format!("Line {}: {}", assembler.line, e))?;
+    Ok(assembler.memory[START..assembler.end].to_vec())
+}
@@ -0,0 +1,405 @@
+//! Chip 8 command line options.
+//! (C) Ryan Jeffrey <ryan@ryanmj.xyz>, 2022
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or (at
+// your option) any later version.
+
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use std::convert::TryFrom;
+
+use cartridge::Cartridge;
+use filter::{Mask, Scaler, Upscaler};
+use font::Font;
+use keymap::Keymap;
+use machine::{Machine, VIP_STACK_ADDRESS};
+use palette::{Palette, parse_color};
+use quirks::Quirks;
+use source::RomSource;
+
+/// Size of every filtered pixel in saved images.
+const DEFAULT_IMAGE_SCALE: usize = 10;
+/// Instructions run per second.
+const DEFAULT_SPEED: u64 = 700;
+
+/// Parse a number, in hex with a `0x` prefix. Return None if it is not one.
+/// # Arguments
+/// * `s` The number.
+fn parse_number(s: &str) -> Option<usize> {
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+/// Options given on the command line.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Options {
+    /// Path of the ROM file, None to choose one in the launcher.
+    pub rom: Option<String>,
+    /// Name of the ROM to run in a ZIP archive holding several.
+    pub zip_entry: Option<String>,
+    /// IPS or BPS patches applied to the ROM when it is loaded, in order.
+    pub patches: Vec<String>,
+    /// Directory the launcher lists ROMs from, the current one by default.
+    pub rom_dir: Option<String>,
+    /// Write the control flow graph of the ROM in DOT format to this file,
+    /// then exit.
+    pub dot: Option<String>,
+    /// Print the disassembly of the ROM, then exit.
+    pub disassemble: bool,
+    /// Octo symbol file, defaults to the ROM's path with a .sym extension.
+    pub symbols: Option<String>,
+    /// Print every instruction before it is run.
+    pub trace: bool,
+    /// Synchronise presenting frames with the display's refresh rate.
+    pub vsync: bool,
+    /// Keymap file, defaults to the 1234/QWER/ASDF/ZXCV layout.
+    pub keymap: Option<String>,
+    /// Show a clickable keypad next to the screen.
+    pub keypad: bool,
+    /// Built-in palette name or palette file.
+    pub palette: Option<String>,
+    /// Palette of the ROM's cartridge, used unless one is chosen.
+    pub rom_palette: Option<Palette>,
+    /// Colour of set cells, replacing the palette's.
+    pub foreground: Option<[u8; 3]>,
+    /// Colour of clear cells, replacing the palette's.
+    pub background: Option<[u8; 3]>,
+    /// Only scale cells by whole numbers, with borders around the screen.
+    pub integer_scale: bool,
+    /// Number of frames cleared cells take to fade out, 0 to clear them at
+    /// once.
+    pub persistence: u32,
+    /// Filter scaling frames up before they are shown or saved.
+    pub upscaler: Upscaler,
+    /// Save a screenshot after this many frames.
+    pub screenshot_at_frame: Option<u64>,
+    /// Size of every filtered pixel in saved images.
+    pub image_scale: usize,
+    /// Run without a window, as fast as possible.
+    pub headless: bool,
+    /// Number of frames to run without a window.
+    pub frames: Option<u64>,
+    /// Record an animated GIF from the start.
+    pub record: bool,
+    /// Behaviours of the interpreter to emulate.
+    pub quirks: Quirks,
+    /// Where the ROM and font are loaded and how much memory there is.
+    pub machine: Machine,
+    /// Instructions run per second.
+    pub speed: u64,
+    /// Reload the ROM when its file changes.
+    pub watch: bool,
+    /// Keep the registers, screen and memory outside the program when
+    /// reloading the ROM.
+    pub keep_state: bool,
+}
+
+impl Options {
+    /// Parse the command line arguments, not including the program name.
+    /// The ROM is the last argument that is not an option. Return string on
+    /// error.
+    /// # Arguments
+    /// * `args` The command line arguments.
+    pub fn parse<T: Iterator<Item = String>>(mut args: T) -> Result<Self, String> {
+        let mut result = Self {
+            rom: None,
+            zip_entry: None,
+            patches: Vec::new(),
+            rom_dir: None,
+            dot: None,
+            disassemble: false,
+            symbols: None,
+            trace: false,
+            vsync: false,
+            keymap: None,
+            keypad: false,
+            palette: None,
+            rom_palette: None,
+            foreground: None,
+            background: None,
+            integer_scale: false,
+            persistence: 0,
+            upscaler: Upscaler::default(),
+            screenshot_at_frame: None,
+            image_scale: DEFAULT_IMAGE_SCALE,
+            headless: false,
+            frames: None,
+            record: false,
+            quirks: Quirks::default(),
+            machine: Machine::default(),
+            speed: DEFAULT_SPEED,
+            watch: false,
+            keep_state: false,
+        };
+        // Given separately from the presets, in any order.
+        let mut display_wait = None;
+        let mut load_address = None;
+        let mut font_address = None;
+        let mut memory_size = None;
+        let mut font = None;
+        let mut stack_depth = None;
+        let mut memory_stack = None;
+        let address = |arg: &str, value: Option<String>| -> Result<u16, String> {
+            let value = value.ok_or_else(|| format!("{} requires an address", arg))?;
+            parse_number(&value).and_then(|a| u16::try_from(a).ok())
+                .ok_or_else(|| format!("Invalid address {}.", value))
+        };
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--dot" => {
+                    result.dot = Some(args.next().ok_or("--dot requires a file name")?);
+                },
+                "--display-wait" => {
+                    display_wait = Some(true);
+                },
+                "--no-display-wait" => {
+                    display_wait = Some(false);
+                },
+                "--disassemble" => {
+                    result.disassemble = true;
+                },
+                "--font" => {
+                    let name = args.next().ok_or("--font requires a name or file")?;
+                    font = Some(Font::from_arg(&name)?);
+                },
+                "--font-address" => {
+                    font_address = Some(address(&arg, args.next())?);
+                },
+                "--load-address" => {
+                    load_address = Some(address(&arg, args.next())?);
+                },
+                "--machine" => {
+                    let name = args.next().ok_or("--machine requires a machine name")?;
+                    result.machine = Machine::parse(&name)?;
+                },
+                "--memory-size" => {
+                    let size = args.next().ok_or("--memory-size requires a number of bytes")?;
+                    memory_size = Some(parse_number(&size)
+                        .ok_or_else(|| format!("Invalid memory size {}.", size))?);
+                },
+                "--memory-stack" => {
+                    memory_stack = Some(true);
+                },
+                "--no-memory-stack" => {
+                    memory_stack = Some(false);
+                },
+                "--mask" => {
+                    let name = args.next().ok_or("--mask requires a mask name")?;
+                    result.upscaler.mask = Mask::parse(&name)?;
+                },
+                "--palette" => {
+                    result.palette = Some(args.next().ok_or("--palette requires a name or file")?);
+                },
+                "--filter" => {
+                    let name = args.next().ok_or("--filter requires a filter name")?;
+                    result.upscaler.scaler = Scaler::parse(&name)?;
+                },
+                "--foreground" => {
+                    let color = args.next().ok_or("--foreground requires a colour")?;
+                    result.foreground = Some(parse_color(&color)?);
+                },
+                "--background" => {
+                    let color = args.next().ok_or("--background requires a colour")?;
+                    result.background = Some(parse_color(&color)?);
+                },
+                "--patch" => {
+                    result.patches.push(args.next().ok_or("--patch requires a file name")?);
+                },
+                "--persistence" => {
+                    let frames = args.next().ok_or("--persistence requires a number of frames")?;
+                    result.persistence = frames.parse()
+                        .map_err(|_| format!("Invalid number of frames {}.", frames))?;
+                },
+                "--quirks" => {
+                    let name = args.next().ok_or("--quirks requires a preset name")?;
+                    result.quirks = Quirks::parse(&name)?;
+                },
+                "--rom-dir" => {
+                    result.rom_dir = Some(args.next().ok_or("--rom-dir requires a directory")?);
+                },
+                "--record" => {
+                    result.record = true;
+                },
+                "--screenshot-at-frame" => {
+                    let frame = args.next().ok_or("--screenshot-at-frame requires a frame")?;
+                    result.screenshot_at_frame = Some(frame.parse()
+                        .map_err(|_| format!("Invalid frame {}.", frame))?);
+                },
+                "--speed" => {
+                    let speed = args.next().ok_or("--speed requires a number of instructions")?;
+                    result.speed = match speed.parse() {
+                        Ok(s) if s > 0 => s,
+                        _ => return Err(format!("Invalid speed {}.", speed)),
+                    };
+                },
+                "--stack-depth" => {
+                    let depth = args.next().ok_or("--stack-depth requires a number of levels")?;
+                    stack_depth = Some(depth.parse()
+                        .map_err(|_| format!("Invalid stack depth {}.", depth))?);
+                },
+                "--symbols" => {
+                    result.symbols = Some(args.next().ok_or("--symbols requires a file name")?);
+                },
+                "--frames" => {
+                    let frames = args.next().ok_or("--frames requires a number of frames")?;
+                    result.frames = Some(frames.parse()
+                        .map_err(|_| format!("Invalid number of frames {}.", frames))?);
+                },
+                "--headless" => {
+                    result.headless = true;
+                },
+                "--image-scale" => {
+                    let scale = args.next().ok_or("--image-scale requires a scale")?;
+                    result.image_scale = match scale.parse() {
+                        Ok(s) if s > 0 => s,
+                        _ => return Err(format!("Invalid image scale {}.", scale)),
+                    };
+                },
+                "--integer-scale" => {
+                    result.integer_scale = true;
+                },
+                "--keymap" => {
+                    result.keymap = Some(args.next().ok_or("--keymap requires a file name")?);
+                },
+                "--keep-state" => {
+                    result.keep_state = true;
+                },
+                "--keypad" => {
+                    result.keypad = true;
+                },
+                "--trace" => {
+                    result.trace = true;
+                },
+                "--vsync" => {
+                    result.vsync = true;
+                },
+                "--watch" => {
+                    result.watch = true;
+                },
+                "--zip-entry" => {
+                    result.zip_entry = Some(args.next().ok_or("--zip-entry requires a name")?);
+                },
+                _ if arg.starts_with("--") => {
+                    return Err(format!("Unknown option {}.", arg));
+                },
+                _ => {
+                    result.rom = Some(arg);
+                },
+            }
+        }
+        if let Some(wait) = display_wait {
+            result.quirks.display_wait = wait;
+        }
+        if let Some(address) = load_address {
+            result.machine.load_address = address;
+        }
+        if let Some(address) = font_address {
+            result.machine.font_address = address;
+        }
+        if let Some(size) = memory_size {
+            result.machine.memory_size = size;
+        }
+        if let Some(font) = font {
+            result.machine.font = font;
+        }
+        if let Some(depth) = stack_depth {
+            result.machine.stack_depth = depth;
+        }
+        if let Some(memory) = memory_stack {
+            result.machine.stack_address = if memory { Some(VIP_STACK_ADDRESS) } else { None };
+        }
+        result.machine.validate()?;
+
+        Ok(result)
+    }
+
+    /// Return the ROM's path, for the modes that cannot use the launcher.
+    /// Return string if no ROM was given.
+    pub fn rom(&self) -> Result<&str, String> {
+        self.rom.as_deref().ok_or_else(|| String::from("No ROM given."))
+    }
+
+    /// Return where the ROM comes from, the ROM file or its entry in a ZIP
+    /// archive. Return string if no ROM was given or it cannot be opened.
+    pub fn source(&self) -> Result<RomSource, String> {
+        RomSource::open(self.rom()?, self.zip_entry.as_deref())
+    }
+
+    /// Return the chosen palette, or else that of the ROM's cartridge, with
+    /// the chosen colours replacing its own.
+    /// Return string on error.
+    pub fn palette(&self) -> Result<Palette, String> {
+        let mut result = match (&self.palette, &self.rom_palette) {
+            (Some(arg), _) => Palette::from_arg(arg)?,
+            (None, Some(palette)) => palette.clone(),
+            (None, None) => Palette::default(),
+        };
+        if let Some(color) = self.foreground {
+            result.fill = color;
+        }
+        if let Some(color) = self.background {
+            result.background = color;
+        }
+        Ok(result)
+    }
+
+    /// Return the options with the settings of the ROM, if it is an Octo
+    /// cartridge, replacing the chosen ones: the speed and the quirks. Its
+    /// palette is used unless `--palette` is given. Return string on error.
+    pub fn with_cartridge(&self) -> Result<Self, String> {
+        let mut result = self.clone();
+        if self.rom.is_none() {
+            return Ok(result);
+        }
+        let source = self.source()?;
+        let bytes = source.bytes()?;
+        if !Cartridge::is_cartridge(&bytes) {
+            return Ok(result);
+        }
+        let cartridge = Cartridge::decode(&bytes).map_err(|e| format!("{}: {}", source, e))?;
+        if let Some(tickrate) = cartridge.tickrate {
+            // Octo runs the instructions of a tick every 60 Hz frame.
+            result.speed = tickrate * 60;
+        }
+        let quirks = &mut result.quirks;
+        for (quirk, value) in [(&mut quirks.display_wait, cartridge.display_wait),
+                               (&mut quirks.shift, cartridge.shift),
+                               (&mut quirks.increment_i, cartridge.increment_i),
+                               (&mut quirks.jump, cartridge.jump),
+                               (&mut quirks.clip, cartridge.clip)] {
+            if let Some(value) = value {
+                *quirk = value;
+            }
+        }
+        if cartridge.palette.is_some() {
+            result.rom_palette = cartridge.palette;
+        }
+        Ok(result)
+    }
+
+    /// Return the chosen keymap, or the default one, with the bindings of
+    /// the ROM's keymap, if there is a ROM, replacing its own. Return string
+    /// on error.
+    pub fn keymap(&self) -> Result<Keymap, String> {
+        let mut result = match &self.keymap {
+            Some(path) => Keymap::load(path)?,
+            None => Keymap::default(),
+        };
+        if let Some(rom) = &self.rom {
+            if let Some(overrides) = Keymap::load_for_rom(rom)? {
+                result.merge(&overrides);
+            }
+        }
+        Ok(result)
+    }
+}
@@ -0,0 +1,162 @@
+//! Chip 8 colour palettes.
+//! (C) Ryan Jeffrey <ryan@ryanmj.xyz>, 2022
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or (at
+// your option) any later version.
+
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use std::fs;
+use std::path::Path;
+
+/// Built-in palettes: name, then background, fill, fill2 and blend.
+const BUILTINS: [(&str, [[u8; 3]; 4]); 5] = [
+    ("classic", [[0x00, 0x00, 0x00], [0xff, 0xff, 0xff], [0xaa, 0xaa, 0xaa], [0x55, 0x55, 0x55]]),
+    ("green", [[0x0a, 0x1a, 0x0a], [0x33, 0xff, 0x66], [0x1a, 0x80, 0x33], [0xb3, 0xff, 0xc6]]),
+    ("amber", [[0x1a, 0x0f, 0x00], [0xff, 0xb0, 0x00], [0x80, 0x58, 0x00], [0xff, 0xd9, 0x80]]),
+    ("lcd", [[0x9b, 0xbc, 0x0f], [0x0f, 0x38, 0x0f], [0x8b, 0xac, 0x0f], [0x30, 0x62, 0x30]]),
+    ("high-contrast", [[0x00, 0x00, 0x00], [0xff, 0xff, 0x00], [0x00, 0xff, 0xff],
+                       [0xff, 0xff, 0xff]]),
+];
+
+/// Colours of the display, named as in Octo. Cells are drawn in `fill` when
+/// set in the first plane, `fill2` when set in the second plane and `blend`
+/// when set in both.
+///
+/// Palette files have one `name = colour` line per colour, colours being
+/// `#RRGGBB`, `#` followed by a space starts a comment. Colours that are not
+/// given are those of the classic palette.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Palette {
+    /// Name of the palette, the file name for palette files.
+    pub name: String,
+    /// Colour of clear cells.
+    pub background: [u8; 3],
+    /// Colour of cells set in the first plane.
+    pub fill: [u8; 3],
+    /// Colour of cells set in the second plane.
+    pub fill2: [u8; 3],
+    /// Colour of cells set in both planes.
+    pub blend: [u8; 3],
+}
+
+impl Default for Palette {
+    /// The classic white on black palette.
+    fn default() -> Self {
+        Self::builtins().remove(0)
+    }
+}
+
+/// Parse a `#RRGGBB` or `RRGGBB` colour. Return string on error.
+/// # Arguments
+/// * `s` The colour.
+pub fn parse_color(s: &str) -> Result<[u8; 3], String> {
+    let hex = s.strip_prefix('#').unwrap_or(s);
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("Invalid colour {}.", s));
+    }
+    let value = match u32::from_str_radix(hex, 16) {
+        Ok(v) => v,
+        _ => return Err(format!("Invalid colour {}.", s)),
+    };
+    Ok([(value >> 16) as u8, (value >> 8) as u8, value as u8])
+}
+
+impl Palette {
+    /// Return every built-in palette, classic first.
+    pub fn builtins() -> Vec<Self> {
+        BUILTINS.iter()
+            .map(|&(name, [background, fill, fill2, blend])| Self {
+                name: name.to_string(),
+                background,
+                fill,
+                fill2,
+                blend,
+            })
+            .collect()
+    }
+
+    /// Return the built-in palette called `name`.
+    /// # Arguments
+    /// * `name` Palette name.
+    pub fn builtin(name: &str) -> Option<Self> {
+        Self::builtins().into_iter().find(|p| p.name == name)
+    }
+
+    /// Parse the text of a palette file. Return string on error.
+    /// # Arguments
+    /// * `name` Name of the palette.
+    /// * `text` Contents of the palette file.
+    pub fn parse(name: &str, text: &str) -> Result<Self, String> {
+        let mut result = Self {
+            name: name.to_string(),
+            ..Self::default()
+        };
+
+        for (n, line) in text.lines().enumerate() {
+            let line = line.split("# ").next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let error = |e: String| format!("Line {}: {}", n + 1, e);
+            let (key, value) = match line.find('=') {
+                Some(i) => (line[..i].trim(), parse_color(line[i + 1..].trim()).map_err(error)?),
+                None => return Err(error(format!("expected name = colour, got {}.", line))),
+            };
+            match key {
+                "background" => result.background = value,
+                "fill" => result.fill = value,
+                "fill2" => result.fill2 = value,
+                "blend" => result.blend = value,
+                _ => return Err(error(format!("Unknown colour {}.", key))),
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Load a palette file. Return string on error.
+    /// # Arguments
+    /// * `path` Path of the palette file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let name = path.file_stem().map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Self::parse(&name, &text)
+    }
+
+    /// Return the built-in palette called `arg`, or load it as a palette file.
+    /// Return string on error.
+    /// # Arguments
+    /// * `arg` Palette name or path.
+    pub fn from_arg(arg: &str) -> Result<Self, String> {
+        match Self::builtin(arg) {
+            Some(p) => Ok(p),
+            None if Path::new(arg).is_file() => Self::load(arg),
+            None => Err(format!("{} is neither a palette file nor one of {}.", arg,
+                                Self::builtins().iter().map(|p| p.name.as_str())
+                                .collect::<Vec<&str>>().join(", "))),
+        }
+    }
+
+    /// Return the colour of a cell.
+    /// # Arguments
+    /// * `planes` Planes the cell is set in, bit 0 the first plane, bit 1 the
+    ///   second.
+    pub fn color(&self, planes: u8) -> [u8; 3] {
+        match planes & 3 {
+            0 => self.background,
+            1 => self.fill,
+            2 => self.fill2,
+            _ => self.blend,
+        }
+    }
+}
@@ -0,0 +1,234 @@
+//! Chip 8 IPS and BPS ROM patches.
+//! (C) Ryan Jeffrey <ryan@ryanmj.xyz>, 2022
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or (at
+// your option) any later version.
+
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use std::fs;
+
+/// Magic bytes IPS patches start with.
+const IPS_MAGIC: &[u8] = b"PATCH";
+/// Offset ending the records of IPS patches, "EOF".
+const IPS_END: usize = 0x454F46;
+/// Magic bytes BPS patches start with.
+const BPS_MAGIC: &[u8] = b"BPS1";
+/// Size of the source, target and patch checksums ending BPS patches.
+const BPS_FOOTER: usize = 12;
+
+/// Return the CRC-32 of bytes, as zlib and BPS patches compute it.
+/// # Arguments
+/// * `bytes` The bytes.
+pub fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0u32, |crc, &b| {
+        (0..8).fold(crc ^ b as u32, |c, _| if c & 1 == 1 { c >> 1 ^ 0xEDB88320 } else { c >> 1 })
+    })
+}
+
+/// Reads the fields of a patch, failing at its end.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    /// Read `n` bytes. Return string at the end of the patch.
+    /// # Arguments
+    /// * `n` Number of bytes.
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        let result = self.offset.checked_add(n).and_then(|end| self.bytes.get(self.offset..end))
+            .ok_or("The patch is cut short.")?;
+        self.offset += n;
+        Ok(result)
+    }
+
+    /// Read a big endian number of `n` bytes, as in IPS patches. Return
+    /// string at the end of the patch.
+    /// # Arguments
+    /// * `n` Number of bytes.
+    fn big_endian(&mut self, n: usize) -> Result<usize, String> {
+        Ok(self.take(n)?.iter().fold(0, |n, &b| n << 8 | b as usize))
+    }
+
+    /// Read a BPS number, 7 bits per byte with the last byte's top bit set
+    /// and one added per byte before it so every number has one encoding.
+    /// Return string at the end of the patch or on overflow.
+    fn number(&mut self) -> Result<usize, String> {
+        let mut result: usize = 0;
+        let mut shift: usize = 1;
+        loop {
+            let byte = self.take(1)?[0];
+            result = (byte as usize & 0x7F).checked_mul(shift)
+                .and_then(|n| n.checked_add(result))
+                .ok_or("A number in the patch is too large.")?;
+            if byte & 0x80 != 0 {
+                return Ok(result);
+            }
+            shift = shift.checked_shl(7).filter(|&s| s != 0)
+                .ok_or("A number in the patch is too large.")?;
+            result = result.checked_add(shift).ok_or("A number in the patch is too large.")?;
+        }
+    }
+}
+
+/// Apply an IPS patch: records of bytes to write at offsets, or of a byte to
+/// write several times, then optionally the size to cut the ROM to. Return
+/// string on error.
+/// # Arguments
+/// * `rom` The ROM, patched in place.
+/// * `patch` Contents of the patch.
+fn apply_ips(rom: &mut Vec<u8>, patch: &[u8]) -> Result<(), String> {
+    let mut reader = Reader { bytes: patch, offset: IPS_MAGIC.len() };
+    loop {
+        let offset = reader.big_endian(3)?;
+        if offset == IPS_END {
+            break;
+        }
+        let (size, data) = match reader.big_endian(2)? {
+            // Run length encoded.
+            0 => {
+                let size = reader.big_endian(2)?;
+                (size, vec![reader.take(1)?[0]; size])
+            },
+            size => (size, reader.take(size)?.to_vec()),
+        };
+        if rom.len() < offset + size {
+            rom.resize(offset + size, 0);
+        }
+        rom[offset..offset + size].copy_from_slice(&data);
+    }
+    if reader.offset + 3 <= patch.len() {
+        rom.truncate(reader.big_endian(3)?);
+    }
+    Ok(())
+}
+
+/// Apply a BPS patch, which builds a new ROM from copies of the original,
+/// of the patch and of the new ROM so far. The checksums of the original,
+/// the new ROM and the patch are checked. Return string on error.
+/// # Arguments
+/// * `rom` The original ROM.
+/// * `patch` Contents of the patch.
+fn apply_bps(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    if patch.len() < BPS_MAGIC.len() + BPS_FOOTER {
+        return Err(String::from("The patch is cut short."));
+    }
+    let end = patch.len() - BPS_FOOTER;
+    let checksum = |i: usize| {
+        u32::from_le_bytes([patch[end + i], patch[end + i + 1], patch[end + i + 2],
+                            patch[end + i + 3]])
+    };
+    if crc32(&patch[..end + 8]) != checksum(8) {
+        return Err(String::from("The patch's checksum is wrong, it is damaged."));
+    }
+    if crc32(rom) != checksum(0) {
+        return Err(String::from("The ROM's checksum is wrong, the patch is for another ROM."));
+    }
+
+    let mut reader = Reader { bytes: &patch[..end], offset: BPS_MAGIC.len() };
+    let source_size = reader.number()?;
+    let target_size = reader.number()?;
+    let metadata_size = reader.number()?;
+    reader.take(metadata_size)?;
+    if source_size != rom.len() {
+        return Err(format!("The patch is for a ROM of {} bytes, not {}.", source_size,
+                           rom.len()));
+    }
+
+    let mut result: Vec<u8> = Vec::new();
+    // Where the next source and target copies start.
+    let mut source_offset: usize = 0;
+    let mut target_offset: usize = 0;
+    let out_of_range = || String::from("The patch copies from outside the ROM.");
+    // Move a copy offset by the signed distance read from the patch.
+    let relative = |reader: &mut Reader, offset: usize| -> Result<usize, String> {
+        let distance = reader.number()?;
+        if distance & 1 == 1 {
+            offset.checked_sub(distance >> 1)
+        } else {
+            offset.checked_add(distance >> 1)
+        }.ok_or_else(out_of_range)
+    };
+    while reader.offset < end {
+        let action = reader.number()?;
+        let length = (action >> 2) + 1;
+        if target_size - result.len() < length {
+            return Err(String::from("The patch writes past the end of the new ROM."));
+        }
+        match action & 3 {
+            // Copy the original's bytes at the same offset.
+            0 => {
+                let start = result.len();
+                result.extend_from_slice(rom.get(start..).and_then(|r| r.get(..length))
+                                          .ok_or_else(out_of_range)?);
+            },
+            // Copy bytes from the patch.
+            1 => result.extend_from_slice(reader.take(length)?),
+            // Copy bytes from anywhere in the original.
+            2 => {
+                source_offset = relative(&mut reader, source_offset)?;
+                let bytes = rom.get(source_offset..).and_then(|r| r.get(..length))
+                    .ok_or_else(out_of_range)?;
+                result.extend_from_slice(bytes);
+                source_offset += length;
+            },
+            // Copy bytes of the new ROM, one at a time as the copy may
+            // overlap what it writes.
+            _ => {
+                target_offset = relative(&mut reader, target_offset)?;
+                if target_offset >= result.len() {
+                    return Err(out_of_range());
+                }
+                for _ in 0..length {
+                    result.push(result[target_offset]);
+                    target_offset += 1;
+                }
+            },
+        }
+    }
+
+    if result.len() != target_size {
+        return Err(String::from("The patch is cut short."));
+    }
+    if crc32(&result) != checksum(4) {
+        return Err(String::from("The patched ROM's checksum is wrong."));
+    }
+    Ok(result)
+}
+
+/// Apply an IPS or BPS patch to a ROM, told apart by their magic bytes.
+/// Return the patched ROM, return string on error.
+/// # Arguments
+/// * `rom` The ROM, the bytes loaded at the load address.
+/// * `patch` Contents of the patch.
+pub fn apply(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    if patch.starts_with(IPS_MAGIC) {
+        let mut result = rom.to_vec();
+        apply_ips(&mut result, patch)?;
+        Ok(result)
+    } else if patch.starts_with(BPS_MAGIC) {
+        apply_bps(rom, patch)
+    } else {
+        Err(String::from("Not an IPS or BPS patch."))
+    }
+}
+
+/// Apply patch files to a ROM in order. Return the patched ROM, return
+/// string on error, with the patch it is in.
+/// # Arguments
+/// * `rom` The ROM.
+/// * `patches` Paths of the IPS or BPS patches.
+pub fn apply_files(rom: Vec<u8>, patches: &[String]) -> Result<Vec<u8>, String> {
+    patches.iter().try_fold(rom, |rom, path| {
+        let patch = fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+        apply(&rom, &patch).map_err(|e| format!("{}: {}", path, e))
+    })
+}
@@ -0,0 +1,91 @@
+//! Chip 8 interpreter quirks.
+//! (C) Ryan Jeffrey <ryan@ryanmj.xyz>, 2022
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or (at
+// your option) any later version.
+
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+/// Behaviours that differ between chip 8 interpreters, which ROMs written
+/// for one of them may depend on.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub struct Quirks {
+    /// `Drw` waits for the start of the next 60 Hz frame, as the COSMAC VIP
+    /// waits for the vertical blank, limiting drawing to one sprite per
+    /// frame.
+    pub display_wait: bool,
+    /// `Shr` and `Shl` shift Vx in place and ignore Vy, as SUPER-CHIP does,
+    /// rather than putting Vy shifted into Vx.
+    pub shift: bool,
+    /// `LdIR` and `LdIRM` leave I pointing past the last register they
+    /// stored or loaded, as the COSMAC VIP does.
+    pub increment_i: bool,
+    /// `JpI` jumps by Vx, x being the top digit of its address, as SUPER-CHIP
+    /// does, rather than by V0.
+    pub jump: bool,
+    /// Sprites stop at the edges of the screen instead of wrapping around to
+    /// the other side. Only where they start wraps.
+    pub clip: bool,
+}
+
+impl Quirks {
+    /// Return the quirks of the original COSMAC VIP interpreter.
+    pub fn vip() -> Self {
+        Self {
+            display_wait: true,
+            shift: false,
+            increment_i: true,
+            jump: false,
+            clip: true,
+        }
+    }
+
+    /// Return the quirks of SUPER-CHIP on the HP 48.
+    pub fn schip() -> Self {
+        Self {
+            display_wait: false,
+            shift: true,
+            increment_i: false,
+            jump: true,
+            clip: true,
+        }
+    }
+
+    /// Return the quirks of modern interpreters, which run as fast as they
+    /// are asked to and wrap sprites around the screen.
+    pub fn modern() -> Self {
+        Self {
+            display_wait: false,
+            shift: false,
+            increment_i: false,
+            jump: false,
+            clip: false,
+        }
+    }
+
+    /// Parse a preset name, `vip`, `schip` or `modern`. Return string on
+    /// error.
+    /// # Arguments
+    /// * `name` Preset name.
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "vip" => Ok(Self::vip()),
+            "schip" => Ok(Self::schip()),
+            "modern" => Ok(Self::modern()),
+            _ => Err(format!("Unknown quirks {}, expected vip, schip or modern.", name)),
+        }
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Self::modern()
+    }
+}
@@ -0,0 +1,171 @@
+//! Chip 8 gameplay recording to animated GIF.
+//! (C) Ryan Jeffrey <ryan@ryanmj.xyz>, 2022
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or (at
+// your option) any later version.
+
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+
+use super::{NUM_COLS, NUM_ROWS};
+use display::BYTES_PER_PIXEL;
+use filter::Upscaler;
+use framebuffer::Framebuffer;
+use gif;
+use image::{Image, render};
+use palette::Palette;
+
+/// Rate of the frame clock.
+const FRAMES_PER_SECOND: u64 = 60;
+/// Shortest GIF delay, in hundredths of a second. Viewers slow shorter ones
+/// down to about a tenth of a second.
+const MIN_DELAY: u64 = 2;
+
+/// A screen shown for one or more frames in a row.
+struct Shot {
+    screen: Framebuffer,
+    palette: Palette,
+    /// Number of frames the screen was shown for.
+    frames: u64,
+}
+
+/// Records the screen every frame and encodes the recording as an animated
+/// GIF. Identical frames in a row are stored once and shown for longer, and
+/// screens shown too briefly for a GIF delay are merged into the one before.
+pub struct Recorder {
+    /// Filter applied to every frame.
+    upscaler: Upscaler,
+    /// Size of every filtered pixel.
+    scale: usize,
+    shots: Vec<Shot>,
+}
+
+/// Convert an image to colour indices and its colour table. Return string if
+/// it has more than 256 colours.
+/// # Arguments
+/// * `image` The image.
+fn index_colors(image: &Image) -> Result<(Vec<u8>, Vec<u8>), String> {
+    let mut indices = HashMap::new();
+    let mut table = Vec::new();
+    let mut result = Vec::with_capacity(image.width * image.height);
+    for pixel in image.pixels.chunks_exact(BYTES_PER_PIXEL) {
+        let color = [pixel[0], pixel[1], pixel[2]];
+        let index = match indices.get(&color) {
+            Some(&i) => i,
+            None if indices.len() < 256 => {
+                let i = indices.len() as u8;
+                indices.insert(color, i);
+                table.extend_from_slice(&color);
+                i
+            },
+            None => return Err(String::from("A frame has more than 256 colours.")),
+        };
+        result.push(index);
+    }
+    Ok((result, table))
+}
+
+/// Return the time of a frame in hundredths of a second, the unit of GIF
+/// delays, rounded to the nearest.
+/// # Arguments
+/// * `frame` Frame number.
+fn centiseconds(frame: u64) -> u64 {
+    (frame * 100 + FRAMES_PER_SECOND / 2) / FRAMES_PER_SECOND
+}
+
+impl Recorder {
+    /// Create an empty recording.
+    /// # Arguments
+    /// * `upscaler` Filter applied to every frame.
+    /// * `scale` Size of every filtered pixel.
+    pub fn new(upscaler: Upscaler, scale: usize) -> Self {
+        Self {
+            upscaler,
+            scale,
+            shots: Vec::new(),
+        }
+    }
+
+    /// Record one frame.
+    /// # Arguments
+    /// * `screen` The emulator screen.
+    /// * `palette` Colours of the screen.
+    pub fn capture(&mut self, screen: &Framebuffer, palette: &Palette) {
+        if let Some(last) = self.shots.last_mut() {
+            if last.screen == *screen && last.palette == *palette {
+                last.frames += 1;
+                return;
+            }
+        }
+        self.shots.push(Shot {
+            screen: screen.clone(),
+            palette: palette.clone(),
+            frames: 1,
+        });
+    }
+
+    /// Return the number of frames recorded.
+    pub fn frames(&self) -> u64 {
+        self.shots.iter().map(|s| s.frames).sum()
+    }
+
+    /// Return the number of distinct frames in a row.
+    pub fn shots(&self) -> usize {
+        self.shots.len()
+    }
+
+    /// Return the shots that become frames of the GIF with their delays in
+    /// hundredths of a second. Delays are rounded from the frame clock so
+    /// they add up to the time recorded. A shot starting less than
+    /// `MIN_DELAY` after the one before it is left out, its time going to
+    /// that one.
+    fn gif_frames(&self) -> Vec<(&Shot, u64)> {
+        // Every shot kept, with its start.
+        let mut starts: Vec<(&Shot, u64)> = Vec::new();
+        let mut frame = 0;
+        for shot in &self.shots {
+            let start = centiseconds(frame);
+            match starts.last() {
+                Some(&(_, last)) if start < last + MIN_DELAY => {},
+                _ => starts.push((shot, start)),
+            }
+            frame += shot.frames;
+        }
+        let end = centiseconds(frame);
+        if starts.len() > 1 && starts.last().is_some_and(|&(_, last)| end < last + MIN_DELAY) {
+            starts.pop();
+        }
+        let ends = starts.iter().skip(1).map(|&(_, start)| start).chain(Some(end));
+        starts.iter().zip(ends).map(|(&(shot, start), end)| (shot, end - start)).collect()
+    }
+
+    /// Encode the recording as an animated GIF that loops forever. Return
+    /// string on error.
+    pub fn encode_gif(&self) -> Result<Vec<u8>, String> {
+        let factor = self.upscaler.factor() * self.scale;
+        let (width, height) = ((NUM_COLS * factor) as u16, (NUM_ROWS * factor) as u16);
+        let mut result = Vec::new();
+        {
+            let mut encoder = gif::Encoder::new(&mut result, width, height, &[])
+                .map_err(|e| e.to_string())?;
+            encoder.set_repeat(gif::Repeat::Infinite).map_err(|e| e.to_string())?;
+
+            for (shot, delay) in self.gif_frames() {
+                let image = render(&shot.screen, &shot.palette, &self.upscaler, self.scale);
+                let (pixels, table) = index_colors(&image)?;
+                let mut frame = gif::Frame::from_palette_pixels(width, height, pixels, table, None);
+                frame.delay = delay.min(u16::MAX as u64) as u16;
+                encoder.write_frame(&frame).map_err(|e| e.to_string())?;
+            }
+        }
+        Ok(result)
+    }
+}
@@ -0,0 +1,303 @@
+//! Chip 8 SDL renderer.
+//! (C) Ryan Jeffrey <ryan@ryanmj.xyz>, 2022
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or (at
+// your option) any later version.
+
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use chip8::{FONTSET, NUM_COLS, NUM_ROWS};
+use chip8::framebuffer::Framebuffer;
+use chip8::display::{BYTES_PER_PIXEL, FRAME_BYTES, fit_screen, screen_to_rgb};
+use chip8::filter::{Persistence, Upscaler};
+use chip8::keypad::{KeyPoll, key_position};
+use chip8::launcher::Menu;
+use chip8::palette::Palette;
+use chip8::text::{GLYPH_HEIGHT, GLYPH_WIDTH, text_pixels};
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::Rect;
+use sdl2::render::{Canvas, Texture, TextureCreator};
+use sdl2::video::{Window, WindowContext};
+
+/// Colour of the borders around the screen.
+const LETTERBOX: Color = Color::RGB(0, 0, 0);
+/// Colour of keypad keys that are not held down.
+const KEY_UP: Color = Color::RGB(0x30, 0x30, 0x30);
+/// Colour of keypad keys that are held down.
+const KEY_DOWN: Color = Color::RGB(0xc0, 0xc0, 0xc0);
+/// Outline of the key the program is polling.
+const KEY_POLLED: Color = Color::RGB(0xff, 0xc0, 0x00);
+
+/// Characters per line the launcher menu's text is scaled to fit.
+const MENU_COLUMNS: u32 = 48;
+/// Lines the launcher menu's text is scaled to fit.
+const MENU_ROWS: u32 = 20;
+
+/// State of the on-screen keypad.
+pub struct Keypad {
+    /// Keys held down.
+    pub pressed: [bool; 0x10],
+    /// The key the program polled during the last frame.
+    pub polled: Option<KeyPoll>,
+}
+
+/// Return the area of the on-screen keypad, a square on the right of the
+/// window.
+/// # Arguments
+/// * `output` Width and height of the canvas.
+pub fn keypad_area(output: (u32, u32)) -> Rect {
+    let (width, height) = output;
+    let size = height.min(width);
+    Rect::new((width - size) as i32, 0, size, size)
+}
+
+/// Draw a rectangle outline `thickness` pixels wide inside `rect`. Return
+/// string on error.
+/// # Arguments
+/// * `canvas` SDL canvas to draw to.
+/// * `rect` Outer edge of the outline.
+/// * `thickness` Width of the outline.
+fn draw_outline(canvas: &mut Canvas<Window>, rect: Rect, thickness: u32) -> Result<(), String> {
+    for i in 0..thickness.min(rect.width() / 2).min(rect.height() / 2) {
+        canvas.draw_rect(Rect::new(rect.x() + i as i32, rect.y() + i as i32,
+                                   rect.width() - 2 * i, rect.height() - 2 * i))?;
+    }
+    Ok(())
+}
+
+/// Draw the keypad with its keys labelled in the chip 8 font. Return string
+/// on error.
+/// # Arguments
+/// * `keypad` Keypad state.
+/// * `area` Area of the canvas to draw in.
+/// * `canvas` SDL canvas to draw to.
+fn draw_keypad(keypad: &Keypad, area: Rect, canvas: &mut Canvas<Window>) -> Result<(), String> {
+    let cell = area.width() / 4;
+    let margin = cell / 16;
+    let scale = cell / 12;
+
+    for key in 0..0x10u8 {
+        let (column, row) = key_position(key);
+        let rect = Rect::new(area.x() + (column as u32 * cell + margin) as i32,
+                             area.y() + (row as u32 * cell + margin) as i32,
+                             cell - 2 * margin, cell - 2 * margin);
+        let pressed = keypad.pressed[key as usize];
+        canvas.set_draw_color(if pressed { KEY_DOWN } else { KEY_UP });
+        canvas.fill_rect(rect)?;
+        if keypad.polled == Some(KeyPoll::Key(key)) {
+            canvas.set_draw_color(KEY_POLLED);
+            draw_outline(canvas, rect, margin.max(1))?;
+        }
+
+        // The 4x5 font sprite of the key, centered.
+        canvas.set_draw_color(if pressed { KEY_UP } else { KEY_DOWN });
+        let left = rect.x() + (rect.width() - 4 * scale) as i32 / 2;
+        let top = rect.y() + (rect.height() - 5 * scale) as i32 / 2;
+        for (y, bits) in FONTSET[key as usize * 5..key as usize * 5 + 5].iter().enumerate() {
+            for x in 0..4 {
+                if bits & (0x80 >> x) != 0 {
+                    canvas.fill_rect(Rect::new(left + (x * scale) as i32,
+                                               top + (y as u32 * scale) as i32,
+                                               scale, scale))?;
+                }
+            }
+        }
+    }
+
+    if keypad.polled == Some(KeyPoll::Any) {
+        canvas.set_draw_color(KEY_POLLED);
+        draw_outline(canvas, area, margin.max(1))?;
+    }
+    Ok(())
+}
+
+/// Draw a line of text in the emulator's font. Return string on error.
+/// # Arguments
+/// * `canvas` SDL canvas to draw to.
+/// * `text` The text.
+/// * `x` Left edge of the text.
+/// * `y` Top edge of the text.
+/// * `scale` Size of a font pixel.
+/// * `color` Colour of the text.
+fn draw_text(canvas: &mut Canvas<Window>, text: &str, x: i32, y: i32, scale: u32,
+             color: Color) -> Result<(), String> {
+    let rects: Vec<Rect> = text_pixels(text).iter()
+        .map(|&(px, py)| Rect::new(x + (px as u32 * scale) as i32, y + (py as u32 * scale) as i32,
+                                   scale, scale))
+        .collect();
+    canvas.set_draw_color(color);
+    canvas.fill_rects(&rects)
+}
+
+/// Draws the screen into a streaming texture at the chip 8 resolution, or
+/// larger with an upscaler, which is scaled up to the window in a single
+/// copy, keeping its aspect ratio.
+pub struct Renderer<'a> {
+    /// Texture with `upscaler.factor()` pixels per cell.
+    texture: Texture<'a>,
+    /// RGB pixel data of the texture.
+    pixels: Vec<u8>,
+    /// The screen currently in the texture.
+    uploaded: Option<Framebuffer>,
+    /// Colours of the screen.
+    palette: Palette,
+    /// Only scale cells by whole numbers.
+    integer_scale: bool,
+    /// Phosphor persistence filter, if enabled.
+    persistence: Option<Persistence>,
+    /// The persistence filter changed cells on the last draw.
+    persistence_changed: bool,
+    /// Filter scaling frames before they are uploaded.
+    upscaler: Upscaler,
+}
+
+impl<'a> Renderer<'a> {
+    /// Create the renderer's texture. Return string on error.
+    /// # Arguments
+    /// * `texture_creator` Texture creator of the canvas to draw to.
+    /// * `palette` Colours of the screen.
+    /// * `integer_scale` Only scale cells by whole numbers.
+    /// * `persistence` Number of frames cleared cells take to fade out, 0 to
+    ///   clear them at once.
+    /// * `upscaler` Filter scaling frames before they are uploaded.
+    pub fn new(texture_creator: &'a TextureCreator<WindowContext>, palette: Palette,
+               integer_scale: bool, persistence: u32,
+               upscaler: Upscaler) -> Result<Self, String> {
+        let factor = upscaler.factor() as u32;
+        let texture = texture_creator
+            .create_texture_streaming(PixelFormatEnum::RGB24, NUM_COLS as u32 * factor,
+                                      NUM_ROWS as u32 * factor)
+            .map_err(|e| e.to_string())?;
+        Ok(Self {
+            texture,
+            pixels: vec![0; FRAME_BYTES],
+            uploaded: None,
+            palette,
+            integer_scale,
+            persistence: if persistence > 0 { Some(Persistence::new(persistence)) } else { None },
+            persistence_changed: false,
+            upscaler,
+        })
+    }
+
+    /// Return true if the persistence filter changed cells on the last
+    /// draw, so they may still be fading out and the screen must be drawn
+    /// again even if it does not change.
+    pub fn persistence_changed(&self) -> bool {
+        self.persistence_changed
+    }
+
+    /// Change the colours of the screen, taking effect on the next draw.
+    /// # Arguments
+    /// * `palette` Colours of the screen.
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+        self.uploaded = None;
+    }
+
+    /// Draw the launcher menu in the palette's colours, a header, the ROMs
+    /// with the selected one highlighted and a footer, with text as large
+    /// as the window allows. Return string on error.
+    /// # Arguments
+    /// * `menu` The launcher menu.
+    /// * `header` Text above the ROMs.
+    /// * `footer` Text at the bottom of the window.
+    /// * `canvas` SDL canvas to draw to.
+    pub fn draw_menu(&self, menu: &mut Menu, header: &str, footer: &str,
+                     canvas: &mut Canvas<Window>) -> Result<(), String> {
+        let (width, height) = canvas.output_size()?;
+        let (char_width, line_height) = (GLYPH_WIDTH as u32 + 1, GLYPH_HEIGHT as u32 + 2);
+        let scale = (width / (MENU_COLUMNS * char_width))
+            .min(height / (MENU_ROWS * line_height)).max(1);
+        let (char_width, line_height) = (char_width * scale, line_height * scale);
+        let margin = char_width;
+        let columns = (width.saturating_sub(2 * margin) / char_width) as usize;
+        // Header, a blank line, the ROMs, a blank line and the footer.
+        let rows = (height.saturating_sub(2 * margin) / line_height).saturating_sub(4) as usize;
+        let line_y = |line: u32| (margin + line * line_height) as i32;
+        let (foreground, background) = (self.palette.fill, self.palette.background);
+        let foreground = Color::RGB(foreground[0], foreground[1], foreground[2]);
+        let background = Color::RGB(background[0], background[1], background[2]);
+
+        canvas.set_draw_color(background);
+        canvas.clear();
+        draw_text(canvas, header, margin as i32, line_y(0), scale, foreground)?;
+        let (lines, selected) = menu.lines(columns, rows);
+        if lines.is_empty() {
+            draw_text(canvas, "No ROMs found", margin as i32, line_y(2), scale, foreground)?;
+        }
+        for (i, line) in lines.iter().enumerate() {
+            let y = line_y(i as u32 + 2);
+            let color = if selected == Some(i) {
+                canvas.set_draw_color(foreground);
+                canvas.fill_rect(Rect::new(0, y - scale as i32, width, line_height))?;
+                background
+            } else {
+                foreground
+            };
+            draw_text(canvas, line, margin as i32, y, scale, color)?;
+        }
+        let footer_y = height.saturating_sub(margin + GLYPH_HEIGHT as u32 * scale) as i32;
+        // Long errors are cut to the width of the window.
+        let footer: String = footer.chars().take(columns).collect();
+        draw_text(canvas, &footer, margin as i32, footer_y, scale, foreground)?;
+        canvas.present();
+        Ok(())
+    }
+
+    /// Draw the screen to the canvas, uploading it to the texture only if it
+    /// changed since the last draw, and the keypad next to it if there is
+    /// one. With persistence, this must be called once per frame while
+    /// `persistence_changed`. Return string on error.
+    /// # Arguments
+    /// * `screen` The emulator screen.
+    /// * `keypad` Keypad state, None to hide the keypad.
+    /// * `canvas` SDL canvas to draw to.
+    pub fn draw(&mut self, screen: &Framebuffer, keypad: Option<&Keypad>,
+                canvas: &mut Canvas<Window>) -> Result<(), String> {
+        let changed = match &mut self.persistence {
+            Some(persistence) => {
+                self.persistence_changed = persistence.update(screen);
+                self.persistence_changed || self.uploaded.is_none()
+            },
+            None => self.uploaded.as_ref() != Some(screen),
+        };
+        if changed {
+            match &self.persistence {
+                Some(persistence) => persistence.to_rgb(&self.palette, &mut self.pixels),
+                None => screen_to_rgb(screen, &self.palette, &mut self.pixels),
+            }
+            let pitch = NUM_COLS * self.upscaler.factor() * BYTES_PER_PIXEL;
+            if self.upscaler == Upscaler::default() {
+                self.texture.update(None, &self.pixels, pitch)
+            } else {
+                self.texture.update(None, &self.upscaler.apply(&self.pixels, NUM_COLS, NUM_ROWS),
+                                    pitch)
+            }.map_err(|e| e.to_string())?;
+            self.uploaded = Some(screen.clone());
+        }
+
+        canvas.set_draw_color(LETTERBOX);
+        canvas.clear();
+        let (width, height) = canvas.output_size()?;
+        let screen_width = match keypad {
+            Some(keypad) => {
+                let area = keypad_area((width, height));
+                draw_keypad(keypad, area, canvas)?;
+                area.x() as u32
+            },
+            None => width,
+        };
+        let (x, y, w, h) = fit_screen(screen_width, height, self.integer_scale);
+        canvas.copy(&self.texture, None, Rect::new(x, y, w, h))?;
+        canvas.present();
+        Ok(())
+    }
+}
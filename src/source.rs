@@ -0,0 +1,315 @@
+//! Chip 8 ROM sources and formats.
+//! (C) Ryan Jeffrey <ryan@ryanmj.xyz>, 2022
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or (at
+// your option) any later version.
+
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use std::fmt;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use cartridge::Cartridge;
+use launcher::ROM_EXTENSIONS;
+use machine::Machine;
+use zip::ZipArchive;
+
+/// Extensions of text files, read as hex listings if they hold nothing else.
+const TEXT_EXTENSIONS: [&str; 2] = ["txt", "hex"];
+
+/// Magic bytes ZIP archives start with, the second for empty ones.
+const ZIP_MAGIC: [&[u8]; 2] = [b"PK\x03\x04", b"PK\x05\x06"];
+
+/// Format of a ROM file, told apart by its contents.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum Format {
+    /// The bytes as they are loaded.
+    Binary,
+    /// An Octo cartridge, whose program is assembled.
+    Cartridge,
+    /// Intel HEX records, whose addresses are those the bytes are loaded
+    /// at.
+    IntelHex,
+    /// Hex digits separated by whitespace, two per byte, as in type-in
+    /// listings.
+    HexText,
+}
+
+impl Format {
+    /// Tell the format of a ROM file from its contents. Raw binaries can be
+    /// made of hex digits and spaces too, so only text files and files of an
+    /// odd length, which no ROM of whole instructions has, are read as hex
+    /// listings.
+    /// # Arguments
+    /// * `bytes` Contents of the file.
+    /// * `name` Name of the file.
+    pub fn detect(bytes: &[u8], name: &Path) -> Self {
+        if Cartridge::is_cartridge(bytes) {
+            return Format::Cartridge;
+        }
+        let text = match std::str::from_utf8(bytes) {
+            Ok(text) if !text.trim().is_empty() => text,
+            _ => return Format::Binary,
+        };
+        let hex = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_hexdigit());
+        let text_file = name.extension()
+            .is_some_and(|e| TEXT_EXTENSIONS.contains(&e.to_string_lossy().to_lowercase().as_str()));
+        if text.lines().map(str::trim).filter(|l| !l.is_empty())
+            .all(|l| l.strip_prefix(':').is_some_and(hex)) {
+            Format::IntelHex
+        } else if (text_file || !bytes.len().is_multiple_of(2)) && text.split_whitespace()
+            .all(|t| hex(t.strip_prefix("0x").unwrap_or(t)) && t.len().is_multiple_of(2)) {
+            Format::HexText
+        } else {
+            Format::Binary
+        }
+    }
+
+    /// Return the bytes to load at the machine's load address from the
+    /// contents of a file in this format. Return string on error.
+    /// # Arguments
+    /// * `bytes` Contents of the file.
+    /// * `machine` Memory layout the ROM is loaded in.
+    pub fn decode(self, bytes: Vec<u8>, machine: &Machine) -> Result<Vec<u8>, String> {
+        let text = || String::from_utf8_lossy(&bytes).into_owned();
+        match self {
+            Format::Binary => Ok(bytes),
+            Format::Cartridge => Cartridge::decode(&bytes)?.rom(),
+            Format::IntelHex => decode_intel_hex(&text(), machine),
+            Format::HexText => decode_hex_text(&text()),
+        }
+    }
+}
+
+/// Return the bytes of hex digit pairs. Return string if there are others.
+/// # Arguments
+/// * `digits` The hex digits.
+fn hex_bytes(digits: &str) -> Result<Vec<u8>, String> {
+    if !digits.len().is_multiple_of(2) || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("Invalid hex {}.", digits));
+    }
+    (0..digits.len()).step_by(2)
+        .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).map_err(|e| e.to_string()))
+        .collect()
+}
+
+/// Decode whitespace separated hex, optionally with `0x` prefixes, such as
+/// `00E0 A22A` or `0x00 0xE0`. Return string on error.
+/// # Arguments
+/// * `text` The hex text.
+fn decode_hex_text(text: &str) -> Result<Vec<u8>, String> {
+    let mut result = Vec::new();
+    for token in text.split_whitespace() {
+        result.extend(hex_bytes(token.strip_prefix("0x").unwrap_or(token))?);
+    }
+    Ok(result)
+}
+
+/// Decode Intel HEX records into the bytes from the load address on, with
+/// gaps between records cleared. Return string on error, with the line it is
+/// on.
+/// # Arguments
+/// * `text` The records.
+/// * `machine` Memory layout the ROM is loaded in.
+fn decode_intel_hex(text: &str, machine: &Machine) -> Result<Vec<u8>, String> {
+    let program_start = machine.load_address as usize;
+    let mut result = Vec::new();
+    // Added to record addresses by extended address records.
+    let mut base = 0;
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let error = |e: String| format!("Line {}: {}", n + 1, e);
+        let record = hex_bytes(line.strip_prefix(':').unwrap_or(line)).map_err(error)?;
+        if record.len() < 5 || record.len() != 5 + record[0] as usize {
+            return Err(error(String::from("The record length is wrong.")));
+        }
+        if record.iter().fold(0u8, |sum, &b| sum.wrapping_add(b)) != 0 {
+            return Err(error(String::from("The checksum is wrong.")));
+        }
+        let address = (record[1] as usize) << 8 | record[2] as usize;
+        let data = &record[4..record.len() - 1];
+        let word = || data.get(..2).map(|d| (d[0] as usize) << 8 | d[1] as usize)
+            .ok_or_else(|| error(String::from("The extended address is missing.")));
+        match record[3] {
+            0 => {
+                let start = base + address;
+                if start < program_start || start + data.len() > machine.memory_size {
+                    return Err(error(format!("Address 0x{:X} is outside the program.", start)));
+                }
+                let offset = start - program_start;
+                if result.len() < offset + data.len() {
+                    result.resize(offset + data.len(), 0);
+                }
+                result[offset..offset + data.len()].copy_from_slice(data);
+            },
+            1 => break,
+            2 => base = word()? << 4,
+            4 => base = word()? << 16,
+            // Start addresses, the program starts at the load address.
+            3 | 5 => {},
+            kind => return Err(error(format!("Unknown record type {:02X}.", kind))),
+        }
+    }
+    Ok(result)
+}
+
+/// Return whether a ZIP entry is a ROM: one of the launcher's extensions,
+/// or none as in old game packs.
+/// # Arguments
+/// * `name` Path of the entry in the archive.
+fn is_rom_entry(name: &str) -> bool {
+    let path = Path::new(name);
+    if name.ends_with('/') || name.starts_with("__MACOSX/") {
+        return false;
+    }
+    match path.extension().and_then(|e| e.to_str()) {
+        Some(extension) => ROM_EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str()),
+        None => true,
+    }
+}
+
+/// Return whether a file is a ZIP archive.
+/// # Arguments
+/// * `path` Path of the file.
+pub fn is_zip<P: AsRef<Path>>(path: P) -> bool {
+    let mut magic = [0; 4];
+    File::open(path).and_then(|mut f| f.read_exact(&mut magic)).is_ok() &&
+        ZIP_MAGIC.iter().any(|m| *m == magic)
+}
+
+/// Open a ZIP archive. Return string on error.
+/// # Arguments
+/// * `path` Path of the archive.
+fn open_zip(path: &Path) -> Result<ZipArchive<File>, String> {
+    let error = |e: String| format!("{}: {}", path.display(), e);
+    let file = File::open(path).map_err(|e| error(e.to_string()))?;
+    ZipArchive::new(file).map_err(|e| error(e.to_string()))
+}
+
+/// Return the names of the ROMs in a ZIP archive, sorted. Return string on
+/// error.
+/// # Arguments
+/// * `path` Path of the archive.
+pub fn zip_entries<P: AsRef<Path>>(path: P) -> Result<Vec<String>, String> {
+    let archive = open_zip(path.as_ref())?;
+    let mut result: Vec<String> = archive.file_names().filter(|n| is_rom_entry(n))
+        .map(String::from)
+        .collect();
+    result.sort();
+    Ok(result)
+}
+
+/// Where the bytes of a ROM come from.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum RomSource {
+    /// A ROM file, in any format.
+    File(PathBuf),
+    /// A ROM in a ZIP archive, in any format.
+    Zip {
+        /// Path of the archive.
+        archive: PathBuf,
+        /// Name of the ROM in the archive.
+        entry: String,
+    },
+}
+
+impl RomSource {
+    /// Return the source of a ROM file or a ROM in a ZIP archive. Archives
+    /// holding one ROM need no entry. Return string on error, listing the
+    /// ROMs of archives holding several if no entry is given.
+    /// # Arguments
+    /// * `path` Path of the ROM file or archive.
+    /// * `entry` Name of the ROM in the archive.
+    pub fn open<P: AsRef<Path>>(path: P, entry: Option<&str>) -> Result<Self, String> {
+        let path = path.as_ref();
+        if !is_zip(path) {
+            return match entry {
+                Some(_) => Err(format!("{} is not a ZIP archive.", path.display())),
+                None => Ok(RomSource::File(path.to_path_buf())),
+            };
+        }
+        let entries = zip_entries(path)?;
+        let entry = match (entry, entries.len()) {
+            (Some(entry), _) if entries.iter().any(|e| e == entry) => String::from(entry),
+            (Some(entry), _) => return Err(format!("{} has no ROM {}.", path.display(), entry)),
+            (None, 1) => entries[0].clone(),
+            (None, 0) => return Err(format!("{} has no ROMs.", path.display())),
+            (None, _) => return Err(format!("{} has several ROMs, choose one of {}.",
+                                            path.display(), entries.join(", "))),
+        };
+        Ok(RomSource::Zip { archive: path.to_path_buf(), entry })
+    }
+
+    /// Return the path of the ROM file, or of the archive holding it.
+    pub fn path(&self) -> &Path {
+        match self {
+            RomSource::File(path) => path,
+            RomSource::Zip { archive, .. } => archive,
+        }
+    }
+
+    /// Return the name of the ROM, its path or its name in the archive.
+    pub fn name(&self) -> &Path {
+        match self {
+            RomSource::File(path) => path,
+            RomSource::Zip { entry, .. } => Path::new(entry),
+        }
+    }
+
+    /// Return the name of the ROM in its archive, None for ROM files.
+    pub fn entry(&self) -> Option<&str> {
+        match self {
+            RomSource::File(_) => None,
+            RomSource::Zip { entry, .. } => Some(entry),
+        }
+    }
+
+    /// Return the contents of the ROM file or archive entry, before they
+    /// are decoded. Return string on error.
+    pub fn bytes(&self) -> Result<Vec<u8>, String> {
+        match self {
+            RomSource::File(path) => fs::read(path)
+                .map_err(|e| format!("{}: {}", path.display(), e)),
+            RomSource::Zip { archive, entry } => {
+                let error = |e: String| format!("{}: {}: {}", archive.display(), entry, e);
+                let mut zip = open_zip(archive)?;
+                let mut file = zip.by_name(entry).map_err(|e| error(e.to_string()))?;
+                let mut result = Vec::new();
+                file.read_to_end(&mut result).map_err(|e| error(e.to_string()))?;
+                Ok(result)
+            },
+        }
+    }
+
+    /// Return the bytes to load at the machine's load address, decoded from
+    /// the ROM's format. Return string on error.
+    /// # Arguments
+    /// * `machine` Memory layout the ROM is loaded in.
+    pub fn read(&self, machine: &Machine) -> Result<Vec<u8>, String> {
+        let bytes = self.bytes()?;
+        Format::detect(&bytes, self.name()).decode(bytes, machine)
+            .map_err(|e| format!("{}: {}", self.name().display(), e))
+    }
+}
+
+impl fmt::Display for RomSource {
+    /// The path of the ROM file, or of the archive with the ROM's name.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RomSource::File(path) => write!(f, "{}", path.display()),
+            RomSource::Zip { archive, entry } => write!(f, "{} ({})", archive.display(), entry),
+        }
+    }
+}
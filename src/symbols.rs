@@ -0,0 +1,150 @@
+//! Chip 8 Octo symbol files.
+//! (C) Ryan Jeffrey <ryan@ryanmj.xyz>, 2022
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or (at
+// your option) any later version.
+
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// A memory region shown live while the program runs.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Monitor {
+    /// Name of the monitor.
+    pub name: String,
+    /// First address of the region.
+    pub address: u16,
+    /// Length of the region in bytes.
+    pub length: u16,
+}
+
+/// Labels, breakpoints and monitors exported by Octo.
+/// The file has one entry per line, `#` starts a comment:
+/// * `name address` A label.
+/// * `:breakpoint name address` A breakpoint.
+/// * `:monitor name address length` A memory monitor.
+///
+/// Addresses and lengths are decimal or `0x` prefixed hexadecimal.
+#[derive(PartialEq, Eq, Clone, Debug, Default)]
+pub struct SymbolTable {
+    /// Label names keyed by address.
+    labels: BTreeMap<u16, String>,
+    /// Breakpoints declared in the source, name and address.
+    pub breakpoints: Vec<(String, u16)>,
+    /// Monitors declared in the source.
+    pub monitors: Vec<Monitor>,
+}
+
+/// Parse a decimal or `0x` prefixed hexadecimal number.
+/// # Arguments
+/// * `s` The number.
+fn parse_number(s: &str) -> Result<u16, String> {
+    let result = if let Some(hex) = s.strip_prefix("0x") {
+        u16::from_str_radix(hex, 16)
+    } else {
+        s.parse::<u16>()
+    };
+    result.map_err(|_| format!("Invalid number {}.", s))
+}
+
+impl SymbolTable {
+    /// Create an empty symbol table.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse the text of a symbol file. Return string on error.
+    /// # Arguments
+    /// * `text` Contents of the symbol file.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut result = Self::new();
+
+        for (n, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("");
+            let words = line.split_whitespace().collect::<Vec<&str>>();
+            let error = |e: String| format!("Line {}: {}", n + 1, e);
+            match words.as_slice() {
+                [] => {},
+                [":breakpoint", name, address] => {
+                    let address = parse_number(address).map_err(error)?;
+                    result.breakpoints.push((name.to_string(), address));
+                },
+                [":monitor", name, address, length] => {
+                    result.monitors.push(Monitor {
+                        name: name.to_string(),
+                        address: parse_number(address).map_err(error)?,
+                        length: parse_number(length).map_err(error)?,
+                    });
+                },
+                [name, address] if !name.starts_with(':') => {
+                    let address = parse_number(address).map_err(error)?;
+                    result.labels.entry(address).or_insert_with(|| name.to_string());
+                },
+                _ => return Err(error(format!("Invalid symbol {}.", line.trim()))),
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Load a symbol file. Return string on error.
+    /// # Arguments
+    /// * `path` Path of the symbol file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let text = fs::read_to_string(path.as_ref())
+            .map_err(|e| format!("{}: {}", path.as_ref().display(), e))?;
+        Self::parse(&text)
+    }
+
+    /// Load the symbol file next to `rom`, the ROM's path with a .sym
+    /// extension. Return an empty table if there is none.
+    /// # Arguments
+    /// * `rom` Path of the ROM file.
+    pub fn load_for_rom(rom: &str) -> Result<Self, String> {
+        let path = Path::new(rom).with_extension("sym");
+        if path.is_file() {
+            Self::load(path)
+        } else {
+            Ok(Self::new())
+        }
+    }
+
+    /// Return the label at exactly `address`.
+    /// # Arguments
+    /// * `address` Memory address.
+    pub fn label(&self, address: u16) -> Option<&str> {
+        self.labels.get(&address).map(|s| s.as_str())
+    }
+
+    /// Return the label at `address`, or the address in hex if it has none.
+    /// # Arguments
+    /// * `address` Memory address.
+    pub fn name(&self, address: u16) -> String {
+        match self.label(address) {
+            Some(l) => l.to_string(),
+            None => format!("0x{:03X}", address),
+        }
+    }
+
+    /// Describe `address` relative to the closest label before it, e.g.
+    /// `draw-player+4`. Use the address in hex if there is no such label.
+    /// # Arguments
+    /// * `address` Memory address.
+    pub fn describe(&self, address: u16) -> String {
+        match self.labels.range(..=address).next_back() {
+            Some((&a, l)) if a == address => l.clone(),
+            Some((&a, l)) => format!("{}+{}", l, address - a),
+            None => format!("0x{:03X}", address),
+        }
+    }
+}
@@ -0,0 +1,111 @@
+//! Chip 8 emulator text font.
+//! (C) Ryan Jeffrey <ryan@ryanmj.xyz>, 2022
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or (at
+// your option) any later version.
+
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+/// Width of a glyph in pixels.
+pub const GLYPH_WIDTH: usize = 3;
+/// Height of a glyph in pixels.
+pub const GLYPH_HEIGHT: usize = 5;
+
+/// Glyphs of the emulator's own text, such as the launcher menu, in the
+/// layout of `FONTSET`: one byte per row, the leftmost pixel in the top bit.
+/// Lowercase letters are drawn as uppercase ones.
+const GLYPHS: [(char, [u8; GLYPH_HEIGHT]); 57] = [
+    (' ', [0x00, 0x00, 0x00, 0x00, 0x00]),
+    ('A', [0x40, 0xA0, 0xE0, 0xA0, 0xA0]),
+    ('B', [0xC0, 0xA0, 0xC0, 0xA0, 0xC0]),
+    ('C', [0x60, 0x80, 0x80, 0x80, 0x60]),
+    ('D', [0xC0, 0xA0, 0xA0, 0xA0, 0xC0]),
+    ('E', [0xE0, 0x80, 0xC0, 0x80, 0xE0]),
+    ('F', [0xE0, 0x80, 0xC0, 0x80, 0x80]),
+    ('G', [0x60, 0x80, 0xA0, 0xA0, 0x60]),
+    ('H', [0xA0, 0xA0, 0xE0, 0xA0, 0xA0]),
+    ('I', [0xE0, 0x40, 0x40, 0x40, 0xE0]),
+    ('J', [0x20, 0x20, 0x20, 0xA0, 0x40]),
+    ('K', [0xA0, 0xA0, 0xC0, 0xA0, 0xA0]),
+    ('L', [0x80, 0x80, 0x80, 0x80, 0xE0]),
+    ('M', [0xA0, 0xE0, 0xE0, 0xA0, 0xA0]),
+    ('N', [0xC0, 0xA0, 0xA0, 0xA0, 0xA0]),
+    ('O', [0x40, 0xA0, 0xA0, 0xA0, 0x40]),
+    ('P', [0xC0, 0xA0, 0xC0, 0x80, 0x80]),
+    ('Q', [0x40, 0xA0, 0xA0, 0xC0, 0x60]),
+    ('R', [0xC0, 0xA0, 0xC0, 0xA0, 0xA0]),
+    ('S', [0x60, 0x80, 0x40, 0x20, 0xC0]),
+    ('T', [0xE0, 0x40, 0x40, 0x40, 0x40]),
+    ('U', [0xA0, 0xA0, 0xA0, 0xA0, 0xE0]),
+    ('V', [0xA0, 0xA0, 0xA0, 0xA0, 0x40]),
+    ('W', [0xA0, 0xA0, 0xE0, 0xE0, 0xA0]),
+    ('X', [0xA0, 0xA0, 0x40, 0xA0, 0xA0]),
+    ('Y', [0xA0, 0xA0, 0x40, 0x40, 0x40]),
+    ('Z', [0xE0, 0x20, 0x40, 0x80, 0xE0]),
+    ('0', [0xE0, 0xA0, 0xA0, 0xA0, 0xE0]),
+    ('1', [0x40, 0xC0, 0x40, 0x40, 0xE0]),
+    ('2', [0xC0, 0x20, 0x40, 0x80, 0xE0]),
+    ('3', [0xC0, 0x20, 0x40, 0x20, 0xC0]),
+    ('4', [0xA0, 0xA0, 0xE0, 0x20, 0x20]),
+    ('5', [0xE0, 0x80, 0xC0, 0x20, 0xC0]),
+    ('6', [0x60, 0x80, 0xE0, 0xA0, 0xE0]),
+    ('7', [0xE0, 0x20, 0x40, 0x40, 0x40]),
+    ('8', [0xE0, 0xA0, 0xE0, 0xA0, 0xE0]),
+    ('9', [0xE0, 0xA0, 0xE0, 0x20, 0xC0]),
+    ('.', [0x00, 0x00, 0x00, 0x00, 0x40]),
+    (',', [0x00, 0x00, 0x00, 0x40, 0x80]),
+    ('-', [0x00, 0x00, 0xE0, 0x00, 0x00]),
+    ('_', [0x00, 0x00, 0x00, 0x00, 0xE0]),
+    (':', [0x00, 0x40, 0x00, 0x40, 0x00]),
+    ('/', [0x20, 0x20, 0x40, 0x80, 0x80]),
+    ('(', [0x40, 0x80, 0x80, 0x80, 0x40]),
+    (')', [0x40, 0x20, 0x20, 0x20, 0x40]),
+    ('<', [0x20, 0x40, 0x80, 0x40, 0x20]),
+    ('>', [0x80, 0x40, 0x20, 0x40, 0x80]),
+    ('!', [0x40, 0x40, 0x40, 0x00, 0x40]),
+    ('?', [0xC0, 0x20, 0x40, 0x00, 0x40]),
+    ('+', [0x00, 0x40, 0xE0, 0x40, 0x00]),
+    ('=', [0x00, 0xE0, 0x00, 0xE0, 0x00]),
+    ('\'', [0x40, 0x40, 0x00, 0x00, 0x00]),
+    ('"', [0xA0, 0xA0, 0x00, 0x00, 0x00]),
+    ('#', [0xA0, 0xE0, 0xA0, 0xE0, 0xA0]),
+    ('*', [0xA0, 0x40, 0xE0, 0x40, 0xA0]),
+    ('[', [0xC0, 0x80, 0x80, 0x80, 0xC0]),
+    (']', [0x60, 0x20, 0x20, 0x20, 0x60]),
+];
+
+/// Return the glyph of a character, a question mark for characters the font
+/// does not have.
+/// # Arguments
+/// * `c` The character.
+pub fn glyph(c: char) -> [u8; GLYPH_HEIGHT] {
+    let c = c.to_ascii_uppercase();
+    GLYPHS.iter().find(|(g, _)| *g == c).or_else(|| GLYPHS.iter().find(|(g, _)| *g == '?'))
+        .map(|(_, rows)| *rows)
+        .unwrap_or_default()
+}
+
+/// Return the pixels of a line of text set in the font, as column and row
+/// from its top left corner, with one pixel between characters.
+/// # Arguments
+/// * `text` The text.
+pub fn text_pixels(text: &str) -> Vec<(usize, usize)> {
+    let mut result = Vec::new();
+    for (i, c) in text.chars().enumerate() {
+        for (y, bits) in glyph(c).iter().enumerate() {
+            for x in 0..GLYPH_WIDTH {
+                if bits & (0x80 >> x) != 0 {
+                    result.push((i * (GLYPH_WIDTH + 1) + x, y));
+                }
+            }
+        }
+    }
+    result
+}
@@ -0,0 +1,266 @@
+//! Chip 8 emulator terminal frontend.
+//! (C) Ryan Jeffrey <ryan@ryanmj.xyz>, 2022
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or (at
+// your option) any later version.
+
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+extern crate chip8;
+extern crate crossterm;
+
+use std::env;
+use std::io::{self, Stdout, Write};
+use std::time::Duration;
+
+use chip8::{Instruction, InterpreterData, NUM_COLS, emulate, get_program};
+use chip8::debugger::Debugger;
+use chip8::display::screen_to_half_blocks;
+use chip8::keymap::Keymap;
+use chip8::options::Options;
+use chip8::palette::Palette;
+use chip8::symbols::SymbolTable;
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers,
+                       KeyboardEnhancementFlags, PopKeyboardEnhancementFlags,
+                       PushKeyboardEnhancementFlags};
+use crossterm::style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+
+/// Terminals without key release events repeat held keys, a key counts as
+/// held for this many frames after it was last seen.
+const HOLD_FRAMES: u32 = 6;
+
+/// Column of the register pane.
+const PANE_COLUMN: u16 = NUM_COLS as u16 + 2;
+
+/// Return the keymap name of a terminal key, the SDL scancode name of the
+/// same key.
+/// # Arguments
+/// * `code` Terminal key.
+fn key_name(code: KeyCode) -> Option<String> {
+    Some(match code {
+        KeyCode::Char(' ') => String::from("Space"),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::Up => String::from("Up"),
+        KeyCode::Down => String::from("Down"),
+        KeyCode::Left => String::from("Left"),
+        KeyCode::Right => String::from("Right"),
+        KeyCode::Enter => String::from("Return"),
+        KeyCode::Tab => String::from("Tab"),
+        KeyCode::Backspace => String::from("Backspace"),
+        _ => return None,
+    })
+}
+
+/// Convert a palette colour for the terminal.
+/// # Arguments
+/// * `color` RGB colour.
+fn term_color(color: [u8; 3]) -> Color {
+    Color::Rgb { r: color[0], g: color[1], b: color[2] }
+}
+
+/// Convert an IO error to string.
+/// # Arguments
+/// * `e` The error.
+fn io_error(e: io::Error) -> String {
+    e.to_string()
+}
+
+/// Terminal state and what is on it.
+struct Terminal {
+    stdout: Stdout,
+    /// The terminal reports key releases.
+    releases: bool,
+    /// Lines of the screen currently on the terminal.
+    drawn: Vec<String>,
+    /// Lines of the register pane currently on the terminal.
+    drawn_pane: Vec<String>,
+}
+
+impl Terminal {
+    /// Switch to raw mode on the alternate screen. Return string on error.
+    fn new() -> Result<Self, String> {
+        let mut stdout = io::stdout();
+        terminal::enable_raw_mode().map_err(io_error)?;
+        crossterm::execute!(stdout, EnterAlternateScreen, Hide, Clear(ClearType::All))
+            .map_err(io_error)?;
+        let releases = terminal::supports_keyboard_enhancement().unwrap_or(false);
+        if releases {
+            crossterm::execute!(stdout, PushKeyboardEnhancementFlags(
+                KeyboardEnhancementFlags::REPORT_EVENT_TYPES)).map_err(io_error)?;
+        }
+        Ok(Self {
+            stdout,
+            releases,
+            drawn: Vec::new(),
+            drawn_pane: Vec::new(),
+        })
+    }
+
+    /// Draw the screen and the lines of the register pane that changed. Return
+    /// string on error.
+    /// # Arguments
+    /// * `emu_state` The emulator state.
+    /// * `palette` Colours of the screen.
+    /// * `pane` Lines of the register pane.
+    fn draw(&mut self, emu_state: &InterpreterData, palette: &Palette,
+            pane: &[String]) -> Result<(), String> {
+        let lines = screen_to_half_blocks(&emu_state.screen);
+        if lines != self.drawn {
+            crossterm::queue!(self.stdout, SetForegroundColor(term_color(palette.fill)),
+                              SetBackgroundColor(term_color(palette.background)))
+                .map_err(io_error)?;
+            for (row, line) in lines.iter().enumerate() {
+                crossterm::queue!(self.stdout, MoveTo(0, row as u16), Print(line))
+                    .map_err(io_error)?;
+            }
+            crossterm::queue!(self.stdout, ResetColor).map_err(io_error)?;
+            self.drawn = lines;
+        }
+        for (row, line) in pane.iter().enumerate() {
+            if self.drawn_pane.get(row) != Some(line) {
+                crossterm::queue!(self.stdout, MoveTo(PANE_COLUMN, row as u16), Print(line),
+                                  Clear(ClearType::UntilNewLine)).map_err(io_error)?;
+            }
+        }
+        self.drawn_pane = pane.to_vec();
+        self.stdout.flush().map_err(io_error)
+    }
+}
+
+impl Drop for Terminal {
+    /// Give the terminal back in the state it was found.
+    fn drop(&mut self) {
+        if self.releases {
+            let _ = crossterm::execute!(self.stdout, PopKeyboardEnhancementFlags);
+        }
+        let _ = crossterm::execute!(self.stdout, ResetColor, Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Run the emulation in the terminal until Escape or Ctrl-C is pressed.
+/// Return string on error.
+/// # Arguments
+/// * `program` The chip8 program as Instructions.
+/// * `emu_state` The emulator state, with the program loaded.
+/// * `keymap` Keyboard bindings.
+/// * `palette` Colours of the screen.
+/// * `debugger` The debugger.
+/// * `speed` Instructions run per second.
+fn run(program: &[Instruction], emu_state: &mut InterpreterData, keymap: &Keymap,
+       palette: &Palette, debugger: &mut Debugger, speed: u64) -> Result<(), String> {
+    let mut terminal = Terminal::new()?;
+    // Frames left that each key is held for.
+    let mut held = [0u32; 0x10];
+    let mut time_passed = Duration::new(0, 0);
+    let mut status = String::from("Esc to quit");
+
+    loop {
+        let mut step = false;
+        let cur_pressed_keys = {
+            let mut keys = [false; 0x10];
+            for (k, frames) in keys.iter_mut().zip(held.iter()) {
+                *k = *frames > 0;
+            }
+            keys
+        };
+
+        if !debugger.paused {
+            if let Some(name) = debugger.check_breakpoint(emu_state) {
+                status = format!("Breakpoint {}, F5 to continue, F6 to step", name);
+            }
+        }
+
+        if !debugger.paused {
+            emulate(program, emu_state, &cur_pressed_keys);
+        }
+        if let Some(error) = debugger.check_error(emu_state) {
+            status = format!("{} Paused, F5 or F6 to retry", error);
+        }
+
+        if time_passed > Duration::from_millis(1000 / 60) {
+            time_passed = Duration::new(0, 0);
+
+            while event::poll(Duration::from_secs(0)).map_err(io_error)? {
+                let key = match event::read().map_err(io_error)? {
+                    Event::Key(key) => key,
+                    _ => continue,
+                };
+                match key {
+                    KeyEvent { code: KeyCode::Esc, .. } => return Ok(()),
+                    KeyEvent { code: KeyCode::Char('c'), modifiers, .. }
+                    if modifiers.contains(KeyModifiers::CONTROL) => return Ok(()),
+                    KeyEvent { code: KeyCode::F(5), kind: KeyEventKind::Press, .. } => {
+                        debugger.resume();
+                        status = String::from("Esc to quit");
+                    },
+                    KeyEvent { code: KeyCode::F(6), kind: KeyEventKind::Press, .. } => {
+                        step = debugger.paused;
+                    },
+                    KeyEvent { code, kind, .. } => {
+                        if let Some(k) = key_name(code).and_then(|name| keymap.key(&name)) {
+                            held[k as usize] = match kind {
+                                KeyEventKind::Release => 0,
+                                _ if terminal.releases => u32::MAX,
+                                _ => HOLD_FRAMES,
+                            };
+                        }
+                    },
+                }
+            }
+            if step {
+                emulate(program, emu_state, &cur_pressed_keys);
+                if let Some(error) = debugger.check_error(emu_state) {
+                    status = format!("{} Paused, F5 or F6 to retry", error);
+                }
+            }
+
+            let mut pane = debugger.state_lines(emu_state);
+            pane.push(String::new());
+            pane.push(status.clone());
+            terminal.draw(emu_state, palette, &pane)?;
+
+            if !debugger.paused {
+                emu_state.tick_frame();
+            }
+            if !terminal.releases {
+                for frames in held.iter_mut() {
+                    *frames = frames.saturating_sub(1);
+                }
+            }
+        }
+
+        // Rate of speed instructions per second.
+        let sleep_for = Duration::from_nanos(1_000_000_000 / speed);
+        std::thread::sleep(sleep_for);
+        time_passed += sleep_for;
+    }
+}
+
+/// Run the emulation. Return string on error.
+fn main() -> Result<(), String> {
+    let options = Options::parse(env::args().skip(1))?.with_cartridge()?;
+    let (program, raw_program) = get_program(&options.source()?, &options.patches, &options.machine)?;
+    let symbols = match &options.symbols {
+        Some(path) => SymbolTable::load(path)?,
+        None => SymbolTable::load_for_rom(options.rom()?)?,
+    };
+    let keymap = options.keymap()?;
+    let palette = options.palette()?;
+
+    let mut emu_state = InterpreterData::with_machine(options.machine.clone());
+    emu_state.load(&raw_program);
+    emu_state.quirks = options.quirks;
+    let mut debugger = Debugger::new(symbols, false);
+
+    run(&program, &mut emu_state, &keymap, &palette, &mut debugger, options.speed)
+}
@@ -0,0 +1,72 @@
+//! Chip 8 ROM file watching.
+//! (C) Ryan Jeffrey <ryan@ryanmj.xyz>, 2022
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or (at
+// your option) any later version.
+
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// What identifies a version of a file, its modification time and size,
+/// None if it cannot be read.
+type Stamp = Option<(SystemTime, u64)>;
+
+/// Return the stamp of a file.
+/// # Arguments
+/// * `path` Path of the file.
+fn stamp(path: &Path) -> Stamp {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// Notices when a file is rebuilt by polling its modification time and
+/// size, without depending on platform file notifications.
+pub struct FileWatcher {
+    path: PathBuf,
+    /// Stamp of the version last reported.
+    current: Stamp,
+    /// Stamp seen on the last poll, a change is only reported once it is
+    /// the same on two polls in a row, so files still being written are
+    /// not reloaded half way.
+    pending: Stamp,
+}
+
+impl FileWatcher {
+    /// Start watching a file in its current version.
+    /// # Arguments
+    /// * `path` Path of the file.
+    pub fn new(path: &str) -> Self {
+        let path = PathBuf::from(path);
+        let current = stamp(&path);
+        Self {
+            path,
+            current,
+            pending: current,
+        }
+    }
+
+    /// Return true if the file changed since the last version reported and
+    /// did not change since the last poll. A file that cannot be read, such
+    /// as one deleted before it is written again, does not count.
+    pub fn poll(&mut self) -> bool {
+        let now = stamp(&self.path);
+        let stable = now == self.pending;
+        self.pending = now;
+        if stable && now.is_some() && now != self.current {
+            self.current = now;
+            true
+        } else {
+            false
+        }
+    }
+}